@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use crate::dtos::validation::not_blank;
+use crate::services::auth_service::ApiScope;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct CreateApiToken {
+    #[validate(
+        length(min = 1, max = 64, message = "Must be between 1 and 64 characters long."),
+        custom(function = "not_blank")
+    )]
+    pub name: String,
+    #[validate(length(min = 1, message = "At least one scope is required."))]
+    pub scopes: Vec<ApiScope>,
+    #[validate(range(min = 1, max = 365, message = "Must be between 1 and 365 days."))]
+    pub expires_in_days: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiTokenResponse {
+    pub id: i64,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<ApiScope>,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub revoked: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreatedApiTokenResponse {
+    #[serde(flatten)]
+    pub details: ApiTokenResponse,
+    pub token: String,
+}
@@ -0,0 +1,21 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+
+/// The JSON body of every error response.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ErrorResponse {
+    pub code: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, Vec<String>>,
+}
+
+impl ErrorResponse {
+    pub fn new(code: &str, message: &str) -> ErrorResponse {
+        ErrorResponse {
+            code: code.to_string(),
+            message: message.to_string(),
+            fields: BTreeMap::new(),
+        }
+    }
+}
@@ -1,18 +1,88 @@
-use actix_jwt_auth_middleware::FromRequest;
 use serde::{Deserialize, Serialize};
+use validator::Validate;
+use crate::dtos::validation::not_blank;
+use crate::services::group_service::{GroupRole, GroupVisibility};
 
-#[derive(Serialize, Deserialize, Debug, Clone, FromRequest)]
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
 pub struct CreateGroupForm {
+    #[validate(
+        length(min = 1, max = 100, message = "Must be between 1 and 100 characters long."),
+        custom(function = "not_blank")
+    )]
     pub name: String,
+    #[validate(length(max = 1000, message = "Must be at most 1000 characters long."))]
+    pub description: Option<String>,
+    /// Defaults to `password`, which requires `password` to be set.
+    pub visibility: Option<GroupVisibility>,
+    #[validate(length(max = 128, message = "Must be at most 128 characters long."))]
+    pub password: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct UpdateGroupForm {
+    #[validate(
+        length(min = 1, max = 100, message = "Must be between 1 and 100 characters long."),
+        custom(function = "not_blank")
+    )]
+    pub name: Option<String>,
+    /// An empty description clears it.
+    #[validate(length(max = 1000, message = "Must be at most 1000 characters long."))]
+    pub description: Option<String>,
+    pub visibility: Option<GroupVisibility>,
+    #[validate(length(min = 1, max = 128, message = "Must be between 1 and 128 characters long."))]
     pub password: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GroupResponse {
-    //todo
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub visibility: GroupVisibility,
+    pub created_at: String,
+    pub is_deleted: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, FromRequest)]
+/// `GET /groups` and `GET /users/groups` filters.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GroupFilter {
+    /// Matches part of the group name, ignoring case.
+    pub q: Option<String>,
+    pub visibility: Option<GroupVisibility>,
+}
+
+/// `GET /groups/{group_id}/members` filters.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MemberFilter {
+    /// Matches part of the username, ignoring case.
+    pub q: Option<String>,
+    pub role: Option<GroupRole>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
 pub struct JoinGroup {
-    pub password: String,
-}
\ No newline at end of file
+    /// Only needed for password-protected groups.
+    #[validate(length(min = 1, max = 128, message = "Must be between 1 and 128 characters long."))]
+    pub password: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GroupMemberResponse {
+    pub user_id: i64,
+    pub username: Option<String>,
+    pub role: GroupRole,
+    pub joined_at: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GroupMembershipResponse {
+    pub group_id: i64,
+    pub name: String,
+    pub role: GroupRole,
+    pub joined_at: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct UpdateMemberRole {
+    pub role: GroupRole,
+}
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use crate::dtos::validation::not_blank;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct CreateFolderForm {
+    #[validate(
+        length(min = 1, max = 100, message = "Must be between 1 and 100 characters long."),
+        custom(function = "not_blank")
+    )]
+    pub name: String,
+    /// Creates a top level folder when left out.
+    pub parent_id: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct UpdateFolderForm {
+    #[validate(
+        length(min = 1, max = 100, message = "Must be between 1 and 100 characters long."),
+        custom(function = "not_blank")
+    )]
+    pub name: Option<String>,
+    /// Folders are listed by position among their siblings, then by name.
+    #[validate(range(min = 0, max = 100000, message = "Must be between 0 and 100000."))]
+    pub position: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct MoveFolderForm {
+    /// `null` moves the folder to the top level.
+    pub parent_id: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct MoveVideoForm {
+    /// `null` moves the video out of any folder.
+    pub folder_id: Option<i64>,
+}
+
+/// `GET /groups/{group_id}/folders` filters.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FolderFilter {
+    /// `root` or a folder id, lists every folder of the group when left out.
+    pub parent: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FolderResponse {
+    pub id: i64,
+    pub group_id: i64,
+    pub parent_id: Option<i64>,
+    pub name: String,
+    pub position: i32,
+    pub created_at: String,
+}
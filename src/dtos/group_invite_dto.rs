@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use crate::services::group_service::GroupRole;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct CreateGroupInvite {
+    pub role: Option<GroupRole>,
+    #[validate(range(min = 1, max = 720, message = "Must be between 1 and 720 hours."))]
+    pub expires_in_hours: Option<u32>,
+    #[validate(range(min = 1, max = 1000, message = "Must be between 1 and 1000."))]
+    pub max_uses: Option<u32>,
+    /// Restricts the invite to an existing account.
+    pub user_id: Option<i64>,
+    /// Restricts the invite to whoever signs in with this email address and mails them the link.
+    #[validate(email(message = "Must be a valid email address."), length(max = 254, message = "Must be at most 254 characters long."))]
+    pub email: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct AcceptGroupInvite {
+    #[validate(length(min = 1, max = 128, message = "Must be between 1 and 128 characters long."))]
+    pub code: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GroupInviteResponse {
+    pub id: i64,
+    pub group_id: i64,
+    pub prefix: String,
+    pub role: GroupRole,
+    pub invited_user_id: Option<i64>,
+    pub invited_email: Option<String>,
+    pub max_uses: Option<i32>,
+    pub uses: i32,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+    pub revoked: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreatedGroupInviteResponse {
+    #[serde(flatten)]
+    pub details: GroupInviteResponse,
+    pub code: String,
+    pub link: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PendingGroupInviteResponse {
+    pub id: i64,
+    pub group_id: i64,
+    pub group_name: String,
+    pub role: GroupRole,
+    pub expires_at: Option<String>,
+}
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use crate::services::group_join_request_service::JoinRequestStatus;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Validate)]
+pub struct CreateJoinRequest {
+    #[validate(length(max = 500, message = "Must be at most 500 characters long."))]
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct JoinRequestFilter {
+    pub status: Option<JoinRequestStatus>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JoinRequestResponse {
+    pub id: i64,
+    pub group_id: i64,
+    pub user_id: i64,
+    pub username: Option<String>,
+    pub status: JoinRequestStatus,
+    pub message: Option<String>,
+    pub created_at: String,
+    pub reviewed_at: Option<String>,
+}
@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use crate::dtos::validation::not_blank;
+
+pub const MAX_PLAYLIST_VIDEOS: usize = 1000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct CreatePlaylistForm {
+    #[validate(
+        length(min = 1, max = 100, message = "Must be between 1 and 100 characters long."),
+        custom(function = "not_blank")
+    )]
+    pub name: String,
+    #[validate(length(max = 1000, message = "Must be at most 1000 characters long."))]
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct UpdatePlaylistForm {
+    #[validate(
+        length(min = 1, max = 100, message = "Must be between 1 and 100 characters long."),
+        custom(function = "not_blank")
+    )]
+    pub name: Option<String>,
+    /// An empty description clears it.
+    #[validate(length(max = 1000, message = "Must be at most 1000 characters long."))]
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct AddPlaylistVideo {
+    pub video_id: i64,
+}
+
+/// Replaces the playlist with exactly these videos in this order.
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct ReorderPlaylist {
+    #[validate(length(max = 1000, message = "Must contain at most 1000 videos."))]
+    pub video_ids: Vec<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PlaylistResponse {
+    pub id: i64,
+    pub group_id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_by: Option<i64>,
+    pub created_at: String,
+}
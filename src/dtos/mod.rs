@@ -1,2 +1,16 @@
 pub mod user_dto;
-pub mod group_dto;
\ No newline at end of file
+pub mod group_dto;
+pub mod group_folder_dto;
+pub mod group_invite_dto;
+pub mod group_join_request_dto;
+pub mod group_playlist_dto;
+pub mod api_token_dto;
+pub mod error_dto;
+pub mod pagination_dto;
+pub mod video_dto;
+pub mod video_comment_dto;
+pub mod video_progress_dto;
+pub mod video_subtitle_dto;
+pub mod video_transcript_dto;
+pub mod search_dto;
+pub mod validation;
\ No newline at end of file
@@ -0,0 +1,125 @@
+use actix_web::{HttpRequest, HttpResponse};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_PER_PAGE: u64 = 20;
+const MAX_PER_PAGE: u64 = 100;
+const CURSOR_PREFIX: &str = "page:";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// The query parameters every list endpoint accepts: `page` or an opaque `cursor`, `per_page`
+/// (or `limit`), and `sort` with an optional `order`. Endpoint specific filters come in a separate query struct.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ListQuery {
+    pub page: Option<u64>,
+    #[serde(alias = "limit")]
+    pub per_page: Option<u64>,
+    pub cursor: Option<String>,
+    pub sort: Option<String>,
+    pub order: Option<SortOrder>,
+}
+
+impl ListQuery {
+    /// The requested page starting at 1, `None` when the cursor is malformed.
+    pub fn page(&self) -> Option<u64> {
+        match &self.cursor {
+            Some(cursor) => decode_cursor(cursor),
+            None => Some(self.page.unwrap_or(1).max(1)),
+        }
+    }
+
+    pub fn per_page(&self) -> u64 {
+        self.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE)
+    }
+}
+
+fn encode_cursor(page: u64) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}{}", CURSOR_PREFIX, page))
+}
+
+fn decode_cursor(cursor: &str) -> Option<u64> {
+    let cursor = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+    cursor.strip_prefix(CURSOR_PREFIX)?.parse().ok().filter(|page| *page >= 1)
+}
+
+/// The envelope every list endpoint responds with.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub page: u64,
+    pub per_page: u64,
+    pub total_items: u64,
+    pub total_pages: u64,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Vec<T>, page: u64, per_page: u64, total_items: u64) -> Page<T> {
+        let total_pages = total_items.div_ceil(per_page);
+
+        Page {
+            items,
+            page,
+            per_page,
+            total_items,
+            total_pages,
+            next_cursor: (page < total_pages).then(|| encode_cursor(page + 1)),
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            page: self.page,
+            per_page: self.per_page,
+            total_items: self.total_items,
+            total_pages: self.total_pages,
+            next_cursor: self.next_cursor,
+        }
+    }
+}
+
+impl<T: Serialize> Page<T> {
+    /// Responds with the page as JSON plus `Link` (first, prev, next, last) and `X-Total-Count` headers.
+    pub fn respond(self, req: &HttpRequest) -> HttpResponse {
+        let mut response = HttpResponse::Ok();
+        response.insert_header(("X-Total-Count", self.total_items.to_string()));
+        response.insert_header(("Link", self.links(req)));
+
+        response.json(self)
+    }
+
+    fn links(&self, req: &HttpRequest) -> String {
+        let last = self.total_pages.max(1);
+
+        // Keep filters and sorting, only the position changes between links.
+        let query = req.query_string()
+            .split('&')
+            .filter(|pair| !pair.is_empty() && !pair.starts_with("page=") && !pair.starts_with("cursor="))
+            .collect::<Vec<&str>>()
+            .join("&");
+
+        let link = |page: u64, rel: &str| match query.is_empty() {
+            true => format!("<{}?page={}>; rel=\"{}\"", req.path(), page, rel),
+            false => format!("<{}?{}&page={}>; rel=\"{}\"", req.path(), query, page, rel),
+        };
+
+        let mut links = vec![link(1, "first")];
+        if self.page > 1 {
+            links.push(link((self.page - 1).min(last), "prev"));
+        }
+        if self.page < self.total_pages {
+            links.push(link(self.page + 1, "next"));
+        }
+        links.push(link(last, "last"));
+
+        links.join(", ")
+    }
+}
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+use crate::services::search_service::SearchKind;
+
+/// `GET /search` parameters, paging and sorting come from `ListQuery`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SearchQuery {
+    /// Web search syntax: quoted phrases, `or` and `-excluded` words.
+    pub q: String,
+    /// Limits results to one kind, users can only be searched by admins.
+    #[serde(rename = "type")]
+    pub kind: Option<SearchKind>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SearchResultResponse {
+    pub kind: SearchKind,
+    pub id: i64,
+    pub title: String,
+    /// The group a video is shared in, the first one the caller can see.
+    pub group_id: Option<i64>,
+    pub rank: f32,
+    /// HTML escaped excerpt with the matched words wrapped in `<mark>`.
+    pub highlight: String,
+    /// Where the matched passage starts, for transcript hits.
+    pub start_seconds: Option<f64>,
+}
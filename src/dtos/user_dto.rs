@@ -1,5 +1,6 @@
-use actix_jwt_auth_middleware::FromRequest;
+use crate::dtos::validation::not_blank;
 use serde::{Deserialize, Serialize};
+use validator::Validate;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct UserResponse {
@@ -7,15 +8,132 @@ pub struct UserResponse {
     pub email: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, FromRequest)]
+/// An account as administrators see it, without credentials or second factor secrets.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AdminUserResponse {
+    pub id: i64,
+    pub username: Option<String>,
+    pub email: String,
+    pub email_verified: bool,
+    pub totp_enabled: bool,
+    pub is_deleted: bool,
+    pub created_at: String,
+}
+
+/// `GET /admin/users` filters.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct UserFilter {
+    /// Matches part of the username or email address, ignoring case.
+    pub q: Option<String>,
+    pub deleted: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
 pub struct UserLogin {
+    #[validate(email(message = "Must be a valid email address."), length(max = 254, message = "Must be at most 254 characters long."))]
     pub email: String,
+    #[validate(length(min = 1, max = 128, message = "Must be between 1 and 128 characters long."))]
     pub password: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, FromRequest)]
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
 pub struct UserRegister {
+    #[validate(
+        length(min = 3, max = 32, message = "Must be between 3 and 32 characters long."),
+        custom(function = "not_blank")
+    )]
     pub username: String,
+    #[validate(email(message = "Must be a valid email address."), length(max = 254, message = "Must be at most 254 characters long."))]
     pub email: String,
+    #[validate(length(min = 1, max = 128, message = "Must be between 1 and 128 characters long."))]
     pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    pub expires_in: u64,
+}
+
+/// Body of `POST /users/refresh` for clients that got their tokens in the body, cookie clients send nothing.
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct RefreshToken {
+    #[validate(length(min = 1, max = 2048, message = "Must be between 1 and 2048 characters long."))]
+    pub refresh_token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct VerifyEmail {
+    #[validate(length(min = 1, max = 2048, message = "Must be between 1 and 2048 characters long."))]
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct ResendVerification {
+    #[validate(email(message = "Must be a valid email address."))]
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct ForgotPassword {
+    #[validate(email(message = "Must be a valid email address."))]
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct ResetPassword {
+    #[validate(length(min = 1, max = 128, message = "Must be between 1 and 128 characters long."))]
+    pub token: String,
+    #[validate(length(min = 1, max = 128, message = "Must be between 1 and 128 characters long."))]
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MfaChallengeResponse {
+    pub mfa_required: bool,
+    pub mfa_token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct MfaLogin {
+    #[validate(length(min = 1, max = 2048, message = "Must be between 1 and 2048 characters long."))]
+    pub mfa_token: String,
+    #[validate(length(min = 6, max = 16, message = "Must be between 6 and 16 characters long."))]
+    pub code: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct TotpCode {
+    #[validate(length(min = 6, max = 16, message = "Must be between 6 and 16 characters long."))]
+    pub code: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TotpEnrollmentResponse {
+    pub secret: String,
+    pub provisioning_uri: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct MfaPolicy {
+    pub require_admin_mfa: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OidcLogin {
+    pub admin: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OidcCallback {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
 }
\ No newline at end of file
@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+use actix_web::{error, web, FromRequest, HttpRequest, HttpResponse};
+use actix_web::dev::Payload;
+use actix_web::error::{JsonPayloadError, QueryPayloadError};
+use serde::de::DeserializeOwned;
+use validator::{Validate, ValidationError, ValidationErrors, ValidationErrorsKind};
+use crate::dtos::error_dto::ErrorResponse;
+use crate::errors::AppError;
+
+/// A JSON body that is rejected with field-level errors unless it passes the DTO's `Validate` rules.
+pub struct ValidatedJson<T>(pub T);
+
+impl<T> ValidatedJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for ValidatedJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> From<ValidatedJson<T>> for web::Json<T> {
+    fn from(validated: ValidatedJson<T>) -> web::Json<T> {
+        web::Json(validated.0)
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidatedJson<T> {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output=Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let json = web::Json::<T>::from_request(req, payload);
+
+        Box::pin(async move {
+            let json = json.await?;
+
+            match json.validate() {
+                Ok(_) => Ok(ValidatedJson(json.into_inner())),
+                Err(errors) => Err(validation_error(&errors)),
+            }
+        })
+    }
+}
+
+fn validation_error(errors: &ValidationErrors) -> actix_web::Error {
+    validation_errors(errors).into()
+}
+
+/// The field-level error for DTOs validated outside of `ValidatedJson`, e.g. multipart forms.
+pub fn validation_errors(errors: &ValidationErrors) -> AppError {
+    let mut fields = BTreeMap::new();
+    collect_field_errors(errors, "", &mut fields);
+
+    AppError::Validation(fields)
+}
+
+/// Flattens nested validation errors into `parent.field` keyed messages.
+fn collect_field_errors(errors: &ValidationErrors, prefix: &str, fields: &mut BTreeMap<String, Vec<String>>) {
+    for (field, kind) in errors.errors() {
+        let path = if prefix.is_empty() { field.to_string() } else { format!("{}.{}", prefix, field) };
+
+        match kind {
+            ValidationErrorsKind::Field(field_errors) => {
+                let messages = field_errors.iter().map(|error| match &error.message {
+                    Some(message) => message.to_string(),
+                    None => format!("Invalid value ({}).", error.code),
+                });
+                fields.entry(path).or_default().extend(messages);
+            },
+            ValidationErrorsKind::Struct(nested) => collect_field_errors(nested, &path, fields),
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    collect_field_errors(nested, &format!("{}[{}]", path, index), fields);
+                }
+            },
+        }
+    }
+}
+
+/// Answers malformed or mistyped JSON bodies with the same error body as failed validation.
+pub fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let response = match &err {
+        JsonPayloadError::OverflowKnownLength { .. } | JsonPayloadError::Overflow { .. } => {
+            HttpResponse::PayloadTooLarge().json(ErrorResponse::new("payload_too_large", "The request body is too large!"))
+        },
+        JsonPayloadError::ContentType => {
+            HttpResponse::UnsupportedMediaType().json(ErrorResponse::new("unsupported_media_type", "The request body must be JSON!"))
+        },
+        JsonPayloadError::Deserialize(e) => {
+            HttpResponse::BadRequest().json(ErrorResponse::new("invalid_body", &e.to_string()))
+        },
+        _ => HttpResponse::BadRequest().json(ErrorResponse::new("invalid_body", "The request body could not be read!")),
+    };
+
+    error::InternalError::from_response(err, response).into()
+}
+
+pub fn not_blank(value: &str) -> Result<(), ValidationError> {
+    if value.trim().is_empty() {
+        return Err(ValidationError::new("blank").with_message("Must not be blank.".into()));
+    }
+
+    Ok(())
+}
+
+pub fn query_error_handler(err: QueryPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let response = HttpResponse::BadRequest().json(ErrorResponse::new("invalid_query", &err.to_string()));
+    error::InternalError::from_response(err, response).into()
+}
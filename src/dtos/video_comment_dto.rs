@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use crate::dtos::validation::not_blank;
+
+/// A comment can point at a moment of the video, or at a range when it has an end too.
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct CreateCommentForm {
+    /// `@username` mentions notify those members of the group.
+    #[validate(
+        length(min = 1, max = 5000, message = "Must be between 1 and 5000 characters long."),
+        custom(function = "not_blank")
+    )]
+    pub body: String,
+    #[validate(range(min = 0.0, max = 604800.0, message = "Must be between 0 and 604800 seconds."))]
+    pub start_seconds: Option<f64>,
+    #[validate(range(min = 0.0, max = 604800.0, message = "Must be between 0 and 604800 seconds."))]
+    pub end_seconds: Option<f64>,
+    /// Replies to another comment on the same video, replies to replies join the same thread.
+    pub parent_id: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct UpdateCommentForm {
+    #[validate(
+        length(min = 1, max = 5000, message = "Must be between 1 and 5000 characters long."),
+        custom(function = "not_blank")
+    )]
+    pub body: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CommentResponse {
+    pub id: i64,
+    pub group_id: i64,
+    pub video_id: i64,
+    /// Missing once the author's account is gone.
+    pub user_id: Option<i64>,
+    pub username: Option<String>,
+    pub parent_id: Option<i64>,
+    /// Left out for deleted comments, which stay so their replies keep a thread.
+    pub body: Option<String>,
+    pub start_seconds: Option<f64>,
+    pub end_seconds: Option<f64>,
+    pub reply_count: u64,
+    pub created_at: String,
+    pub edited_at: Option<String>,
+    pub deleted: bool,
+}
@@ -0,0 +1,92 @@
+use std::collections::{BTreeMap, HashMap};
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError};
+
+pub const MAX_TAGS: usize = 20;
+pub const MAX_TAG_LENGTH: usize = 50;
+pub const MAX_METADATA_ENTRIES: usize = 50;
+pub const MAX_METADATA_KEY_LENGTH: usize = 64;
+pub const MAX_METADATA_VALUE_LENGTH: usize = 1000;
+
+const METADATA_FILTER_PREFIX: &str = "meta.";
+
+/// `GET /groups/{group_id}/videos` filters.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct VideoFilter {
+    /// Matches part of the title or file name, ignoring case.
+    pub q: Option<String>,
+    /// Only videos carrying this tag.
+    pub tag: Option<String>,
+    /// `root` or a folder id, only videos directly in that folder.
+    pub folder: Option<String>,
+    /// Collects the remaining parameters, `meta.<key>=<value>` ones filter on the group's metadata.
+    #[serde(flatten)]
+    pub params: HashMap<String, String>,
+}
+
+impl VideoFilter {
+    pub fn metadata(&self) -> BTreeMap<&str, &str> {
+        self.params.iter()
+            .filter_map(|(key, value)| Some((key.strip_prefix(METADATA_FILTER_PREFIX)?, value.as_str())))
+            .collect()
+    }
+}
+
+fn valid_tags(tags: &[String]) -> Result<(), ValidationError> {
+    if tags.len() > MAX_TAGS {
+        return Err(ValidationError::new("too_many").with_message(format!("At most {} tags are allowed.", MAX_TAGS).into()));
+    }
+    if tags.iter().any(|tag| tag.trim().is_empty() || tag.trim().chars().count() > MAX_TAG_LENGTH) {
+        return Err(ValidationError::new("length").with_message(format!("Tags must be between 1 and {} characters long.", MAX_TAG_LENGTH).into()));
+    }
+
+    Ok(())
+}
+
+fn valid_metadata(metadata: &BTreeMap<String, String>) -> Result<(), ValidationError> {
+    if metadata.len() > MAX_METADATA_ENTRIES {
+        return Err(ValidationError::new("too_many").with_message(format!("At most {} entries are allowed.", MAX_METADATA_ENTRIES).into()));
+    }
+    if metadata.keys().any(|key| key.trim().is_empty() || key.trim().chars().count() > MAX_METADATA_KEY_LENGTH) {
+        return Err(ValidationError::new("length").with_message(format!("Keys must be between 1 and {} characters long.", MAX_METADATA_KEY_LENGTH).into()));
+    }
+    if metadata.values().any(|value| value.chars().count() > MAX_METADATA_VALUE_LENGTH) {
+        return Err(ValidationError::new("length").with_message(format!("Values must be at most {} characters long.", MAX_METADATA_VALUE_LENGTH).into()));
+    }
+
+    Ok(())
+}
+
+/// Used both as the `PATCH` body and for the extra upload fields, fields left out stay unchanged.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Validate)]
+pub struct UpdateVideoForm {
+    /// An empty title falls back to the file name.
+    #[validate(length(max = 200, message = "Must be at most 200 characters long."))]
+    pub title: Option<String>,
+    /// An empty description clears it.
+    #[validate(length(max = 5000, message = "Must be at most 5000 characters long."))]
+    pub description: Option<String>,
+    /// Replaces every tag, tags are stored lower case.
+    #[validate(custom(function = "valid_tags"))]
+    pub tags: Option<Vec<String>>,
+    /// Replaces the video's key/value metadata within the group.
+    #[validate(custom(function = "valid_metadata"))]
+    pub metadata: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VideoResponse {
+    pub id: i64,
+    pub key: String,
+    /// The uploaded file name.
+    pub name: String,
+    /// The title if one was set, the file name otherwise.
+    pub title: String,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    /// Set per group, empty outside of a group listing.
+    pub metadata: BTreeMap<String, String>,
+    /// The folder the video sits in within the group.
+    pub folder_id: Option<i64>,
+    pub uploaded_at: Option<String>,
+}
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use crate::dtos::video_dto::VideoResponse;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct UpdateProgressForm {
+    #[validate(range(min = 0.0, max = 604800.0, message = "Must be between 0 and 604800 seconds."))]
+    pub position_seconds: f64,
+    /// The player knows the length of the video, the server doesn't.
+    #[validate(range(min = 0.0, max = 604800.0, message = "Must be between 0 and 604800 seconds."))]
+    pub duration_seconds: Option<f64>,
+    /// Marks the video as watched or unwatched, otherwise it counts as watched once the position nears the end.
+    pub completed: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProgressResponse {
+    pub video_id: i64,
+    pub group_id: i64,
+    pub position_seconds: f64,
+    pub duration_seconds: Option<f64>,
+    pub completed: bool,
+    pub updated_at: String,
+    pub completed_at: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WatchHistoryResponse {
+    pub progress: ProgressResponse,
+    pub video: VideoResponse,
+}
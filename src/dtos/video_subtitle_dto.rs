@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+use crate::dtos::video_dto::VideoResponse;
+use crate::dtos::video_transcript_dto::TranscriptionJobResponse;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SubtitleResponse {
+    /// Lower-case BCP 47 tag, e.g. `en` or `pt-br`.
+    pub language: String,
+    /// The name shown in the player's caption menu, the language unless one was given.
+    pub label: String,
+    /// Serves the track as WebVTT, usable as a `<track>` source.
+    pub url: String,
+    pub updated_at: String,
+}
+
+/// `GET /groups/{group_id}/videos/{video_id}`, the video along with its caption tracks and transcript status.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VideoDetailResponse {
+    #[serde(flatten)]
+    pub video: VideoResponse,
+    pub subtitles: Vec<SubtitleResponse>,
+    /// The latest transcription job, missing if the video was never transcribed.
+    pub transcription: Option<TranscriptionJobResponse>,
+}
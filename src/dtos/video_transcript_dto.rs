@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+use crate::services::video_transcript_service::TranscriptionStatus;
+
+/// `GET /groups/{group_id}/videos/{video_id}/transcript` filters.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TranscriptFilter {
+    /// Matches part of the spoken text, ignoring case.
+    pub q: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TranscriptionJobResponse {
+    pub id: i64,
+    pub video_id: i64,
+    pub engine: String,
+    pub status: TranscriptionStatus,
+    /// Why the job failed.
+    pub error: Option<String>,
+    pub created_at: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TranscriptSegmentResponse {
+    pub position: i32,
+    pub start_seconds: f64,
+    pub end_seconds: f64,
+    pub text: String,
+}
@@ -1,23 +1,38 @@
-use actix_jwt_auth_middleware::TokenSigner;
-use actix_web::{delete, get, post, put, web, Responder};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
 use actix_web::middleware::from_fn;
-use jwt_compact::alg::Hs256;
+use aws_sdk_s3 as s3;
 use sea_orm::DatabaseConnection;
-use crate::dtos::user_dto::UserLogin;
+use crate::dtos::group_dto::GroupFilter;
+use crate::dtos::pagination_dto::ListQuery;
+use crate::dtos::user_dto::{MfaLogin, MfaPolicy, UserFilter, UserLogin};
+use crate::dtos::validation::ValidatedJson;
+use crate::endpoints::user_endpoints::{login_response, tokens_response};
+use crate::errors::AppError;
 use crate::services::auth_service::{is_admin, Role, UserClaims};
-use crate::services::user_service;
-use crate::services::user_service::{UserOperation};
+use crate::services::{admin_service, group_service, mfa_service, throttle_service, user_service};
+use crate::services::group_service::GroupOperation;
+use crate::services::token_service::TokenService;
+use crate::services::user_service::{TokenDelivery, UserOperation};
 
 pub fn admin_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/admin")
             .service(admin_login)
+            .service(admin_login_token)
+            .service(admin_login_mfa)
             .service(
                 web::scope("")
                     .wrap(from_fn(is_admin))
                     .service(get_all_users)
                     .service(delete_user)
                     .service(restore_user)
+                    .service(unlock_user)
+                    .service(get_all_groups)
+                    .service(delete_group)
+                    .service(restore_group)
+                    .service(purge_group)
+                    .service(get_mfa_policy)
+                    .service(set_mfa_policy)
             )
     );
 }
@@ -25,23 +40,105 @@ pub fn admin_routes(cfg: &mut web::ServiceConfig) {
 #[post("/login")]
 pub async fn admin_login(
     db: web::Data<DatabaseConnection>,
-    user_login: web::Json<UserLogin>,
-    token_signer: web::Data<TokenSigner<UserClaims, Hs256>>,
-) -> impl Responder {
-    user_service::login(db, user_login, token_signer, Role::Admin).await
+    user_login: ValidatedJson<UserLogin>,
+    token_service: web::Data<TokenService>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    Ok(login_response(user_service::login(db, user_login.into(), token_service, Role::Admin, TokenDelivery::Cookie, &throttle_service::client_ip(&req)).await?))
+}
+
+#[post("/login/token")]
+pub async fn admin_login_token(
+    db: web::Data<DatabaseConnection>,
+    user_login: ValidatedJson<UserLogin>,
+    token_service: web::Data<TokenService>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    Ok(login_response(user_service::login(db, user_login.into(), token_service, Role::Admin, TokenDelivery::Body, &throttle_service::client_ip(&req)).await?))
+}
+
+#[post("/login/mfa")]
+pub async fn admin_login_mfa(
+    db: web::Data<DatabaseConnection>,
+    token_service: web::Data<TokenService>,
+    form: ValidatedJson<MfaLogin>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    Ok(tokens_response(mfa_service::complete_login(db, token_service, form.into(), &throttle_service::client_ip(&req)).await?))
+}
+
+#[get("/settings/mfa")]
+pub async fn get_mfa_policy(db: web::Data<DatabaseConnection>) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(admin_service::get_mfa_policy(db).await?))
+}
+
+#[put("/settings/mfa")]
+pub async fn set_mfa_policy(db: web::Data<DatabaseConnection>, policy: ValidatedJson<MfaPolicy>) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(admin_service::set_mfa_policy(db, policy.into()).await?))
 }
 
 #[get("/users")]
-pub async fn get_all_users(db: web::Data<DatabaseConnection>) -> impl Responder {
-    user_service::get_users(db).await
+pub async fn get_all_users(
+    db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
+    filter: web::Query<UserFilter>,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(user_service::get_users(db, filter, query).await?.respond(&req))
 }
 
 #[delete("/user/{id}")]
-pub async fn delete_user(db: web::Data<DatabaseConnection>, id: web::Path<i64>) -> impl Responder {
-    user_service::modify_user_state(db, id, UserOperation::Delete).await
+pub async fn delete_user(db: web::Data<DatabaseConnection>, id: web::Path<i64>) -> Result<HttpResponse, AppError> {
+    user_service::modify_user_state(db, id, UserOperation::Delete).await?;
+    Ok(HttpResponse::Ok().finish())
 }
 
 #[put("/user/{id}")]
-pub async fn restore_user(db: web::Data<DatabaseConnection>, id: web::Path<i64>) -> impl Responder {
-    user_service::modify_user_state(db, id, UserOperation::Restore).await
-}
\ No newline at end of file
+pub async fn restore_user(db: web::Data<DatabaseConnection>, id: web::Path<i64>) -> Result<HttpResponse, AppError> {
+    user_service::modify_user_state(db, id, UserOperation::Restore).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[post("/user/{id}/unlock")]
+pub async fn unlock_user(
+    db: web::Data<DatabaseConnection>,
+    id: web::Path<i64>,
+    admin_claims: UserClaims,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    admin_service::unlock_user(db, id, admin_claims, &throttle_service::client_ip(&req)).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[get("/groups")]
+pub async fn get_all_groups(
+    db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
+    admin_claims: UserClaims,
+    filter: web::Query<GroupFilter>,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(group_service::get_groups(db, Some(admin_claims), filter, query).await?.respond(&req))
+}
+
+#[delete("/group/{id}")]
+pub async fn delete_group(db: web::Data<DatabaseConnection>, id: web::Path<i64>) -> Result<HttpResponse, AppError> {
+    group_service::modify_group_state(db, id, GroupOperation::Delete).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[put("/group/{id}")]
+pub async fn restore_group(db: web::Data<DatabaseConnection>, id: web::Path<i64>) -> Result<HttpResponse, AppError> {
+    group_service::modify_group_state(db, id, GroupOperation::Restore).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[delete("/group/{id}/purge")]
+pub async fn purge_group(
+    db: web::Data<DatabaseConnection>,
+    client: web::Data<s3::Client>,
+    id: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    group_service::purge_group(db, client, id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -1,20 +1,81 @@
-use actix_web::{get, post, web, Responder};
+use actix_web::{delete, get, patch, post, put, web, HttpRequest, HttpResponse};
+use actix_multipart::form::MultipartForm;
 use actix_web::middleware::from_fn;
+use aws_sdk_s3 as s3;
 use sea_orm::DatabaseConnection;
-use crate::dtos::group_dto::CreateGroupForm;
-use crate::services::auth_service::is_registered;
-use crate::services::group_service;
+use crate::dtos::group_dto::{CreateGroupForm, GroupFilter, MemberFilter, UpdateGroupForm, UpdateMemberRole};
+use crate::dtos::group_folder_dto::{CreateFolderForm, FolderFilter, MoveFolderForm, MoveVideoForm, UpdateFolderForm};
+use crate::dtos::group_invite_dto::CreateGroupInvite;
+use crate::dtos::group_join_request_dto::{CreateJoinRequest, JoinRequestFilter};
+use crate::dtos::group_playlist_dto::{AddPlaylistVideo, CreatePlaylistForm, ReorderPlaylist, UpdatePlaylistForm};
+use crate::dtos::pagination_dto::ListQuery;
+use crate::dtos::video_comment_dto::{CreateCommentForm, UpdateCommentForm};
+use crate::dtos::video_dto::{UpdateVideoForm, VideoFilter};
+use crate::dtos::video_progress_dto::UpdateProgressForm;
+use crate::dtos::video_transcript_dto::TranscriptFilter;
+use crate::dtos::validation::ValidatedJson;
+use crate::errors::AppError;
+use crate::services::auth_service::{is_registered, UserClaims};
+use crate::services::{group_folder_service, group_invite_service, group_join_request_service, group_playlist_service, group_service, video_comment_service, video_progress_service, video_service, video_subtitle_service, video_transcript_service};
+use crate::services::group_join_request_service::JoinRequestStatus;
+use crate::services::group_service::{GroupOperation, GroupVisibility};
 use crate::services::hash_service::hash_password;
+use crate::services::mail_service::Mailer;
+use crate::services::password_policy_service::PasswordPolicy;
+use crate::services::transcription_service::TranscriptionEngine;
+use crate::services::video_subtitle_service::SubtitleUploadForm;
 
 pub fn group_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/groups")
             .service(list_groups)
-            .service(create_group)
             .service(
                 web::scope("")
                     .wrap(from_fn(is_registered))
+                    .service(create_group)
+                    .service(update_group)
+                    .service(delete_group)
+                    .service(restore_group)
                     .service(list_group_videos)
+                    .service(get_group_video)
+                    .service(update_group_video)
+                    .service(move_group_video)
+                    .service(update_video_progress)
+                    .service(get_video_progress)
+                    .service(list_video_comments)
+                    .service(create_video_comment)
+                    .service(update_video_comment)
+                    .service(delete_video_comment)
+                    .service(list_comment_replies)
+                    .service(get_video_subtitle)
+                    .service(upload_video_subtitle)
+                    .service(delete_video_subtitle)
+                    .service(list_transcript_segments)
+                    .service(get_transcript_captions)
+                    .service(transcribe_video)
+                    .service(list_group_folders)
+                    .service(create_group_folder)
+                    .service(update_group_folder)
+                    .service(move_group_folder)
+                    .service(delete_group_folder)
+                    .service(list_group_playlists)
+                    .service(create_group_playlist)
+                    .service(update_group_playlist)
+                    .service(delete_group_playlist)
+                    .service(list_playlist_videos)
+                    .service(add_playlist_video)
+                    .service(reorder_playlist)
+                    .service(remove_playlist_video)
+                    .service(list_group_members)
+                    .service(update_group_member)
+                    .service(remove_group_member)
+                    .service(create_group_invite)
+                    .service(list_group_invites)
+                    .service(revoke_group_invite)
+                    .service(create_join_request)
+                    .service(list_join_requests)
+                    .service(approve_join_request)
+                    .service(reject_join_request)
             )
     );
 }
@@ -22,34 +83,500 @@ pub fn group_routes(cfg: &mut web::ServiceConfig) {
 #[get("")]
 pub async fn list_groups(
     db: web::Data<DatabaseConnection>,
-) -> impl Responder {
-    group_service::get_groups(db.clone()).await
+    req: HttpRequest,
+    user_claims: Option<UserClaims>,
+    filter: web::Query<GroupFilter>,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(group_service::get_groups(db, user_claims, filter, query).await?.respond(&req))
 }
 
 #[post("")]
 pub async fn create_group(
     db: web::Data<DatabaseConnection>,
-    form: web::Json<CreateGroupForm>,
-) -> impl Responder {
-    let form = form.into_inner();
+    password_policy: web::Data<PasswordPolicy>,
+    form: ValidatedJson<CreateGroupForm>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    let mut form = form.into_inner();
 
-    let hashed_password = match hash_password(&form.password.unwrap_or_default()).await {
-        Ok(hashed_password) => hashed_password,
-        Err(error) => return error
-    };
+    if form.visibility.unwrap_or(GroupVisibility::Password) == GroupVisibility::Password {
+        let password = form.password.unwrap_or_default();
+        password_policy.validate("password", &password, &[&form.name]).await?;
+        form.password = Some(hash_password(&password).await?);
+    }
 
-    let hashed_form = CreateGroupForm {
-        name: form.name.clone(),
-        password: Some(hashed_password),
-    };
+    group_service::create_group(db, form, user_claims).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[patch("/{group_id}")]
+pub async fn update_group(
+    db: web::Data<DatabaseConnection>,
+    password_policy: web::Data<PasswordPolicy>,
+    group_id: web::Path<i64>,
+    form: ValidatedJson<UpdateGroupForm>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    let mut form = form.into_inner();
+
+    if let Some(password) = &form.password {
+        let name = form.name.clone().unwrap_or_default();
+        password_policy.validate("password", password, &[&name]).await?;
+        form.password = Some(hash_password(password).await?);
+    }
+
+    group_service::update_group(db, group_id, user_claims, form).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[delete("/{group_id}")]
+pub async fn delete_group(
+    db: web::Data<DatabaseConnection>,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    group_service::require_owner(db.get_ref(), *group_id, user_claims.id).await?;
+    group_service::modify_group_state(db, group_id, GroupOperation::Delete).await?;
+    Ok(HttpResponse::Ok().finish())
+}
 
-    group_service::create_group(db, hashed_form).await
+#[post("/{group_id}/restore")]
+pub async fn restore_group(
+    db: web::Data<DatabaseConnection>,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    group_service::require_owner(db.get_ref(), *group_id, user_claims.id).await?;
+    group_service::modify_group_state(db, group_id, GroupOperation::Restore).await?;
+    Ok(HttpResponse::Ok().finish())
 }
 
 #[get("/{group_id}/videos")]
 pub async fn list_group_videos(
     db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+    filter: web::Query<VideoFilter>,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(group_service::get_group_videos(db, group_id, user_claims, filter, query).await?.respond(&req))
+}
+
+#[get("/{group_id}/videos/{video_id}")]
+pub async fn get_group_video(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(video_service::get_video(db, path, user_claims).await?))
+}
+
+/// Title, description and tags are shared by every group the video is in, metadata belongs to this group.
+#[patch("/{group_id}/videos/{video_id}")]
+pub async fn update_group_video(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: ValidatedJson<UpdateVideoForm>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(video_service::update_video(db, path, user_claims, form.into()).await?))
+}
+
+#[put("/{group_id}/videos/{video_id}/folder")]
+pub async fn move_group_video(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: ValidatedJson<MoveVideoForm>,
+) -> Result<HttpResponse, AppError> {
+    group_folder_service::move_video(db, path, user_claims, form.into()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Reported by the player while a member watches, the position is kept across groups and sessions.
+#[put("/{group_id}/videos/{video_id}/progress")]
+pub async fn update_video_progress(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: ValidatedJson<UpdateProgressForm>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(video_progress_service::update_progress(db, path, user_claims, form.into()).await?))
+}
+
+#[get("/{group_id}/videos/{video_id}/progress")]
+pub async fn get_video_progress(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(video_progress_service::get_progress(db, path, user_claims).await?))
+}
+
+/// Top-level comments, `sort=timestamp` orders them along the video.
+#[get("/{group_id}/videos/{video_id}/comments")]
+pub async fn list_video_comments(
+    db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(video_comment_service::list_comments(db, path, user_claims, query).await?.respond(&req))
+}
+
+#[post("/{group_id}/videos/{video_id}/comments")]
+pub async fn create_video_comment(
+    db: web::Data<DatabaseConnection>,
+    mailer: web::Data<dyn Mailer>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: ValidatedJson<CreateCommentForm>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Created().json(video_comment_service::create_comment(db, mailer, path, user_claims, form.into()).await?))
+}
+
+#[patch("/{group_id}/videos/{video_id}/comments/{comment_id}")]
+pub async fn update_video_comment(
+    db: web::Data<DatabaseConnection>,
+    mailer: web::Data<dyn Mailer>,
+    path: web::Path<(i64, i64, i64)>,
+    user_claims: UserClaims,
+    form: ValidatedJson<UpdateCommentForm>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(video_comment_service::update_comment(db, mailer, path, user_claims, form.into()).await?))
+}
+
+#[delete("/{group_id}/videos/{video_id}/comments/{comment_id}")]
+pub async fn delete_video_comment(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    video_comment_service::delete_comment(db, path, user_claims).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/{group_id}/videos/{video_id}/comments/{comment_id}/replies")]
+pub async fn list_comment_replies(
+    db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
+    path: web::Path<(i64, i64, i64)>,
+    user_claims: UserClaims,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(video_comment_service::list_replies(db, path, user_claims, query).await?.respond(&req))
+}
+
+#[get("/{group_id}/videos/{video_id}/subtitles/{language}")]
+pub async fn get_video_subtitle(
+    db: web::Data<DatabaseConnection>,
+    client: web::Data<s3::Client>,
+    path: web::Path<(i64, i64, String)>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    let subtitle = video_subtitle_service::serve_subtitle(db, client, path, user_claims).await?;
+    Ok(HttpResponse::Ok().content_type(video_subtitle_service::WEBVTT_CONTENT_TYPE).body(subtitle))
+}
+
+/// Multipart upload of a `.vtt` or `.srt` file with an optional `label`, replacing the language's current track.
+#[put("/{group_id}/videos/{video_id}/subtitles/{language}")]
+pub async fn upload_video_subtitle(
+    db: web::Data<DatabaseConnection>,
+    client: web::Data<s3::Client>,
+    path: web::Path<(i64, i64, String)>,
+    user_claims: UserClaims,
+    form: MultipartForm<SubtitleUploadForm>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(video_subtitle_service::upload_subtitle(db, client, path, user_claims, form).await?))
+}
+
+#[delete("/{group_id}/videos/{video_id}/subtitles/{language}")]
+pub async fn delete_video_subtitle(
+    db: web::Data<DatabaseConnection>,
+    client: web::Data<s3::Client>,
+    path: web::Path<(i64, i64, String)>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    video_subtitle_service::delete_subtitle(db, client, path, user_claims).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/{group_id}/videos/{video_id}/transcript")]
+pub async fn list_transcript_segments(
+    db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    filter: web::Query<TranscriptFilter>,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(video_transcript_service::list_segments(db, path, user_claims, filter, query).await?.respond(&req))
+}
+
+#[get("/{group_id}/videos/{video_id}/transcript/captions")]
+pub async fn get_transcript_captions(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    let captions = video_transcript_service::serve_captions(db, path, user_claims).await?;
+    Ok(HttpResponse::Ok().content_type(video_subtitle_service::WEBVTT_CONTENT_TYPE).body(captions))
+}
+
+/// Queues a new transcription of the video, the previous transcript stays until it completes.
+#[post("/{group_id}/videos/{video_id}/transcript")]
+pub async fn transcribe_video(
+    db: web::Data<DatabaseConnection>,
+    client: web::Data<s3::Client>,
+    engine: web::Data<dyn TranscriptionEngine>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Accepted().json(video_transcript_service::retranscribe(db, client, engine, path, user_claims).await?))
+}
+
+#[get("/{group_id}/folders")]
+pub async fn list_group_folders(
+    db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+    filter: web::Query<FolderFilter>,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(group_folder_service::list_folders(db, group_id, user_claims, filter, query).await?.respond(&req))
+}
+
+#[post("/{group_id}/folders")]
+pub async fn create_group_folder(
+    db: web::Data<DatabaseConnection>,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+    form: ValidatedJson<CreateFolderForm>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Created().json(group_folder_service::create_folder(db, group_id, user_claims, form.into()).await?))
+}
+
+#[patch("/{group_id}/folders/{folder_id}")]
+pub async fn update_group_folder(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: ValidatedJson<UpdateFolderForm>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(group_folder_service::update_folder(db, path, user_claims, form.into()).await?))
+}
+
+#[put("/{group_id}/folders/{folder_id}/parent")]
+pub async fn move_group_folder(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: ValidatedJson<MoveFolderForm>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(group_folder_service::move_folder(db, path, user_claims, form.into()).await?))
+}
+
+#[delete("/{group_id}/folders/{folder_id}")]
+pub async fn delete_group_folder(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    group_folder_service::delete_folder(db, path, user_claims).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/{group_id}/playlists")]
+pub async fn list_group_playlists(
+    db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(group_playlist_service::list_playlists(db, group_id, user_claims, query).await?.respond(&req))
+}
+
+#[post("/{group_id}/playlists")]
+pub async fn create_group_playlist(
+    db: web::Data<DatabaseConnection>,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+    form: ValidatedJson<CreatePlaylistForm>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Created().json(group_playlist_service::create_playlist(db, group_id, user_claims, form.into()).await?))
+}
+
+#[patch("/{group_id}/playlists/{playlist_id}")]
+pub async fn update_group_playlist(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: ValidatedJson<UpdatePlaylistForm>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(group_playlist_service::update_playlist(db, path, user_claims, form.into()).await?))
+}
+
+#[delete("/{group_id}/playlists/{playlist_id}")]
+pub async fn delete_group_playlist(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    group_playlist_service::delete_playlist(db, path, user_claims).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/{group_id}/playlists/{playlist_id}/videos")]
+pub async fn list_playlist_videos(
+    db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(group_playlist_service::list_playlist_videos(db, path, user_claims, query).await?.respond(&req))
+}
+
+#[post("/{group_id}/playlists/{playlist_id}/videos")]
+pub async fn add_playlist_video(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: ValidatedJson<AddPlaylistVideo>,
+) -> Result<HttpResponse, AppError> {
+    group_playlist_service::add_playlist_video(db, path, user_claims, form.into()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[put("/{group_id}/playlists/{playlist_id}/videos")]
+pub async fn reorder_playlist(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: ValidatedJson<ReorderPlaylist>,
+) -> Result<HttpResponse, AppError> {
+    group_playlist_service::reorder_playlist(db, path, user_claims, form.into()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[delete("/{group_id}/playlists/{playlist_id}/videos/{video_id}")]
+pub async fn remove_playlist_video(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    group_playlist_service::remove_playlist_video(db, path, user_claims).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/{group_id}/members")]
+pub async fn list_group_members(
+    db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+    filter: web::Query<MemberFilter>,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(group_service::get_group_users(db, group_id, user_claims, filter, query).await?.respond(&req))
+}
+
+#[patch("/{group_id}/members/{user_id}")]
+pub async fn update_group_member(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: ValidatedJson<UpdateMemberRole>,
+) -> Result<HttpResponse, AppError> {
+    group_service::update_member_role(db, path, user_claims, form.into()).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[delete("/{group_id}/members/{user_id}")]
+pub async fn remove_group_member(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    group_service::remove_member(db, path, user_claims).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[post("/{group_id}/invites")]
+pub async fn create_group_invite(
+    db: web::Data<DatabaseConnection>,
+    mailer: web::Data<dyn Mailer>,
     group_id: web::Path<i64>,
-) -> impl Responder {
-    group_service::get_group_videos(db, group_id).await
-}
\ No newline at end of file
+    user_claims: UserClaims,
+    form: ValidatedJson<CreateGroupInvite>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Created().json(group_invite_service::create_invite(db, mailer, group_id, user_claims, form.into()).await?))
+}
+
+#[get("/{group_id}/invites")]
+pub async fn list_group_invites(
+    db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(group_invite_service::list_invites(db, group_id, user_claims, query).await?.respond(&req))
+}
+
+#[delete("/{group_id}/invites/{invite_id}")]
+pub async fn revoke_group_invite(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    group_invite_service::revoke_invite(db, path, user_claims).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[post("/{group_id}/join-requests")]
+pub async fn create_join_request(
+    db: web::Data<DatabaseConnection>,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+    form: ValidatedJson<CreateJoinRequest>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Created().json(group_join_request_service::create_request(db, group_id, user_claims, form.into()).await?))
+}
+
+#[get("/{group_id}/join-requests")]
+pub async fn list_join_requests(
+    db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+    filter: web::Query<JoinRequestFilter>,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(group_join_request_service::list_requests(db, group_id, user_claims, filter, query).await?.respond(&req))
+}
+
+#[post("/{group_id}/join-requests/{request_id}/approve")]
+pub async fn approve_join_request(
+    db: web::Data<DatabaseConnection>,
+    mailer: web::Data<dyn Mailer>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(group_join_request_service::review_request(db, mailer, path, user_claims, JoinRequestStatus::Approved).await?))
+}
+
+#[post("/{group_id}/join-requests/{request_id}/reject")]
+pub async fn reject_join_request(
+    db: web::Data<DatabaseConnection>,
+    mailer: web::Data<dyn Mailer>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(group_join_request_service::review_request(db, mailer, path, user_claims, JoinRequestStatus::Rejected).await?))
+}
@@ -1,4 +1,7 @@
 pub mod user_endpoints;
 pub mod admin_endpoints;
 pub mod storage_endpoints;
-pub mod group_endpoints;
\ No newline at end of file
+pub mod group_endpoints;
+pub mod oidc_endpoints;
+pub mod well_known_endpoints;
+pub mod search_endpoints;
\ No newline at end of file
@@ -0,0 +1,286 @@
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use actix_web::http::header::LOCATION;
+use sea_orm::DatabaseConnection;
+use crate::dtos::user_dto::{OidcCallback, OidcLogin};
+use crate::errors::AppError;
+use crate::services::oidc_service;
+use crate::services::oidc_service::{OidcClient, OidcRedirect};
+use crate::services::token_service::TokenService;
+
+pub fn oidc_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/auth/oidc")
+            .service(oidc_login)
+            .service(oidc_callback)
+    );
+}
+
+/// SSO is optional, so the client is only registered when it's configured. Registering the `Option` itself
+/// would hide the client from the handlers, which look it up as `Data<OidcClient>`.
+pub fn register_client(cfg: &mut web::ServiceConfig, oidc_client: Option<web::Data<OidcClient>>) {
+    if let Some(oidc_client) = oidc_client {
+        cfg.app_data(oidc_client);
+    }
+}
+
+fn redirect_response(redirect: OidcRedirect) -> HttpResponse {
+    let mut response = HttpResponse::Found();
+    response.insert_header((LOCATION, redirect.location));
+    for cookie in redirect.cookies {
+        response.cookie(cookie);
+    }
+    response.finish()
+}
+
+#[get("/login")]
+pub async fn oidc_login(
+    oidc: Option<web::Data<OidcClient>>,
+    token_service: web::Data<TokenService>,
+    query: web::Query<OidcLogin>,
+) -> Result<HttpResponse, AppError> {
+    let oidc = oidc.ok_or(AppError::not_found("Single sign-on is not configured!"))?;
+    Ok(redirect_response(oidc_service::begin_login(oidc, token_service, query).await?))
+}
+
+#[get("/callback")]
+pub async fn oidc_callback(
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    oidc: Option<web::Data<OidcClient>>,
+    token_service: web::Data<TokenService>,
+    query: web::Query<OidcCallback>,
+) -> Result<HttpResponse, AppError> {
+    let oidc = oidc.ok_or(AppError::not_found("Single sign-on is not configured!"))?;
+    Ok(redirect_response(oidc_service::complete_login(req, db, oidc, token_service, query).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use actix_web::cookie::Cookie;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App, HttpServer};
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use ed25519_dalek::pkcs8::EncodePrivateKey;
+    use ed25519_dalek::pkcs8::spki::der::pem::LineEnding;
+    use jsonwebtoken::Algorithm;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+    use serde_json::json;
+    use sha2::{Digest, Sha256};
+    use crate::entities::users;
+    use crate::services::auth_service::{Role, TokenType, ACCESS_TOKEN_COOKIE, REFRESH_TOKEN_COOKIE};
+    use crate::services::oidc_service::OidcConfig;
+    use super::*;
+
+    const CLIENT_ID: &str = "lockbox";
+    const REDIRECT_URI: &str = "https://lockbox.test/auth/oidc/callback";
+    const AUTHORIZATION_CODE: &str = "authorization-code";
+
+    fn token_service(kid: &str, seed: u8) -> TokenService {
+        let pem = ed25519_dalek::SigningKey::from_bytes(&[seed; 32])
+            .to_pkcs8_pem(LineEnding::LF)
+            .unwrap();
+        TokenService::new(kid, Algorithm::EdDSA, &pem).unwrap()
+    }
+
+    /// What the IdP remembers from the authorization request, the browser would carry it there.
+    #[derive(Default)]
+    struct Authorization {
+        nonce: String,
+        code_challenge: String,
+    }
+
+    struct MockIdp {
+        issuer: String,
+        signer: TokenService,
+        authorization: Mutex<Authorization>,
+    }
+
+    async fn discovery(idp: web::Data<MockIdp>) -> HttpResponse {
+        HttpResponse::Ok().json(json!({
+            "issuer": idp.issuer,
+            "authorization_endpoint": format!("{}/authorize", idp.issuer),
+            "token_endpoint": format!("{}/token", idp.issuer),
+            "jwks_uri": format!("{}/jwks", idp.issuer),
+        }))
+    }
+
+    async fn jwks(idp: web::Data<MockIdp>) -> HttpResponse {
+        HttpResponse::Ok().json(idp.signer.jwks())
+    }
+
+    async fn token(idp: web::Data<MockIdp>, form: web::Form<HashMap<String, String>>) -> HttpResponse {
+        let authorization = idp.authorization.lock().unwrap();
+        let code_verifier = form.get("code_verifier").map(String::as_str).unwrap_or_default();
+
+        let valid = form.get("grant_type").map(String::as_str) == Some("authorization_code")
+            && form.get("code").map(String::as_str) == Some(AUTHORIZATION_CODE)
+            && form.get("client_id").map(String::as_str) == Some(CLIENT_ID)
+            && form.get("redirect_uri").map(String::as_str) == Some(REDIRECT_URI)
+            && URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes())) == authorization.code_challenge;
+        if !valid {
+            return HttpResponse::BadRequest().json(json!({ "error": "invalid_grant" }));
+        }
+
+        let id_token = idp.signer.create_signed_token(&json!({
+            "iss": idp.issuer,
+            "aud": CLIENT_ID,
+            "sub": "idp-user-1",
+            "email": "Jane@Example.com",
+            "email_verified": true,
+            "nonce": authorization.nonce,
+            "amr": ["pwd", "otp"],
+        }), Duration::from_secs(300)).unwrap();
+
+        HttpResponse::Ok().json(json!({ "id_token": id_token, "token_type": "Bearer", "access_token": "idp-access-token" }))
+    }
+
+    /// Serves discovery, the key set and the token endpoint on a random local port.
+    fn start_idp() -> web::Data<MockIdp> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let idp = web::Data::new(MockIdp {
+            issuer: format!("http://{}", listener.local_addr().unwrap()),
+            signer: token_service("idp-key", 7),
+            authorization: Mutex::new(Authorization::default()),
+        });
+
+        let app_idp = idp.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(app_idp.clone())
+                .route("/.well-known/openid-configuration", web::get().to(discovery))
+                .route("/jwks", web::get().to(jwks))
+                .route("/token", web::post().to(token))
+        })
+            .workers(1)
+            .listen(listener)
+            .unwrap()
+            .run();
+        actix_web::rt::spawn(server);
+
+        idp
+    }
+
+    fn oidc_client(issuer: &str, trust_idp_mfa: bool) -> web::Data<OidcClient> {
+        web::Data::new(OidcClient::new(OidcConfig {
+            issuer: issuer.to_string(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: None,
+            redirect_uri: REDIRECT_URI.to_string(),
+            scopes: "openid email".to_string(),
+            groups_claim: "groups".to_string(),
+            admin_groups: Vec::new(),
+            post_login_redirect: "/app".to_string(),
+            trust_idp_mfa,
+        }))
+    }
+
+    fn existing_user(totp_enabled: bool) -> users::Model {
+        users::Model {
+            id: 42,
+            username: Some("jane".to_string()),
+            email: "jane@example.com".to_string(),
+            password: None,
+            created_at: chrono::Utc::now().fixed_offset(),
+            is_deleted: false,
+            email_verified: true,
+            session_version: 3,
+            totp_secret: totp_enabled.then(|| "JBSWY3DPEHPK3PXP".to_string()),
+            totp_enabled,
+            totp_last_step: None,
+            is_admin: false,
+        }
+    }
+
+    /// Runs the whole browser flow against the mock IdP and returns the callback's redirect.
+    async fn sign_in(user: users::Model, trust_idp_mfa: bool, tokens: web::Data<TokenService>) -> (String, Vec<Cookie<'static>>) {
+        let idp = start_idp();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![user]])
+            .into_connection();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(db))
+                .app_data(tokens)
+                .configure(|cfg| register_client(cfg, Some(oidc_client(&idp.issuer, trust_idp_mfa))))
+                .configure(oidc_routes)
+        ).await;
+
+        let response = test::call_service(&app, test::TestRequest::get().uri("/auth/oidc/login").to_request()).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+
+        let location = reqwest::Url::parse(response.headers().get(LOCATION).unwrap().to_str().unwrap()).unwrap();
+        assert_eq!(location.path(), "/authorize");
+        let query: HashMap<String, String> = location.query_pairs().into_owned().collect();
+        assert_eq!(query["client_id"], CLIENT_ID);
+        assert_eq!(query["code_challenge_method"], "S256");
+
+        *idp.authorization.lock().unwrap() = Authorization {
+            nonce: query["nonce"].clone(),
+            code_challenge: query["code_challenge"].clone(),
+        };
+        let flow_cookie = response.response().cookies().find(|cookie| cookie.name() == "oidc_flow").unwrap().into_owned();
+
+        let callback = format!("/auth/oidc/callback?code={}&state={}", AUTHORIZATION_CODE, query["state"]);
+        let response = test::call_service(&app, test::TestRequest::get().uri(&callback).cookie(flow_cookie).to_request()).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+
+        let location = response.headers().get(LOCATION).unwrap().to_str().unwrap().to_string();
+        let cookies = response.response().cookies().map(Cookie::into_owned).collect();
+        (location, cookies)
+    }
+
+    fn find_cookie<'a>(cookies: &'a [Cookie<'static>], name: &str) -> Option<&'a Cookie<'static>> {
+        cookies.iter().find(|cookie| cookie.name() == name)
+    }
+
+    #[actix_web::test]
+    async fn sso_round_trip_signs_in_the_matching_user() {
+        let tokens = web::Data::new(token_service("lockbox-key", 1));
+        let (location, cookies) = sign_in(existing_user(false), false, tokens.clone()).await;
+
+        assert_eq!(location, "/app");
+        assert!(find_cookie(&cookies, REFRESH_TOKEN_COOKIE).is_some());
+
+        let claims = tokens.verify_user_token(find_cookie(&cookies, ACCESS_TOKEN_COOKIE).unwrap().value(), TokenType::Access).unwrap();
+        assert_eq!(claims.id, 42);
+        assert_eq!(claims.session_version, 3);
+        assert!(matches!(claims.role, Role::RegisteredUser));
+    }
+
+    #[actix_web::test]
+    async fn sso_asks_for_the_local_second_factor() {
+        let tokens = web::Data::new(token_service("lockbox-key", 1));
+        let (location, cookies) = sign_in(existing_user(true), false, tokens).await;
+
+        assert!(location.starts_with("/app#mfa_token="));
+        assert!(find_cookie(&cookies, ACCESS_TOKEN_COOKIE).is_none());
+        assert!(find_cookie(&cookies, REFRESH_TOKEN_COOKIE).is_none());
+    }
+
+    #[actix_web::test]
+    async fn sso_accepts_a_trusted_idp_second_factor() {
+        let tokens = web::Data::new(token_service("lockbox-key", 1));
+        let (location, cookies) = sign_in(existing_user(true), true, tokens).await;
+
+        assert_eq!(location, "/app");
+        assert!(find_cookie(&cookies, ACCESS_TOKEN_COOKIE).is_some());
+    }
+
+    #[actix_web::test]
+    async fn sso_is_not_found_when_unconfigured() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(token_service("lockbox-key", 1)))
+                .configure(|cfg| register_client(cfg, None))
+                .configure(oidc_routes)
+        ).await;
+
+        let response = test::call_service(&app, test::TestRequest::get().uri("/auth/oidc/login").to_request()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,26 @@
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use sea_orm::DatabaseConnection;
+use crate::dtos::pagination_dto::ListQuery;
+use crate::dtos::search_dto::SearchQuery;
+use crate::errors::AppError;
+use crate::services::auth_service::UserClaims;
+use crate::services::search_service;
+
+pub fn search_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/search")
+            .service(search)
+    );
+}
+
+/// Open to registered users and admins alike, only admins get user results.
+#[get("")]
+pub async fn search(
+    db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
+    user_claims: UserClaims,
+    search: web::Query<SearchQuery>,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(search_service::search(db, user_claims, search, query).await?.respond(&req))
+}
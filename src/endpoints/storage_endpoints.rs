@@ -2,8 +2,11 @@ use actix_multipart::form::MultipartForm;
 use actix_web::{get, post, web, HttpResponse};
 use aws_sdk_s3 as s3;
 use sea_orm::DatabaseConnection;
+use crate::errors::AppError;
+use crate::services::auth_service::UserClaims;
 use crate::services::storage_service;
 use crate::services::storage_service::UploadForm;
+use crate::services::transcription_service::TranscriptionEngine;
 
 pub fn storage_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -16,16 +19,220 @@ pub fn storage_routes(cfg: &mut web::ServiceConfig) {
 #[post("/upload/video/{group_id}")]
 pub async fn upload_file(
     client: web::Data<s3::Client>,
+    engine: web::Data<dyn TranscriptionEngine>,
+    // Extracted ahead of the form so anonymous uploads are turned away before the file is buffered.
+    user_claims: UserClaims,
     MultipartForm(form): MultipartForm<UploadForm>,
     db: web::Data<DatabaseConnection>,
     group_id: web::Path<i64>,
-) -> HttpResponse {
-    storage_service::upload_video(client, MultipartForm(form), db, group_id).await
-        .unwrap_or(HttpResponse::InternalServerError().finish())
+) -> Result<HttpResponse, AppError> {
+    storage_service::upload_video(client, engine, MultipartForm(form), db, group_id, user_claims).await?;
+    Ok(HttpResponse::Ok().body("Upload completed successfully!"))
 }
 
 #[get("/playback/{key}")]
-pub async fn playback(client: web::Data<s3::Client>, key: web::Path<String>) -> HttpResponse {
-    storage_service::serve_video(client, key).await
-        .unwrap_or(HttpResponse::InternalServerError().body("OUTER"))
-}
\ No newline at end of file
+pub async fn playback(
+    client: web::Data<s3::Client>,
+    db: web::Data<DatabaseConnection>,
+    key: web::Path<String>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    let video = storage_service::serve_video(client, db, key, user_claims).await?;
+    Ok(HttpResponse::Ok().content_type("video/mp4").body(video))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use actix_web::http::header::{AUTHORIZATION, CONTENT_TYPE};
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use aws_config::{BehaviorVersion, Region};
+    use chrono::Utc;
+    use ed25519_dalek::pkcs8::EncodePrivateKey;
+    use ed25519_dalek::pkcs8::spki::der::pem::LineEnding;
+    use jsonwebtoken::Algorithm;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+    use crate::entities::{api_tokens, group_user, group_video, groups, users};
+    use crate::services::auth_service::{Role, TokenType};
+    use crate::services::group_service::GroupVisibility;
+    use crate::services::token_service::TokenService;
+    use crate::services::transcription_service::TranscriptionFuture;
+    use super::*;
+
+    const USER_ID: i64 = 30;
+    const BOUNDARY: &str = "lockbox-boundary";
+
+    /// Counts the videos it's asked to transcribe and never finds any speech.
+    #[derive(Default)]
+    struct CountingEngine {
+        calls: AtomicUsize,
+    }
+
+    impl TranscriptionEngine for CountingEngine {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        fn transcribe<'a>(&'a self, _media: &'a Path) -> TranscriptionFuture<'a> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async { Ok(Vec::new()) })
+        }
+    }
+
+    fn token_service() -> TokenService {
+        let pem = ed25519_dalek::SigningKey::from_bytes(&[7; 32])
+            .to_pkcs8_pem(LineEnding::LF)
+            .unwrap();
+        TokenService::new("storage-test", Algorithm::EdDSA, &pem).unwrap()
+    }
+
+    fn client() -> s3::Client {
+        let config = s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("auto"))
+            .build();
+        s3::Client::from_conf(config)
+    }
+
+    fn user() -> users::Model {
+        users::Model {
+            id: USER_ID,
+            username: Some("jane".to_string()),
+            email: "jane@example.com".to_string(),
+            password: None,
+            created_at: Utc::now().fixed_offset(),
+            is_deleted: false,
+            email_verified: true,
+            session_version: 0,
+            totp_secret: None,
+            totp_enabled: false,
+            totp_last_step: None,
+            is_admin: false,
+        }
+    }
+
+    fn group() -> groups::Model {
+        groups::Model {
+            id: 1,
+            name: "film-club".to_string(),
+            password: None,
+            created_at: Utc::now().fixed_offset(),
+            is_deleted: false,
+            description: None,
+            visibility: GroupVisibility::Public.as_str().to_string(),
+        }
+    }
+
+    /// A token used moments ago, so authenticating it doesn't also record its use.
+    fn api_token(scopes: &str) -> (api_tokens::Model, Option<users::Model>) {
+        let now = Utc::now().fixed_offset();
+        let api_token = api_tokens::Model {
+            id: 1,
+            user_id: USER_ID,
+            name: "player".to_string(),
+            token_hash: String::new(),
+            prefix: "lbx_abcd".to_string(),
+            scopes: scopes.to_string(),
+            created_at: now,
+            expires_at: None,
+            last_used_at: Some(now),
+            revoked_at: None,
+        };
+        (api_token, Some(user()))
+    }
+
+    fn video_form() -> Vec<u8> {
+        format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"clip.mp4\"\r\nContent-Type: video/mp4\r\n\r\nnot really a video\r\n--{b}--\r\n",
+            b = BOUNDARY,
+        ).into_bytes()
+    }
+
+    /// Posts a video to group 1 and returns the status along with how many transcriptions were started.
+    async fn upload(db: MockDatabase, authorization: Option<String>) -> (StatusCode, usize) {
+        let engine = Arc::new(CountingEngine::default());
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(db.into_connection()))
+                .app_data(web::Data::new(token_service()))
+                .app_data(web::Data::new(client()))
+                .app_data(web::Data::from(engine.clone() as Arc<dyn TranscriptionEngine>))
+                .configure(storage_routes)
+        ).await;
+
+        let mut request = test::TestRequest::post()
+            .uri("/storage/upload/video/1")
+            .insert_header((CONTENT_TYPE, format!("multipart/form-data; boundary={}", BOUNDARY)))
+            .set_payload(video_form());
+        if let Some(authorization) = authorization {
+            request = request.insert_header((AUTHORIZATION, authorization));
+        }
+
+        let response = test::call_service(&app, request.to_request()).await;
+        actix_web::rt::task::yield_now().await;
+
+        (response.status(), engine.calls.load(Ordering::SeqCst))
+    }
+
+    #[actix_web::test]
+    async fn anonymous_uploads_are_rejected_before_anything_is_queued() {
+        let (status, transcriptions) = upload(MockDatabase::new(DatabaseBackend::Postgres), None).await;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(transcriptions, 0);
+    }
+
+    #[actix_web::test]
+    async fn non_members_cant_upload_or_queue_transcriptions() {
+        let claims = UserClaims { id: USER_ID, role: Role::RegisteredUser, session_version: 0, scopes: None };
+        let access_token = token_service().create_user_token(&claims, TokenType::Access).unwrap();
+        // The session check, the group and then no membership, any further query would fail the mock.
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![user()]])
+            .append_query_results([vec![group()]])
+            .append_query_results([Vec::<group_user::Model>::new()]);
+
+        let (status, transcriptions) = upload(db, Some(format!("Bearer {}", access_token))).await;
+
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(transcriptions, 0);
+    }
+
+    #[actix_web::test]
+    async fn read_scoped_api_tokens_cant_upload() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![api_token("videos:read")]]);
+
+        let (status, transcriptions) = upload(db, Some("Bearer lbx_read-only".to_string())).await;
+
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(transcriptions, 0);
+    }
+
+    #[actix_web::test]
+    async fn read_scoped_api_tokens_reach_playback() {
+        // Past the scope check the video lookup runs, and finds nothing.
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![api_token("videos:read")]])
+            .append_query_results([Vec::<group_video::Model>::new()])
+            .into_connection();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(db))
+                .app_data(web::Data::new(token_service()))
+                .app_data(web::Data::new(client()))
+                .configure(storage_routes)
+        ).await;
+
+        let request = test::TestRequest::get()
+            .uri("/storage/playback/abcdefghij.mp4")
+            .insert_header((AUTHORIZATION, "Bearer lbx_read-only"))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -1,67 +1,363 @@
-use actix_jwt_auth_middleware::TokenSigner;
-use actix_web::{post, get, web, Responder};
+use actix_web::{delete, post, get, web, HttpRequest, HttpResponse};
 use actix_web::middleware::from_fn;
-use jwt_compact::alg::Hs256;
 use sea_orm::DatabaseConnection;
-use crate::dtos::group_dto::JoinGroup;
-use crate::dtos::user_dto::{UserLogin, UserRegister};
-use crate::services::{hash_service, user_service};
-use crate::services::auth_service::{is_registered, Role, UserClaims};
+use crate::dtos::api_token_dto::CreateApiToken;
+use crate::dtos::group_dto::{GroupFilter, JoinGroup};
+use crate::dtos::group_invite_dto::AcceptGroupInvite;
+use crate::dtos::group_join_request_dto::JoinRequestFilter;
+use crate::dtos::pagination_dto::ListQuery;
+use crate::dtos::user_dto::{ForgotPassword, MfaLogin, RefreshToken, ResendVerification, ResetPassword, TotpCode, UserLogin, UserRegister, VerifyEmail};
+use crate::dtos::validation::ValidatedJson;
+use crate::errors::AppError;
+use crate::services::{api_token_service, group_invite_service, group_join_request_service, group_service, hash_service, mfa_service, password_reset_service, throttle_service, user_service, verification_service, video_progress_service};
+use crate::services::auth_service::{is_registered, Role, UserClaims, REFRESH_TOKEN_COOKIE};
+use crate::services::mail_service::Mailer;
+use crate::services::password_policy_service::PasswordPolicy;
+use crate::services::token_service::TokenService;
+use crate::services::user_service::{IssuedTokens, LoginOutcome, TokenDelivery};
 
 pub fn user_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/users")
             .service(login)
+            .service(login_token)
+            .service(login_mfa)
+            .service(refresh)
             .service(register_user)
+            .service(verify_email)
+            .service(resend_verification)
+            .service(forgot_password)
+            .service(reset_password)
             .service(
                 web::scope("")
                     .wrap(from_fn(is_registered))
                     .service(get_current_user)
                     .service(join_group)
+                    .service(list_my_groups)
+                    .service(leave_group)
+                    .service(list_group_invites)
+                    .service(accept_group_invite)
+                    .service(accept_direct_group_invite)
+                    .service(list_join_requests)
+                    .service(list_watch_history)
+                    .service(list_continue_watching)
+                    .service(delete_watch_history_entry)
+                    .service(enroll_totp)
+                    .service(confirm_totp)
+                    .service(regenerate_recovery_codes)
+                    .service(disable_totp)
+                    .service(create_api_token)
+                    .service(list_api_tokens)
+                    .service(revoke_api_token)
             )
     );
 }
 
+pub fn tokens_response(tokens: IssuedTokens) -> HttpResponse {
+    match tokens {
+        IssuedTokens::Cookies(cookies) => {
+            let mut response = HttpResponse::Ok();
+            for cookie in cookies {
+                response.cookie(cookie);
+            }
+            response.finish()
+        },
+        IssuedTokens::Body(tokens) => HttpResponse::Ok().json(tokens),
+    }
+}
+
+pub fn login_response(outcome: LoginOutcome) -> HttpResponse {
+    match outcome {
+        LoginOutcome::MfaRequired(challenge) => HttpResponse::Ok().json(challenge),
+        LoginOutcome::Authenticated(tokens) => tokens_response(tokens),
+    }
+}
+
 #[post("/register")]
 pub async fn register_user(
     db: web::Data<DatabaseConnection>, 
-    new_user: web::Json<UserRegister>
-) -> impl Responder {
+    token_service: web::Data<TokenService>,
+    mailer: web::Data<dyn Mailer>,
+    password_policy: web::Data<PasswordPolicy>,
+    new_user: ValidatedJson<UserRegister>
+) -> Result<HttpResponse, AppError> {
     let password = new_user.password.clone();
-    let hashed_password =  match hash_service::hash_password(&password).await {
-        Ok(hashed_password) => hashed_password,
-        Err(error) => return error
-    };
+
+    password_policy.validate("password", &password, &[&new_user.username, &new_user.email]).await?;
+
+    let hashed_password = hash_service::hash_password(&password).await?;
 
     let mut user = new_user.into_inner();
     user.password = hashed_password;
 
-    user_service::create_user(db, web::Json(user)).await
+    user_service::create_user(db, token_service, mailer, web::Json(user)).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[post("/verify/email")]
+pub async fn verify_email(
+    db: web::Data<DatabaseConnection>,
+    token_service: web::Data<TokenService>,
+    form: ValidatedJson<VerifyEmail>,
+) -> Result<HttpResponse, AppError> {
+    verification_service::verify_email(db, token_service, form.into()).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[post("/verify/email/resend")]
+pub async fn resend_verification(
+    db: web::Data<DatabaseConnection>,
+    token_service: web::Data<TokenService>,
+    mailer: web::Data<dyn Mailer>,
+    form: ValidatedJson<ResendVerification>,
+) -> Result<HttpResponse, AppError> {
+    verification_service::resend_verification(db, token_service, mailer, form.into()).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[post("/password/forgot")]
+pub async fn forgot_password(
+    db: web::Data<DatabaseConnection>,
+    mailer: web::Data<dyn Mailer>,
+    form: ValidatedJson<ForgotPassword>,
+) -> HttpResponse {
+    password_reset_service::forgot_password(db, mailer, form.into()).await;
+    HttpResponse::Ok().finish()
+}
+
+#[post("/password/reset")]
+pub async fn reset_password(
+    db: web::Data<DatabaseConnection>,
+    password_policy: web::Data<PasswordPolicy>,
+    form: ValidatedJson<ResetPassword>,
+) -> Result<HttpResponse, AppError> {
+    password_reset_service::reset_password(db, password_policy, form.into()).await?;
+    Ok(HttpResponse::Ok().finish())
 }
 
 #[post("/login")]
 pub async fn login(
     db: web::Data<DatabaseConnection>,
-    user_login: web::Json<UserLogin>,
-    token_signer: web::Data<TokenSigner<UserClaims, Hs256>>,
-) -> impl Responder {
-    user_service::login(db, user_login, token_signer, Role::RegisteredUser).await
+    user_login: ValidatedJson<UserLogin>,
+    token_service: web::Data<TokenService>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    Ok(login_response(user_service::login(db, user_login.into(), token_service, Role::RegisteredUser, TokenDelivery::Cookie, &throttle_service::client_ip(&req)).await?))
+}
+
+#[post("/login/token")]
+pub async fn login_token(
+    db: web::Data<DatabaseConnection>,
+    user_login: ValidatedJson<UserLogin>,
+    token_service: web::Data<TokenService>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    Ok(login_response(user_service::login(db, user_login.into(), token_service, Role::RegisteredUser, TokenDelivery::Body, &throttle_service::client_ip(&req)).await?))
+}
+
+#[post("/login/mfa")]
+pub async fn login_mfa(
+    db: web::Data<DatabaseConnection>,
+    token_service: web::Data<TokenService>,
+    form: ValidatedJson<MfaLogin>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    Ok(tokens_response(mfa_service::complete_login(db, token_service, form.into(), &throttle_service::client_ip(&req)).await?))
+}
+
+/// Takes the refresh token from the body or the cookie, and answers in the same form it arrived in.
+#[post("/refresh")]
+pub async fn refresh(
+    db: web::Data<DatabaseConnection>,
+    token_service: web::Data<TokenService>,
+    form: Option<ValidatedJson<RefreshToken>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let (refresh_token, delivery) = match form {
+        Some(form) => (form.into_inner().refresh_token, TokenDelivery::Body),
+        None => match req.cookie(REFRESH_TOKEN_COOKIE) {
+            Some(cookie) => (cookie.value().to_owned(), TokenDelivery::Cookie),
+            None => return Err(AppError::unauthorized("Refresh token not found!")),
+        },
+    };
+
+    Ok(tokens_response(user_service::refresh_tokens(db, token_service, &refresh_token, delivery).await?))
+}
+
+#[post("/mfa/totp")]
+pub async fn enroll_totp(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(mfa_service::enroll_totp(db, user_claims).await?))
+}
+
+#[post("/mfa/totp/confirm")]
+pub async fn confirm_totp(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    form: ValidatedJson<TotpCode>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(mfa_service::confirm_totp(db, user_claims, form.into()).await?))
+}
+
+#[post("/mfa/recovery-codes")]
+pub async fn regenerate_recovery_codes(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    form: ValidatedJson<TotpCode>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(mfa_service::regenerate_recovery_codes(db, user_claims, form.into(), &throttle_service::client_ip(&req)).await?))
+}
+
+#[delete("/mfa/totp")]
+pub async fn disable_totp(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    form: ValidatedJson<TotpCode>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    mfa_service::disable_totp(db, user_claims, form.into(), &throttle_service::client_ip(&req)).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[post("/tokens")]
+pub async fn create_api_token(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    form: ValidatedJson<CreateApiToken>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Created().json(api_token_service::create_token(db, user_claims, form.into()).await?))
+}
+
+#[get("/tokens")]
+pub async fn list_api_tokens(
+    db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
+    user_claims: UserClaims,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(api_token_service::list_tokens(db, user_claims, query).await?.respond(&req))
+}
+
+#[delete("/tokens/{token_id}")]
+pub async fn revoke_api_token(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    token_id: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    api_token_service::revoke_token(db, user_claims, token_id).await?;
+    Ok(HttpResponse::NoContent().finish())
 }
 
 #[get("/current")]
 pub async fn get_current_user(
     db: web::Data<DatabaseConnection>,
     user_claims: UserClaims
-) -> impl Responder {
-    user_service::get_user(db, user_claims.id).await
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(user_service::get_user(db, user_claims.id).await?))
 }
 
 #[post("/join/group/{group_id}")]
 pub async fn join_group(
     db: web::Data<DatabaseConnection>,
     group_id: web::Path<i64>,
-    join_group: web::Json<JoinGroup>,
-    user_claims: UserClaims
-) -> impl Responder {
-    user_service::join_group(db, group_id, join_group, user_claims).await
-}
\ No newline at end of file
+    join_group: ValidatedJson<JoinGroup>,
+    user_claims: UserClaims,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    user_service::join_group(db, group_id, join_group.into(), user_claims, &throttle_service::client_ip(&req)).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[get("/groups")]
+pub async fn list_my_groups(
+    db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
+    user_claims: UserClaims,
+    filter: web::Query<GroupFilter>,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(group_service::get_user_groups(db, user_claims, filter, query).await?.respond(&req))
+}
+
+#[delete("/groups/{group_id}")]
+pub async fn leave_group(
+    db: web::Data<DatabaseConnection>,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    group_service::leave_group(db, group_id, user_claims).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/invites")]
+pub async fn list_group_invites(
+    db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
+    user_claims: UserClaims,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(group_invite_service::list_pending_invites(db, user_claims, query).await?.respond(&req))
+}
+
+#[post("/invites/accept")]
+pub async fn accept_group_invite(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    form: ValidatedJson<AcceptGroupInvite>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(group_invite_service::accept_invite(db, user_claims, form.into()).await?))
+}
+
+#[post("/invites/{invite_id}/accept")]
+pub async fn accept_direct_group_invite(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    invite_id: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(group_invite_service::accept_direct_invite(db, user_claims, invite_id).await?))
+}
+
+#[get("/join-requests")]
+pub async fn list_join_requests(
+    db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
+    user_claims: UserClaims,
+    filter: web::Query<JoinRequestFilter>,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(group_join_request_service::list_own_requests(db, user_claims, filter, query).await?.respond(&req))
+}
+
+#[get("/history")]
+pub async fn list_watch_history(
+    db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
+    user_claims: UserClaims,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(video_progress_service::list_history(db, user_claims, query, false).await?.respond(&req))
+}
+
+/// Started but unfinished videos, most recently watched first.
+#[get("/history/continue")]
+pub async fn list_continue_watching(
+    db: web::Data<DatabaseConnection>,
+    req: HttpRequest,
+    user_claims: UserClaims,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, AppError> {
+    Ok(video_progress_service::list_history(db, user_claims, query, true).await?.respond(&req))
+}
+
+#[delete("/history/{video_id}")]
+pub async fn delete_watch_history_entry(
+    db: web::Data<DatabaseConnection>,
+    video_id: web::Path<i64>,
+    user_claims: UserClaims,
+) -> Result<HttpResponse, AppError> {
+    video_progress_service::delete_progress(db, video_id, user_claims).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
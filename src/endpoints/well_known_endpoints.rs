@@ -0,0 +1,17 @@
+use actix_web::{get, web, HttpResponse};
+use actix_web::http::header::{CacheControl, CacheDirective};
+use crate::services::token_service::TokenService;
+
+pub fn well_known_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/.well-known")
+            .service(jwks)
+    );
+}
+
+#[get("/jwks.json")]
+pub async fn jwks(token_service: web::Data<TokenService>) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge(300)]))
+        .json(token_service.jwks())
+}
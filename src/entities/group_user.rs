@@ -11,6 +11,8 @@ pub struct Model {
     pub group_id: i64,
     pub user_id: i64,
     pub joined_at: DateTimeWithTimeZone,
+    #[sea_orm(column_type = "Text")]
+    pub role: String,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -10,10 +10,21 @@ pub struct Model {
     pub group_id: i64,
     #[sea_orm(primary_key, auto_increment = false)]
     pub video_id: i64,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub metadata: Json,
+    pub folder_id: Option<i64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::group_folders::Entity",
+        from = "Column::FolderId",
+        to = "super::group_folders::Column::Id",
+        on_update = "NoAction",
+        on_delete = "SetNull"
+    )]
+    GroupFolders,
     #[sea_orm(
         belongs_to = "super::groups::Entity",
         from = "Column::GroupId",
@@ -32,6 +43,12 @@ pub enum Relation {
     Videos,
 }
 
+impl Related<super::group_folders::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::GroupFolders.def()
+    }
+}
+
 impl Related<super::groups::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Groups.def()
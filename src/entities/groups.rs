@@ -14,6 +14,10 @@ pub struct Model {
     pub password: Option<String>,
     pub created_at: DateTimeWithTimeZone,
     pub is_deleted: bool,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub description: Option<String>,
+    #[sea_orm(column_type = "Text")]
+    pub visibility: String,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -2,8 +2,25 @@
 
 pub mod prelude;
 
+pub mod api_tokens;
+pub mod audit_events;
+pub mod auth_throttles;
+pub mod email_verifications;
+pub mod group_folders;
+pub mod group_invites;
+pub mod group_join_requests;
+pub mod group_playlist_videos;
+pub mod group_playlists;
 pub mod group_user;
 pub mod group_video;
 pub mod groups;
+pub mod password_resets;
+pub mod recovery_codes;
+pub mod settings;
+pub mod transcript_segments;
+pub mod transcription_jobs;
 pub mod users;
+pub mod video_comments;
+pub mod video_progress;
+pub mod video_subtitles;
 pub mod videos;
@@ -1,7 +1,4 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.5
 
-pub use super::group_user::Entity as GroupUser;
 pub use super::group_video::Entity as GroupVideo;
-pub use super::groups::Entity as Groups;
-pub use super::users::Entity as Users;
 pub use super::videos::Entity as Videos;
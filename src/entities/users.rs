@@ -17,12 +17,47 @@ pub struct Model {
     pub created_at: DateTimeWithTimeZone,
     #[sea_orm(column_name = "isDeleted")]
     pub is_deleted: bool,
+    pub email_verified: bool,
+    pub session_version: i32,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    pub totp_last_step: Option<i64>,
+    pub is_admin: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::api_tokens::Entity")]
+    ApiTokens,
+    #[sea_orm(has_many = "super::auth_throttles::Entity")]
+    AuthThrottles,
+    #[sea_orm(has_many = "super::email_verifications::Entity")]
+    EmailVerifications,
     #[sea_orm(has_many = "super::group_user::Entity")]
     GroupUser,
+    #[sea_orm(has_many = "super::password_resets::Entity")]
+    PasswordResets,
+    #[sea_orm(has_many = "super::recovery_codes::Entity")]
+    RecoveryCodes,
+}
+
+impl Related<super::api_tokens::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApiTokens.def()
+    }
+}
+
+impl Related<super::auth_throttles::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AuthThrottles.def()
+    }
+}
+
+impl Related<super::email_verifications::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::EmailVerifications.def()
+    }
 }
 
 impl Related<super::group_user::Entity> for Entity {
@@ -31,4 +66,16 @@ impl Related<super::group_user::Entity> for Entity {
     }
 }
 
+impl Related<super::password_resets::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PasswordResets.def()
+    }
+}
+
+impl Related<super::recovery_codes::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RecoveryCodes.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -13,6 +13,12 @@ pub struct Model {
     #[sea_orm(column_type = "Text")]
     pub key: String,
     pub uploaded_at: Option<DateTime>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub title: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub description: Option<String>,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub tags: Json,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
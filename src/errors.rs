@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use sea_orm::{DbErr, SqlErr};
+use crate::dtos::error_dto::ErrorResponse;
+
+/// Every error a service can fail with. Internal variants carry their cause for the log only,
+/// clients get a stable `code` and a generic message.
+#[derive(Debug)]
+pub enum AppError {
+    Database(DbErr),
+    Storage(String),
+    Upstream(String),
+    Internal(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    Duplicate { field: String, message: String },
+    BadRequest(String),
+    Validation(BTreeMap<String, Vec<String>>),
+    TooManyRequests { message: String, retry_after: u64 },
+}
+
+impl AppError {
+    pub fn internal(cause: impl fmt::Display) -> AppError {
+        AppError::Internal(cause.to_string())
+    }
+
+    pub fn unauthorized(message: &str) -> AppError {
+        AppError::Unauthorized(message.to_string())
+    }
+
+    pub fn forbidden(message: &str) -> AppError {
+        AppError::Forbidden(message.to_string())
+    }
+
+    pub fn not_found(message: &str) -> AppError {
+        AppError::NotFound(message.to_string())
+    }
+
+    pub fn conflict(message: &str) -> AppError {
+        AppError::Conflict(message.to_string())
+    }
+
+    pub fn bad_request(message: &str) -> AppError {
+        AppError::BadRequest(message.to_string())
+    }
+
+    pub fn field(field: &str, message: &str) -> AppError {
+        AppError::Validation(BTreeMap::from([(field.to_string(), vec![message.to_string()])]))
+    }
+
+    /// Maps a unique-constraint violation to a 409 naming the first of `fields` that appears in the
+    /// violated constraint, any other database error passes through unchanged.
+    pub fn unique(error: DbErr, fields: &[(&str, &str)]) -> AppError {
+        let constraint = match error.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(constraint)) => constraint,
+            _ => return AppError::Database(error),
+        };
+
+        match fields.iter().find(|(field, _)| constraint.contains(field)) {
+            Some((field, message)) => AppError::Duplicate {
+                field: field.to_string(),
+                message: message.to_string(),
+            },
+            None => AppError::conflict("The resource already exists!"),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Database(_) => "database_error",
+            AppError::Storage(_) => "storage_error",
+            AppError::Upstream(_) => "upstream_error",
+            AppError::Internal(_) => "internal_error",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict(_) => "conflict",
+            AppError::Duplicate { .. } => "duplicate",
+            AppError::BadRequest(_) => "bad_request",
+            AppError::Validation(_) => "validation_failed",
+            AppError::TooManyRequests { .. } => "too_many_requests",
+        }
+    }
+
+    fn public_message(&self) -> &str {
+        match self {
+            AppError::Database(_) | AppError::Internal(_) => "An internal error occurred!",
+            AppError::Storage(_) => "The storage backend failed to process the request!",
+            AppError::Upstream(_) => "An upstream service is unavailable!",
+            AppError::Validation(_) => "The request contains invalid fields!",
+            AppError::Unauthorized(message)
+            | AppError::Forbidden(message)
+            | AppError::NotFound(message)
+            | AppError::Conflict(message)
+            | AppError::Duplicate { message, .. }
+            | AppError::BadRequest(message)
+            | AppError::TooManyRequests { message, .. } => message,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AppError::Database(e) => write!(f, "database error: {}", e),
+            AppError::Storage(cause) => write!(f, "storage error: {}", cause),
+            AppError::Upstream(cause) => write!(f, "upstream error: {}", cause),
+            AppError::Internal(cause) => write!(f, "internal error: {}", cause),
+            _ => write!(f, "{}", self.public_message()),
+        }
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::Database(_) | AppError::Storage(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) | AppError::Duplicate { .. } => StatusCode::CONFLICT,
+            AppError::BadRequest(_) | AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if self.status_code().is_server_error() {
+            log::error!("{}", self);
+        }
+
+        let body = ErrorResponse {
+            code: self.code().to_string(),
+            message: self.public_message().to_string(),
+            fields: match self {
+                AppError::Validation(fields) => fields.clone(),
+                AppError::Duplicate { field, message } => BTreeMap::from([(field.clone(), vec![message.clone()])]),
+                _ => BTreeMap::new(),
+            },
+        };
+
+        let mut response = HttpResponse::build(self.status_code());
+        if let AppError::TooManyRequests { retry_after, .. } = self {
+            response.insert_header(("Retry-After", retry_after.to_string()));
+        }
+
+        response.json(body)
+    }
+}
+
+impl From<DbErr> for AppError {
+    fn from(error: DbErr) -> AppError {
+        AppError::Database(error)
+    }
+}
@@ -3,21 +3,32 @@ mod entities;
 mod services;
 mod endpoints;
 mod dtos;
+mod migrations;
+mod errors;
 
-use actix_jwt_auth_middleware::{Authority, TokenSigner};
-use actix_jwt_auth_middleware::use_jwt::{UseJWTOnApp, UseJWTOnScope};
-use actix_web::{web, HttpResponse, Responder};
-use jwt_compact::alg::{Hs256, Hs256Key};
-use core::time::Duration;
+use std::sync::Arc;
+use actix_web::web;
 use actix_multipart::form::MultipartFormConfig;
 use actix_web::web::ServiceConfig;
 use shuttle_actix_web::ShuttleActixWeb;
+use crate::dtos::validation;
 use crate::endpoints::admin_endpoints::{admin_routes};
 use crate::endpoints::group_endpoints::group_routes;
+use crate::endpoints::oidc_endpoints::{oidc_routes, register_client};
+use crate::endpoints::search_endpoints::search_routes;
 use crate::endpoints::storage_endpoints::storage_routes;
 use crate::endpoints::user_endpoints::{user_routes};
-use crate::services::auth_service::{UserClaims};
-use crate::services::storage_service;
+use crate::endpoints::well_known_endpoints::well_known_routes;
+use crate::migrations::Migrator;
+use crate::services::mail_service;
+use crate::services::mail_service::Mailer;
+use crate::services::oidc_service::{OidcClient, OidcConfig};
+use crate::services::password_policy_service::PasswordPolicy;
+use crate::services::{storage_service, video_transcript_service};
+use crate::services::token_service::TokenService;
+use crate::services::transcription_service;
+use crate::services::transcription_service::TranscriptionEngine;
+use sea_orm_migration::MigratorTrait;
 use shuttle_runtime::SecretStore;
 
 #[shuttle_runtime::main]
@@ -26,45 +37,51 @@ async fn main(#[shuttle_runtime::Secrets] secrets: SecretStore) -> ShuttleActixW
     let db = db::establish_connection(secrets.clone()).await
         .expect("Failed to establish database connection");
 
-    let s3_client = storage_service::create_client(secrets.clone()).await;
+    Migrator::up(&db, None).await
+        .expect("Failed to run database migrations");
+
+    video_transcript_service::fail_interrupted_jobs(&db).await
+        .expect("Failed to clean up interrupted transcription jobs");
 
-    std::env::set_var("JWT_PRIVATE_KEY", secrets.get("JWT_PRIVATE_KEY").unwrap_or_default().to_string());
-    std::env::set_var("VIDEO_STORAGE_BUCKET", secrets.get("VIDEO_STORAGE_BUCKET").unwrap_or_default().to_string());
+    let s3_client = storage_service::create_client(secrets.clone()).await;
 
-    let public_key = Hs256Key::new(secrets.get("JWT_PUBLIC_KEY").unwrap_or_default().into_bytes());
-    let private_key = Hs256Key::new(secrets.get("JWT_PRIVATE_KEY").unwrap_or_default().into_bytes());
+    std::env::set_var("VIDEO_STORAGE_BUCKET", secrets.get("VIDEO_STORAGE_BUCKET").unwrap_or_default());
+    std::env::set_var("EMAIL_VERIFICATION_URL", secrets.get("EMAIL_VERIFICATION_URL").unwrap_or_default());
+    std::env::set_var("PASSWORD_RESET_URL", secrets.get("PASSWORD_RESET_URL").unwrap_or_default());
+    std::env::set_var("GROUP_INVITE_URL", secrets.get("GROUP_INVITE_URL").unwrap_or_default());
+    std::env::set_var("TRUSTED_PROXIES", secrets.get("TRUSTED_PROXIES").unwrap_or_default());
 
-    let authority = Authority::<UserClaims, Hs256, _, _>::new()
-        .refresh_authorizer(|| async move { Ok(()) })
-        .token_signer(Some(
-            TokenSigner::new()
-                .signing_key(private_key.clone())
-                .algorithm(Hs256)
-                .access_token_lifetime(Duration::new(3600, 0))
-                .refresh_token_lifetime(Duration::new(3600*24, 0))
-                .build()
-                .expect("Failed to build Authority!"),
-        ))
-        .enable_cookie_tokens(true)
-        .renew_access_token_automatically(true)
-        .verifying_key(public_key)
-        .build()
-        .expect("Failed to build Authority!");
+    let db = web::Data::new(db);
+    let token_service = web::Data::new(TokenService::from_secrets(&secrets));
+    let mailer: web::Data<dyn Mailer> = web::Data::from(Arc::from(mail_service::create_mailer(&secrets)));
+    let transcription_engine: web::Data<dyn TranscriptionEngine> = web::Data::from(Arc::from(transcription_service::create_engine(&secrets)));
+    let password_policy = web::Data::new(PasswordPolicy::from_secrets(&secrets));
+    let oidc_client = OidcConfig::from_secrets(&secrets).map(|config| web::Data::new(OidcClient::new(config)));
 
     let config = move |cfg: &mut ServiceConfig| {
+        register_client(cfg, oidc_client.clone());
+
         cfg.app_data(
                 MultipartFormConfig::
                 total_limit(Default::default(), 1024 * 1024 * 512).memory_limit(1024 * 1024 * 5)
             )
-            .app_data(web::Data::new(db.clone()))
+            .app_data(web::JsonConfig::default().error_handler(validation::json_error_handler))
+            .app_data(web::QueryConfig::default().error_handler(validation::query_error_handler))
+            .app_data(db.clone())
             .app_data(web::Data::new(s3_client.clone()))
+            .app_data(token_service.clone())
+            .app_data(mailer.clone())
+            .app_data(transcription_engine.clone())
+            .app_data(password_policy.clone())
             .service(
                 web::scope("")
+                    .configure(well_known_routes)
+                    .configure(oidc_routes)
                     .configure(user_routes)
                     .configure(admin_routes)
                     .configure(storage_routes)
                     .configure(group_routes)
-                    .use_jwt(authority.clone(), web::scope(""))
+                    .configure(search_routes)
             );
     };
 
@@ -0,0 +1,82 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Accounts that existed before verification was introduced are treated as verified.
+        manager.alter_table(
+            Table::alter()
+                .table(Users::Table)
+                .add_column(ColumnDef::new(Users::EmailVerified).boolean().not_null().default(true))
+                .to_owned()
+        ).await?;
+
+        manager.alter_table(
+            Table::alter()
+                .table(Users::Table)
+                .modify_column(ColumnDef::new(Users::EmailVerified).boolean().not_null().default(false))
+                .to_owned()
+        ).await?;
+
+        manager.create_table(
+            Table::create()
+                .table(EmailVerifications::Table)
+                .if_not_exists()
+                .col(ColumnDef::new(EmailVerifications::Id).big_integer().not_null().auto_increment().primary_key())
+                .col(ColumnDef::new(EmailVerifications::UserId).big_integer().not_null())
+                .col(ColumnDef::new(EmailVerifications::TokenId).text().not_null().unique_key())
+                .col(ColumnDef::new(EmailVerifications::CreatedAt).timestamp_with_time_zone().not_null().default(Expr::current_timestamp()))
+                .col(ColumnDef::new(EmailVerifications::ExpiresAt).timestamp_with_time_zone().not_null())
+                .col(ColumnDef::new(EmailVerifications::UsedAt).timestamp_with_time_zone().null())
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(EmailVerifications::Table, EmailVerifications::UserId)
+                        .to(Users::Table, Users::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_email_verifications_user_id")
+                .table(EmailVerifications::Table)
+                .col(EmailVerifications::UserId)
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(EmailVerifications::Table).to_owned()).await?;
+
+        manager.alter_table(
+            Table::alter()
+                .table(Users::Table)
+                .drop_column(Users::EmailVerified)
+                .to_owned()
+        ).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    #[sea_orm(iden = "Users")]
+    Table,
+    Id,
+    EmailVerified,
+}
+
+#[derive(DeriveIden)]
+enum EmailVerifications {
+    #[sea_orm(iden = "EmailVerifications")]
+    Table,
+    Id,
+    UserId,
+    TokenId,
+    CreatedAt,
+    ExpiresAt,
+    UsedAt,
+}
@@ -0,0 +1,74 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.alter_table(
+            Table::alter()
+                .table(Users::Table)
+                .add_column(ColumnDef::new(Users::SessionVersion).integer().not_null().default(0))
+                .to_owned()
+        ).await?;
+
+        manager.create_table(
+            Table::create()
+                .table(PasswordResets::Table)
+                .if_not_exists()
+                .col(ColumnDef::new(PasswordResets::Id).big_integer().not_null().auto_increment().primary_key())
+                .col(ColumnDef::new(PasswordResets::UserId).big_integer().not_null())
+                .col(ColumnDef::new(PasswordResets::TokenHash).text().not_null().unique_key())
+                .col(ColumnDef::new(PasswordResets::CreatedAt).timestamp_with_time_zone().not_null().default(Expr::current_timestamp()))
+                .col(ColumnDef::new(PasswordResets::ExpiresAt).timestamp_with_time_zone().not_null())
+                .col(ColumnDef::new(PasswordResets::UsedAt).timestamp_with_time_zone().null())
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(PasswordResets::Table, PasswordResets::UserId)
+                        .to(Users::Table, Users::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_password_resets_user_id")
+                .table(PasswordResets::Table)
+                .col(PasswordResets::UserId)
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(PasswordResets::Table).to_owned()).await?;
+
+        manager.alter_table(
+            Table::alter()
+                .table(Users::Table)
+                .drop_column(Users::SessionVersion)
+                .to_owned()
+        ).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    #[sea_orm(iden = "Users")]
+    Table,
+    Id,
+    SessionVersion,
+}
+
+#[derive(DeriveIden)]
+enum PasswordResets {
+    #[sea_orm(iden = "PasswordResets")]
+    Table,
+    Id,
+    UserId,
+    TokenHash,
+    CreatedAt,
+    ExpiresAt,
+    UsedAt,
+}
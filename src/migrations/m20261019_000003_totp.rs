@@ -0,0 +1,98 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.alter_table(
+            Table::alter()
+                .table(Users::Table)
+                .add_column(ColumnDef::new(Users::TotpSecret).text().null())
+                .add_column(ColumnDef::new(Users::TotpEnabled).boolean().not_null().default(false))
+                .add_column(ColumnDef::new(Users::TotpLastStep).big_integer().null())
+                .to_owned()
+        ).await?;
+
+        manager.create_table(
+            Table::create()
+                .table(RecoveryCodes::Table)
+                .if_not_exists()
+                .col(ColumnDef::new(RecoveryCodes::Id).big_integer().not_null().auto_increment().primary_key())
+                .col(ColumnDef::new(RecoveryCodes::UserId).big_integer().not_null())
+                .col(ColumnDef::new(RecoveryCodes::CodeHash).text().not_null())
+                .col(ColumnDef::new(RecoveryCodes::CreatedAt).timestamp_with_time_zone().not_null().default(Expr::current_timestamp()))
+                .col(ColumnDef::new(RecoveryCodes::UsedAt).timestamp_with_time_zone().null())
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(RecoveryCodes::Table, RecoveryCodes::UserId)
+                        .to(Users::Table, Users::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_recovery_codes_user_id")
+                .table(RecoveryCodes::Table)
+                .col(RecoveryCodes::UserId)
+                .to_owned()
+        ).await?;
+
+        manager.create_table(
+            Table::create()
+                .table(Settings::Table)
+                .if_not_exists()
+                .col(ColumnDef::new(Settings::Key).text().not_null().primary_key())
+                .col(ColumnDef::new(Settings::Value).text().not_null())
+                .col(ColumnDef::new(Settings::UpdatedAt).timestamp_with_time_zone().not_null().default(Expr::current_timestamp()))
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(Settings::Table).to_owned()).await?;
+        manager.drop_table(Table::drop().table(RecoveryCodes::Table).to_owned()).await?;
+
+        manager.alter_table(
+            Table::alter()
+                .table(Users::Table)
+                .drop_column(Users::TotpSecret)
+                .drop_column(Users::TotpEnabled)
+                .drop_column(Users::TotpLastStep)
+                .to_owned()
+        ).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    #[sea_orm(iden = "Users")]
+    Table,
+    Id,
+    TotpSecret,
+    TotpEnabled,
+    TotpLastStep,
+}
+
+#[derive(DeriveIden)]
+enum RecoveryCodes {
+    #[sea_orm(iden = "RecoveryCodes")]
+    Table,
+    Id,
+    UserId,
+    CodeHash,
+    CreatedAt,
+    UsedAt,
+}
+
+#[derive(DeriveIden)]
+enum Settings {
+    #[sea_orm(iden = "Settings")]
+    Table,
+    Key,
+    Value,
+    UpdatedAt,
+}
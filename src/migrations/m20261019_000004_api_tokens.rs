@@ -0,0 +1,67 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(ApiTokens::Table)
+                .if_not_exists()
+                .col(ColumnDef::new(ApiTokens::Id).big_integer().not_null().auto_increment().primary_key())
+                .col(ColumnDef::new(ApiTokens::UserId).big_integer().not_null())
+                .col(ColumnDef::new(ApiTokens::Name).text().not_null())
+                .col(ColumnDef::new(ApiTokens::TokenHash).text().not_null().unique_key())
+                .col(ColumnDef::new(ApiTokens::Prefix).text().not_null())
+                .col(ColumnDef::new(ApiTokens::Scopes).text().not_null())
+                .col(ColumnDef::new(ApiTokens::CreatedAt).timestamp_with_time_zone().not_null().default(Expr::current_timestamp()))
+                .col(ColumnDef::new(ApiTokens::ExpiresAt).timestamp_with_time_zone().null())
+                .col(ColumnDef::new(ApiTokens::LastUsedAt).timestamp_with_time_zone().null())
+                .col(ColumnDef::new(ApiTokens::RevokedAt).timestamp_with_time_zone().null())
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(ApiTokens::Table, ApiTokens::UserId)
+                        .to(Users::Table, Users::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_api_tokens_user_id")
+                .table(ApiTokens::Table)
+                .col(ApiTokens::UserId)
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(ApiTokens::Table).to_owned()).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    #[sea_orm(iden = "Users")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum ApiTokens {
+    #[sea_orm(iden = "ApiTokens")]
+    Table,
+    Id,
+    UserId,
+    Name,
+    TokenHash,
+    Prefix,
+    Scopes,
+    CreatedAt,
+    ExpiresAt,
+    LastUsedAt,
+    RevokedAt,
+}
@@ -0,0 +1,105 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(AuthThrottles::Table)
+                .if_not_exists()
+                .col(ColumnDef::new(AuthThrottles::Key).text().not_null().primary_key())
+                .col(ColumnDef::new(AuthThrottles::UserId).big_integer().null())
+                .col(ColumnDef::new(AuthThrottles::Failures).integer().not_null().default(0))
+                .col(ColumnDef::new(AuthThrottles::LastFailureAt).timestamp_with_time_zone().not_null())
+                .col(ColumnDef::new(AuthThrottles::LockedUntil).timestamp_with_time_zone().null())
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(AuthThrottles::Table, AuthThrottles::UserId)
+                        .to(Users::Table, Users::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_auth_throttles_user_id")
+                .table(AuthThrottles::Table)
+                .col(AuthThrottles::UserId)
+                .to_owned()
+        ).await?;
+
+        manager.create_table(
+            Table::create()
+                .table(AuditEvents::Table)
+                .if_not_exists()
+                .col(ColumnDef::new(AuditEvents::Id).big_integer().not_null().auto_increment().primary_key())
+                .col(ColumnDef::new(AuditEvents::Event).text().not_null())
+                .col(ColumnDef::new(AuditEvents::UserId).big_integer().null())
+                .col(ColumnDef::new(AuditEvents::ActorId).big_integer().null())
+                .col(ColumnDef::new(AuditEvents::IpAddress).text().null())
+                .col(ColumnDef::new(AuditEvents::Details).text().null())
+                .col(ColumnDef::new(AuditEvents::CreatedAt).timestamp_with_time_zone().not_null().default(Expr::current_timestamp()))
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(AuditEvents::Table, AuditEvents::UserId)
+                        .to(Users::Table, Users::Id)
+                        .on_delete(ForeignKeyAction::SetNull)
+                )
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(AuditEvents::Table, AuditEvents::ActorId)
+                        .to(Users::Table, Users::Id)
+                        .on_delete(ForeignKeyAction::SetNull)
+                )
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_audit_events_user_id")
+                .table(AuditEvents::Table)
+                .col(AuditEvents::UserId)
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(AuditEvents::Table).to_owned()).await?;
+        manager.drop_table(Table::drop().table(AuthThrottles::Table).to_owned()).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    #[sea_orm(iden = "Users")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum AuthThrottles {
+    #[sea_orm(iden = "AuthThrottles")]
+    Table,
+    Key,
+    UserId,
+    Failures,
+    LastFailureAt,
+    LockedUntil,
+}
+
+#[derive(DeriveIden)]
+enum AuditEvents {
+    #[sea_orm(iden = "AuditEvents")]
+    Table,
+    Id,
+    Event,
+    UserId,
+    ActorId,
+    IpAddress,
+    Details,
+    CreatedAt,
+}
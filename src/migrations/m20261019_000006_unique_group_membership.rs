@@ -0,0 +1,42 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Earlier double joins left duplicate memberships behind, keep the oldest one of each.
+        manager.get_connection().execute_unprepared(
+            r#"DELETE FROM "GroupUser" a USING "GroupUser" b
+               WHERE a.id > b.id AND a.group_id = b.group_id AND a.user_id = b.user_id"#
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_group_user_group_id_user_id")
+                .table(GroupUser::Table)
+                .col(GroupUser::GroupId)
+                .col(GroupUser::UserId)
+                .unique()
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_index(
+            Index::drop()
+                .name("idx_group_user_group_id_user_id")
+                .table(GroupUser::Table)
+                .to_owned()
+        ).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum GroupUser {
+    #[sea_orm(iden = "GroupUser")]
+    Table,
+    GroupId,
+    UserId,
+}
@@ -0,0 +1,57 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.alter_table(
+            Table::alter()
+                .table(GroupUser::Table)
+                .add_column(ColumnDef::new(GroupUser::Role).text().not_null().default("member"))
+                .to_owned()
+        ).await?;
+
+        // Groups predate roles and record no creator, the earliest member is the closest thing to one.
+        manager.get_connection().execute_unprepared(
+            r#"UPDATE "GroupUser" SET role = 'owner'
+               WHERE id IN (
+                   SELECT DISTINCT ON (group_id) id FROM "GroupUser"
+                   ORDER BY group_id, joined_at, id
+               )"#
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_group_user_user_id")
+                .table(GroupUser::Table)
+                .col(GroupUser::UserId)
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_index(
+            Index::drop()
+                .name("idx_group_user_user_id")
+                .table(GroupUser::Table)
+                .to_owned()
+        ).await?;
+
+        manager.alter_table(
+            Table::alter()
+                .table(GroupUser::Table)
+                .drop_column(GroupUser::Role)
+                .to_owned()
+        ).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum GroupUser {
+    #[sea_orm(iden = "GroupUser")]
+    Table,
+    UserId,
+    Role,
+}
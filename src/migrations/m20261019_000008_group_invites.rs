@@ -0,0 +1,100 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(GroupInvites::Table)
+                .if_not_exists()
+                .col(ColumnDef::new(GroupInvites::Id).big_integer().not_null().auto_increment().primary_key())
+                .col(ColumnDef::new(GroupInvites::GroupId).big_integer().not_null())
+                .col(ColumnDef::new(GroupInvites::CreatedBy).big_integer().null())
+                .col(ColumnDef::new(GroupInvites::CodeHash).text().not_null().unique_key())
+                .col(ColumnDef::new(GroupInvites::Prefix).text().not_null())
+                .col(ColumnDef::new(GroupInvites::Role).text().not_null().default("member"))
+                .col(ColumnDef::new(GroupInvites::InvitedUserId).big_integer().null())
+                .col(ColumnDef::new(GroupInvites::InvitedEmail).text().null())
+                .col(ColumnDef::new(GroupInvites::MaxUses).integer().null())
+                .col(ColumnDef::new(GroupInvites::Uses).integer().not_null().default(0))
+                .col(ColumnDef::new(GroupInvites::CreatedAt).timestamp_with_time_zone().not_null().default(Expr::current_timestamp()))
+                .col(ColumnDef::new(GroupInvites::ExpiresAt).timestamp_with_time_zone().null())
+                .col(ColumnDef::new(GroupInvites::RevokedAt).timestamp_with_time_zone().null())
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(GroupInvites::Table, GroupInvites::GroupId)
+                        .to(Groups::Table, Groups::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(GroupInvites::Table, GroupInvites::CreatedBy)
+                        .to(Users::Table, Users::Id)
+                        .on_delete(ForeignKeyAction::SetNull)
+                )
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(GroupInvites::Table, GroupInvites::InvitedUserId)
+                        .to(Users::Table, Users::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_group_invites_group_id")
+                .table(GroupInvites::Table)
+                .col(GroupInvites::GroupId)
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_group_invites_invited_user_id")
+                .table(GroupInvites::Table)
+                .col(GroupInvites::InvitedUserId)
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(GroupInvites::Table).to_owned()).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Groups {
+    #[sea_orm(iden = "Groups")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    #[sea_orm(iden = "Users")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum GroupInvites {
+    #[sea_orm(iden = "GroupInvites")]
+    Table,
+    Id,
+    GroupId,
+    CreatedBy,
+    CodeHash,
+    Prefix,
+    Role,
+    InvitedUserId,
+    InvitedEmail,
+    MaxUses,
+    Uses,
+    CreatedAt,
+    ExpiresAt,
+    RevokedAt,
+}
@@ -0,0 +1,90 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(GroupJoinRequests::Table)
+                .if_not_exists()
+                .col(ColumnDef::new(GroupJoinRequests::Id).big_integer().not_null().auto_increment().primary_key())
+                .col(ColumnDef::new(GroupJoinRequests::GroupId).big_integer().not_null())
+                .col(ColumnDef::new(GroupJoinRequests::UserId).big_integer().not_null())
+                .col(ColumnDef::new(GroupJoinRequests::Status).text().not_null().default("pending"))
+                .col(ColumnDef::new(GroupJoinRequests::Message).text().null())
+                .col(ColumnDef::new(GroupJoinRequests::ReviewedBy).big_integer().null())
+                .col(ColumnDef::new(GroupJoinRequests::CreatedAt).timestamp_with_time_zone().not_null().default(Expr::current_timestamp()))
+                .col(ColumnDef::new(GroupJoinRequests::ReviewedAt).timestamp_with_time_zone().null())
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(GroupJoinRequests::Table, GroupJoinRequests::GroupId)
+                        .to(Groups::Table, Groups::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(GroupJoinRequests::Table, GroupJoinRequests::UserId)
+                        .to(Users::Table, Users::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(GroupJoinRequests::Table, GroupJoinRequests::ReviewedBy)
+                        .to(Users::Table, Users::Id)
+                        .on_delete(ForeignKeyAction::SetNull)
+                )
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_group_join_requests_group_id_status")
+                .table(GroupJoinRequests::Table)
+                .col(GroupJoinRequests::GroupId)
+                .col(GroupJoinRequests::Status)
+                .to_owned()
+        ).await?;
+
+        // A user can only have one open request per group, reviewed ones are kept as history.
+        manager.get_connection().execute_unprepared(
+            r#"CREATE UNIQUE INDEX "idx_group_join_requests_pending" ON "GroupJoinRequests" (group_id, user_id) WHERE status = 'pending'"#
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(GroupJoinRequests::Table).to_owned()).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Groups {
+    #[sea_orm(iden = "Groups")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    #[sea_orm(iden = "Users")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum GroupJoinRequests {
+    #[sea_orm(iden = "GroupJoinRequests")]
+    Table,
+    Id,
+    GroupId,
+    UserId,
+    Status,
+    Message,
+    ReviewedBy,
+    CreatedAt,
+    ReviewedAt,
+}
@@ -0,0 +1,33 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Every existing group was joined with its password, so that stays the default.
+        manager.alter_table(
+            Table::alter()
+                .table(Groups::Table)
+                .add_column(ColumnDef::new(Groups::Visibility).text().not_null().default("password"))
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.alter_table(
+            Table::alter()
+                .table(Groups::Table)
+                .drop_column(Groups::Visibility)
+                .to_owned()
+        ).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Groups {
+    #[sea_orm(iden = "Groups")]
+    Table,
+    Visibility,
+}
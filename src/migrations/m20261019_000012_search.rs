@@ -0,0 +1,47 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// The `simple` configuration doesn't stem, which suits file names and usernames better than a language one.
+// Dots and underscores are blanked out first so `team_meeting.mp4` is found by `meeting`.
+const SEARCH_VECTORS: [(&str, &str, &str); 3] = [
+    ("Videos", "idx_videos_search_vector",
+        "to_tsvector('simple', translate(name, '._', '  '))"),
+    ("Groups", "idx_groups_search_vector",
+        "setweight(to_tsvector('simple', translate(name, '._', '  ')), 'A') || setweight(to_tsvector('simple', coalesce(description, '')), 'B')"),
+    ("Users", "idx_users_search_vector",
+        "to_tsvector('simple', translate(coalesce(username, ''), '._', '  '))"),
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        for (table, index, expression) in SEARCH_VECTORS {
+            db.execute_unprepared(&format!(
+                r#"ALTER TABLE "{}" ADD COLUMN search_vector tsvector GENERATED ALWAYS AS ({}) STORED"#,
+                table, expression
+            )).await?;
+
+            db.execute_unprepared(&format!(
+                r#"CREATE INDEX {} ON "{}" USING GIN (search_vector)"#,
+                index, table
+            )).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Dropping the column drops its index too.
+        for (table, _, _) in SEARCH_VECTORS {
+            db.execute_unprepared(&format!(r#"ALTER TABLE "{}" DROP COLUMN search_vector"#, table)).await?;
+        }
+
+        Ok(())
+    }
+}
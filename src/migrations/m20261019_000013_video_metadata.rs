@@ -0,0 +1,89 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// Titles rank above tags and tags above descriptions, the file name stays searchable next to the title.
+const VIDEO_SEARCH_VECTOR: &str = "setweight(to_tsvector('simple', translate(coalesce(title, '') || ' ' || name, '._', '  ')), 'A') \
+    || setweight(jsonb_to_tsvector('simple', tags, '[\"string\"]'), 'B') \
+    || setweight(to_tsvector('simple', coalesce(description, '')), 'C')";
+
+const PREVIOUS_VIDEO_SEARCH_VECTOR: &str = "to_tsvector('simple', translate(name, '._', '  '))";
+
+async fn replace_video_search_vector(manager: &SchemaManager<'_>, expression: &str) -> Result<(), DbErr> {
+    let db = manager.get_connection();
+
+    db.execute_unprepared(r#"ALTER TABLE "Videos" DROP COLUMN search_vector"#).await?;
+    db.execute_unprepared(&format!(
+        r#"ALTER TABLE "Videos" ADD COLUMN search_vector tsvector GENERATED ALWAYS AS ({}) STORED"#,
+        expression
+    )).await?;
+    db.execute_unprepared(r#"CREATE INDEX idx_videos_search_vector ON "Videos" USING GIN (search_vector)"#).await?;
+
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.alter_table(
+            Table::alter()
+                .table(Videos::Table)
+                .add_column(ColumnDef::new(Videos::Title).text().null())
+                .add_column(ColumnDef::new(Videos::Description).text().null())
+                .add_column(ColumnDef::new(Videos::Tags).json_binary().not_null().default(Expr::cust("'[]'::jsonb")))
+                .to_owned()
+        ).await?;
+
+        manager.alter_table(
+            Table::alter()
+                .table(GroupVideo::Table)
+                .add_column(ColumnDef::new(GroupVideo::Metadata).json_binary().not_null().default(Expr::cust("'{}'::jsonb")))
+                .to_owned()
+        ).await?;
+
+        // Containment (`@>`) is all the listing filters need, which `jsonb_path_ops` indexes compactly.
+        let db = manager.get_connection();
+        db.execute_unprepared(r#"CREATE INDEX idx_videos_tags ON "Videos" USING GIN (tags jsonb_path_ops)"#).await?;
+        db.execute_unprepared(r#"CREATE INDEX idx_group_video_metadata ON "GroupVideo" USING GIN (metadata jsonb_path_ops)"#).await?;
+
+        replace_video_search_vector(manager, VIDEO_SEARCH_VECTOR).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        replace_video_search_vector(manager, PREVIOUS_VIDEO_SEARCH_VECTOR).await?;
+
+        // Dropping the columns drops their indexes too.
+        manager.alter_table(
+            Table::alter()
+                .table(GroupVideo::Table)
+                .drop_column(GroupVideo::Metadata)
+                .to_owned()
+        ).await?;
+
+        manager.alter_table(
+            Table::alter()
+                .table(Videos::Table)
+                .drop_column(Videos::Title)
+                .drop_column(Videos::Description)
+                .drop_column(Videos::Tags)
+                .to_owned()
+        ).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Videos {
+    #[sea_orm(iden = "Videos")]
+    Table,
+    Title,
+    Description,
+    Tags,
+}
+
+#[derive(DeriveIden)]
+enum GroupVideo {
+    #[sea_orm(iden = "GroupVideo")]
+    Table,
+    Metadata,
+}
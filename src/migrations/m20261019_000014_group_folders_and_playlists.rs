@@ -0,0 +1,201 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(GroupFolders::Table)
+                .if_not_exists()
+                .col(ColumnDef::new(GroupFolders::Id).big_integer().not_null().auto_increment().primary_key())
+                .col(ColumnDef::new(GroupFolders::GroupId).big_integer().not_null())
+                .col(ColumnDef::new(GroupFolders::ParentId).big_integer().null())
+                .col(ColumnDef::new(GroupFolders::Name).text().not_null())
+                .col(ColumnDef::new(GroupFolders::Position).integer().not_null().default(0))
+                .col(ColumnDef::new(GroupFolders::CreatedAt).timestamp_with_time_zone().not_null().default(Expr::current_timestamp()))
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(GroupFolders::Table, GroupFolders::GroupId)
+                        .to(Groups::Table, Groups::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(GroupFolders::Table, GroupFolders::ParentId)
+                        .to(GroupFolders::Table, GroupFolders::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .to_owned()
+        ).await?;
+
+        // Sibling folders need distinct names, top level folders have no parent to compare on.
+        manager.get_connection().execute_unprepared(
+            r#"CREATE UNIQUE INDEX "idx_group_folders_name" ON "GroupFolders" (group_id, coalesce(parent_id, 0), lower(name))"#
+        ).await?;
+
+        // Videos in a deleted folder fall back to the top level of the group.
+        manager.alter_table(
+            Table::alter()
+                .table(GroupVideo::Table)
+                .add_column(ColumnDef::new(GroupVideo::FolderId).big_integer().null())
+                .add_foreign_key(
+                    TableForeignKey::new()
+                        .name("fk_group_video_folder_id")
+                        .from_tbl(GroupVideo::Table)
+                        .from_col(GroupVideo::FolderId)
+                        .to_tbl(GroupFolders::Table)
+                        .to_col(GroupFolders::Id)
+                        .on_delete(ForeignKeyAction::SetNull)
+                )
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_group_video_group_id_folder_id")
+                .table(GroupVideo::Table)
+                .col(GroupVideo::GroupId)
+                .col(GroupVideo::FolderId)
+                .to_owned()
+        ).await?;
+
+        manager.create_table(
+            Table::create()
+                .table(GroupPlaylists::Table)
+                .if_not_exists()
+                .col(ColumnDef::new(GroupPlaylists::Id).big_integer().not_null().auto_increment().primary_key())
+                .col(ColumnDef::new(GroupPlaylists::GroupId).big_integer().not_null())
+                .col(ColumnDef::new(GroupPlaylists::Name).text().not_null())
+                .col(ColumnDef::new(GroupPlaylists::Description).text().null())
+                .col(ColumnDef::new(GroupPlaylists::CreatedBy).big_integer().null())
+                .col(ColumnDef::new(GroupPlaylists::CreatedAt).timestamp_with_time_zone().not_null().default(Expr::current_timestamp()))
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(GroupPlaylists::Table, GroupPlaylists::GroupId)
+                        .to(Groups::Table, Groups::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(GroupPlaylists::Table, GroupPlaylists::CreatedBy)
+                        .to(Users::Table, Users::Id)
+                        .on_delete(ForeignKeyAction::SetNull)
+                )
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_group_playlists_group_id")
+                .table(GroupPlaylists::Table)
+                .col(GroupPlaylists::GroupId)
+                .to_owned()
+        ).await?;
+
+        manager.create_table(
+            Table::create()
+                .table(GroupPlaylistVideos::Table)
+                .if_not_exists()
+                .col(ColumnDef::new(GroupPlaylistVideos::PlaylistId).big_integer().not_null())
+                .col(ColumnDef::new(GroupPlaylistVideos::VideoId).big_integer().not_null())
+                .col(ColumnDef::new(GroupPlaylistVideos::Position).integer().not_null())
+                .primary_key(
+                    Index::create()
+                        .col(GroupPlaylistVideos::PlaylistId)
+                        .col(GroupPlaylistVideos::VideoId)
+                )
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(GroupPlaylistVideos::Table, GroupPlaylistVideos::PlaylistId)
+                        .to(GroupPlaylists::Table, GroupPlaylists::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(GroupPlaylistVideos::Table, GroupPlaylistVideos::VideoId)
+                        .to(Videos::Table, Videos::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(GroupPlaylistVideos::Table).to_owned()).await?;
+        manager.drop_table(Table::drop().table(GroupPlaylists::Table).to_owned()).await?;
+
+        manager.alter_table(
+            Table::alter()
+                .table(GroupVideo::Table)
+                .drop_column(GroupVideo::FolderId)
+                .to_owned()
+        ).await?;
+
+        manager.drop_table(Table::drop().table(GroupFolders::Table).to_owned()).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Groups {
+    #[sea_orm(iden = "Groups")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    #[sea_orm(iden = "Users")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Videos {
+    #[sea_orm(iden = "Videos")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum GroupVideo {
+    #[sea_orm(iden = "GroupVideo")]
+    Table,
+    GroupId,
+    FolderId,
+}
+
+#[derive(DeriveIden)]
+enum GroupFolders {
+    #[sea_orm(iden = "GroupFolders")]
+    Table,
+    Id,
+    GroupId,
+    ParentId,
+    Name,
+    Position,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum GroupPlaylists {
+    #[sea_orm(iden = "GroupPlaylists")]
+    Table,
+    Id,
+    GroupId,
+    Name,
+    Description,
+    CreatedBy,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum GroupPlaylistVideos {
+    #[sea_orm(iden = "GroupPlaylistVideos")]
+    Table,
+    PlaylistId,
+    VideoId,
+    Position,
+}
@@ -0,0 +1,103 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(VideoProgress::Table)
+                .if_not_exists()
+                .col(ColumnDef::new(VideoProgress::Id).big_integer().not_null().auto_increment().primary_key())
+                .col(ColumnDef::new(VideoProgress::UserId).big_integer().not_null())
+                .col(ColumnDef::new(VideoProgress::VideoId).big_integer().not_null())
+                .col(ColumnDef::new(VideoProgress::GroupId).big_integer().not_null())
+                .col(ColumnDef::new(VideoProgress::PositionSeconds).double().not_null().default(0))
+                .col(ColumnDef::new(VideoProgress::DurationSeconds).double().null())
+                .col(ColumnDef::new(VideoProgress::Completed).boolean().not_null().default(false))
+                .col(ColumnDef::new(VideoProgress::UpdatedAt).timestamp_with_time_zone().not_null().default(Expr::current_timestamp()))
+                .col(ColumnDef::new(VideoProgress::CompletedAt).timestamp_with_time_zone().null())
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(VideoProgress::Table, VideoProgress::UserId)
+                        .to(Users::Table, Users::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(VideoProgress::Table, VideoProgress::VideoId)
+                        .to(Videos::Table, Videos::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(VideoProgress::Table, VideoProgress::GroupId)
+                        .to(Groups::Table, Groups::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .to_owned()
+        ).await?;
+
+        // One position per user and video, whichever group it was last watched in.
+        manager.create_index(
+            Index::create()
+                .name("idx_video_progress_user_id_video_id")
+                .table(VideoProgress::Table)
+                .col(VideoProgress::UserId)
+                .col(VideoProgress::VideoId)
+                .unique()
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_video_progress_user_id_updated_at")
+                .table(VideoProgress::Table)
+                .col(VideoProgress::UserId)
+                .col(VideoProgress::UpdatedAt)
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(VideoProgress::Table).to_owned()).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    #[sea_orm(iden = "Users")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Videos {
+    #[sea_orm(iden = "Videos")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Groups {
+    #[sea_orm(iden = "Groups")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum VideoProgress {
+    #[sea_orm(iden = "VideoProgress")]
+    Table,
+    Id,
+    UserId,
+    VideoId,
+    GroupId,
+    PositionSeconds,
+    DurationSeconds,
+    Completed,
+    UpdatedAt,
+    CompletedAt,
+}
@@ -0,0 +1,111 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(VideoComments::Table)
+                .if_not_exists()
+                .col(ColumnDef::new(VideoComments::Id).big_integer().not_null().auto_increment().primary_key())
+                .col(ColumnDef::new(VideoComments::GroupId).big_integer().not_null())
+                .col(ColumnDef::new(VideoComments::VideoId).big_integer().not_null())
+                .col(ColumnDef::new(VideoComments::UserId).big_integer().null())
+                .col(ColumnDef::new(VideoComments::ParentId).big_integer().null())
+                .col(ColumnDef::new(VideoComments::Body).text().not_null())
+                .col(ColumnDef::new(VideoComments::StartSeconds).double().null())
+                .col(ColumnDef::new(VideoComments::EndSeconds).double().null())
+                .col(ColumnDef::new(VideoComments::CreatedAt).timestamp_with_time_zone().not_null().default(Expr::current_timestamp()))
+                .col(ColumnDef::new(VideoComments::EditedAt).timestamp_with_time_zone().null())
+                .col(ColumnDef::new(VideoComments::DeletedAt).timestamp_with_time_zone().null())
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(VideoComments::Table, VideoComments::GroupId)
+                        .to(Groups::Table, Groups::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(VideoComments::Table, VideoComments::VideoId)
+                        .to(Videos::Table, Videos::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(VideoComments::Table, VideoComments::UserId)
+                        .to(Users::Table, Users::Id)
+                        .on_delete(ForeignKeyAction::SetNull)
+                )
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(VideoComments::Table, VideoComments::ParentId)
+                        .to(VideoComments::Table, VideoComments::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_video_comments_group_id_video_id_created_at")
+                .table(VideoComments::Table)
+                .col(VideoComments::GroupId)
+                .col(VideoComments::VideoId)
+                .col(VideoComments::CreatedAt)
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_video_comments_parent_id")
+                .table(VideoComments::Table)
+                .col(VideoComments::ParentId)
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(VideoComments::Table).to_owned()).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Groups {
+    #[sea_orm(iden = "Groups")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Videos {
+    #[sea_orm(iden = "Videos")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    #[sea_orm(iden = "Users")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum VideoComments {
+    #[sea_orm(iden = "VideoComments")]
+    Table,
+    Id,
+    GroupId,
+    VideoId,
+    UserId,
+    ParentId,
+    Body,
+    StartSeconds,
+    EndSeconds,
+    CreatedAt,
+    EditedAt,
+    DeletedAt,
+}
@@ -0,0 +1,63 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(VideoSubtitles::Table)
+                .if_not_exists()
+                .col(ColumnDef::new(VideoSubtitles::Id).big_integer().not_null().auto_increment().primary_key())
+                .col(ColumnDef::new(VideoSubtitles::VideoId).big_integer().not_null())
+                .col(ColumnDef::new(VideoSubtitles::Language).text().not_null())
+                .col(ColumnDef::new(VideoSubtitles::Label).text().null())
+                .col(ColumnDef::new(VideoSubtitles::Key).text().not_null().unique_key())
+                .col(ColumnDef::new(VideoSubtitles::CreatedAt).timestamp_with_time_zone().not_null().default(Expr::current_timestamp()))
+                .col(ColumnDef::new(VideoSubtitles::UpdatedAt).timestamp_with_time_zone().not_null().default(Expr::current_timestamp()))
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(VideoSubtitles::Table, VideoSubtitles::VideoId)
+                        .to(Videos::Table, Videos::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_video_subtitles_video_id_language")
+                .table(VideoSubtitles::Table)
+                .col(VideoSubtitles::VideoId)
+                .col(VideoSubtitles::Language)
+                .unique()
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(VideoSubtitles::Table).to_owned()).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Videos {
+    #[sea_orm(iden = "Videos")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum VideoSubtitles {
+    #[sea_orm(iden = "VideoSubtitles")]
+    Table,
+    Id,
+    VideoId,
+    Language,
+    Label,
+    Key,
+    CreatedAt,
+    UpdatedAt,
+}
@@ -0,0 +1,124 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        manager.create_table(
+            Table::create()
+                .table(TranscriptionJobs::Table)
+                .if_not_exists()
+                .col(ColumnDef::new(TranscriptionJobs::Id).big_integer().not_null().auto_increment().primary_key())
+                .col(ColumnDef::new(TranscriptionJobs::VideoId).big_integer().not_null())
+                .col(ColumnDef::new(TranscriptionJobs::Engine).text().not_null())
+                .col(ColumnDef::new(TranscriptionJobs::Status).text().not_null().default("queued"))
+                .col(ColumnDef::new(TranscriptionJobs::Error).text().null())
+                .col(ColumnDef::new(TranscriptionJobs::CreatedAt).timestamp_with_time_zone().not_null().default(Expr::current_timestamp()))
+                .col(ColumnDef::new(TranscriptionJobs::StartedAt).timestamp_with_time_zone().null())
+                .col(ColumnDef::new(TranscriptionJobs::FinishedAt).timestamp_with_time_zone().null())
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(TranscriptionJobs::Table, TranscriptionJobs::VideoId)
+                        .to(Videos::Table, Videos::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_transcription_jobs_video_id_created_at")
+                .table(TranscriptionJobs::Table)
+                .col(TranscriptionJobs::VideoId)
+                .col(TranscriptionJobs::CreatedAt)
+                .to_owned()
+        ).await?;
+
+        // A video is transcribed by one job at a time.
+        db.execute_unprepared(
+            r#"CREATE UNIQUE INDEX "idx_transcription_jobs_active" ON "TranscriptionJobs" (video_id) WHERE status IN ('queued', 'running')"#
+        ).await?;
+
+        manager.create_table(
+            Table::create()
+                .table(TranscriptSegments::Table)
+                .if_not_exists()
+                .col(ColumnDef::new(TranscriptSegments::Id).big_integer().not_null().auto_increment().primary_key())
+                .col(ColumnDef::new(TranscriptSegments::VideoId).big_integer().not_null())
+                .col(ColumnDef::new(TranscriptSegments::Position).integer().not_null())
+                .col(ColumnDef::new(TranscriptSegments::StartSeconds).double().not_null())
+                .col(ColumnDef::new(TranscriptSegments::EndSeconds).double().not_null())
+                .col(ColumnDef::new(TranscriptSegments::Text).text().not_null())
+                .foreign_key(
+                    ForeignKey::create()
+                        .from(TranscriptSegments::Table, TranscriptSegments::VideoId)
+                        .to(Videos::Table, Videos::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                )
+                .to_owned()
+        ).await?;
+
+        manager.create_index(
+            Index::create()
+                .name("idx_transcript_segments_video_id_position")
+                .table(TranscriptSegments::Table)
+                .col(TranscriptSegments::VideoId)
+                .col(TranscriptSegments::Position)
+                .unique()
+                .to_owned()
+        ).await?;
+
+        // Same `simple` configuration as the other search vectors, see the search migration.
+        db.execute_unprepared(
+            r#"ALTER TABLE "TranscriptSegments" ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (to_tsvector('simple', text)) STORED"#
+        ).await?;
+
+        db.execute_unprepared(
+            r#"CREATE INDEX idx_transcript_segments_search_vector ON "TranscriptSegments" USING GIN (search_vector)"#
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(TranscriptSegments::Table).to_owned()).await?;
+        manager.drop_table(Table::drop().table(TranscriptionJobs::Table).to_owned()).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Videos {
+    #[sea_orm(iden = "Videos")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum TranscriptionJobs {
+    #[sea_orm(iden = "TranscriptionJobs")]
+    Table,
+    Id,
+    VideoId,
+    Engine,
+    Status,
+    Error,
+    CreatedAt,
+    StartedAt,
+    FinishedAt,
+}
+
+#[derive(DeriveIden)]
+enum TranscriptSegments {
+    #[sea_orm(iden = "TranscriptSegments")]
+    Table,
+    Id,
+    VideoId,
+    Position,
+    StartSeconds,
+    EndSeconds,
+    Text,
+}
@@ -0,0 +1,50 @@
+use sea_orm_migration::prelude::*;
+
+mod m20261019_000001_email_verification;
+mod m20261019_000002_password_reset;
+mod m20261019_000003_totp;
+mod m20261019_000004_api_tokens;
+mod m20261019_000005_login_throttling;
+mod m20261019_000006_unique_group_membership;
+mod m20261019_000007_group_member_roles;
+mod m20261019_000008_group_invites;
+mod m20261019_000009_group_join_requests;
+mod m20261019_000010_group_description;
+mod m20261019_000011_group_visibility;
+mod m20261019_000012_search;
+mod m20261019_000013_video_metadata;
+mod m20261019_000014_group_folders_and_playlists;
+mod m20261019_000015_video_progress;
+mod m20261019_000016_video_comments;
+mod m20261019_000017_video_subtitles;
+mod m20261019_000018_transcripts;
+mod m20261019_000019_user_admin_flag;
+
+pub struct Migrator;
+
+#[async_trait::async_trait]
+impl MigratorTrait for Migrator {
+    fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+        vec![
+            Box::new(m20261019_000001_email_verification::Migration),
+            Box::new(m20261019_000002_password_reset::Migration),
+            Box::new(m20261019_000003_totp::Migration),
+            Box::new(m20261019_000004_api_tokens::Migration),
+            Box::new(m20261019_000005_login_throttling::Migration),
+            Box::new(m20261019_000006_unique_group_membership::Migration),
+            Box::new(m20261019_000007_group_member_roles::Migration),
+            Box::new(m20261019_000008_group_invites::Migration),
+            Box::new(m20261019_000009_group_join_requests::Migration),
+            Box::new(m20261019_000010_group_description::Migration),
+            Box::new(m20261019_000011_group_visibility::Migration),
+            Box::new(m20261019_000012_search::Migration),
+            Box::new(m20261019_000013_video_metadata::Migration),
+            Box::new(m20261019_000014_group_folders_and_playlists::Migration),
+            Box::new(m20261019_000015_video_progress::Migration),
+            Box::new(m20261019_000016_video_comments::Migration),
+            Box::new(m20261019_000017_video_subtitles::Migration),
+            Box::new(m20261019_000018_transcripts::Migration),
+            Box::new(m20261019_000019_user_admin_flag::Migration),
+        ]
+    }
+}
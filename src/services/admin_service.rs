@@ -1,3 +1,68 @@
+use actix_web::web;
+use chrono::Utc;
+use sea_orm::{DatabaseConnection, DbErr, EntityTrait};
+use sea_orm::ActiveValue::Set;
+use sea_orm::sea_query::OnConflict;
+use crate::dtos::user_dto::MfaPolicy;
+use crate::entities::{settings, users};
+use crate::errors::AppError;
+use crate::services::{audit_service, throttle_service};
+use crate::services::audit_service::AuditEvent;
+use crate::services::auth_service::UserClaims;
 
+const REQUIRE_ADMIN_MFA: &str = "require_admin_mfa";
 
-/* todo */
\ No newline at end of file
+pub async fn require_admin_mfa(db: &DatabaseConnection) -> Result<bool, DbErr> {
+    let setting = settings::Entity::find_by_id(REQUIRE_ADMIN_MFA.to_string()).one(db).await?;
+    Ok(setting.is_some_and(|setting| setting.value == "true"))
+}
+
+pub async fn get_mfa_policy(db: web::Data<DatabaseConnection>) -> Result<MfaPolicy, AppError> {
+    let require_admin_mfa = require_admin_mfa(db.get_ref()).await?;
+    Ok(MfaPolicy { require_admin_mfa })
+}
+
+pub async fn set_mfa_policy(db: web::Data<DatabaseConnection>, policy: web::Json<MfaPolicy>) -> Result<MfaPolicy, AppError> {
+    let setting = settings::ActiveModel {
+        key: Set(REQUIRE_ADMIN_MFA.to_string()),
+        value: Set(policy.require_admin_mfa.to_string()),
+        updated_at: Set(Utc::now().fixed_offset()),
+    };
+
+    settings::Entity::insert(setting)
+        .on_conflict(
+            OnConflict::column(settings::Column::Key)
+                .update_columns([settings::Column::Value, settings::Column::UpdatedAt])
+                .to_owned()
+        )
+        .exec(db.get_ref())
+        .await?;
+
+    Ok(policy.into_inner())
+}
+
+pub async fn unlock_user(
+    db: web::Data<DatabaseConnection>,
+    user_id: web::Path<i64>,
+    admin_claims: UserClaims,
+    client_ip: &str,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+
+    let user = users::Entity::find_by_id(user_id.into_inner())
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("User not found!"))?;
+
+    let cleared = throttle_service::unlock_account(db, user.id, &user.email).await?;
+
+    audit_service::record(db, AuditEvent {
+        event: audit_service::ACCOUNT_UNLOCKED,
+        user_id: Some(user.id),
+        actor_id: Some(admin_claims.id),
+        ip_address: Some(client_ip),
+        details: Some(format!("{} throttle(s) cleared", cleared)),
+    }).await;
+
+    Ok(())
+}
@@ -0,0 +1,154 @@
+use std::time::Duration;
+use actix_web::web;
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, IntoSimpleExpr, QueryFilter, QueryOrder};
+use sea_orm::ActiveValue::Set;
+use sha2::{Digest, Sha256};
+use crate::dtos::api_token_dto::{ApiTokenResponse, CreateApiToken, CreatedApiTokenResponse};
+use crate::dtos::pagination_dto::{ListQuery, Page, SortOrder};
+use crate::entities::{api_tokens, users};
+use crate::errors::AppError;
+use crate::services::auth_service::{ApiScope, Role, UserClaims};
+use crate::services::pagination_service;
+
+pub const API_TOKEN_PREFIX: &str = "lbx_";
+const LAST_USED_RESOLUTION: Duration = Duration::from_secs(60);
+
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+fn parse_scopes(scopes: &str) -> Vec<ApiScope> {
+    scopes.split_whitespace()
+        .filter_map(|scope| serde_json::from_value(serde_json::Value::String(scope.to_string())).ok())
+        .collect()
+}
+
+fn format_scopes(scopes: &[ApiScope]) -> String {
+    scopes.iter()
+        .filter_map(|scope| serde_json::to_value(scope).ok())
+        .filter_map(|scope| scope.as_str().map(str::to_string))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn to_response(token: api_tokens::Model) -> ApiTokenResponse {
+    ApiTokenResponse {
+        id: token.id,
+        name: token.name,
+        prefix: token.prefix,
+        scopes: parse_scopes(&token.scopes),
+        created_at: token.created_at.to_rfc3339(),
+        expires_at: token.expires_at.map(|expires_at| expires_at.to_rfc3339()),
+        last_used_at: token.last_used_at.map(|last_used_at| last_used_at.to_rfc3339()),
+        revoked: token.revoked_at.is_some(),
+    }
+}
+
+/// Resolves an API token to the claims of its owner, restricted to the token's scopes.
+pub async fn authenticate(db: &DatabaseConnection, token: &str) -> Result<UserClaims, AppError> {
+    let api_token = api_tokens::Entity::find()
+        .filter(api_tokens::Column::TokenHash.eq(hash_token(token)))
+        .find_also_related(users::Entity)
+        .one(db)
+        .await?;
+
+    let now = Utc::now();
+    let (api_token, user) = match api_token {
+        Some((api_token, Some(user)))
+            if api_token.revoked_at.is_none()
+                && api_token.expires_at.is_none_or(|expires_at| expires_at > now)
+                && !user.is_deleted => (api_token, user),
+        _ => return Err(AppError::unauthorized("Invalid, expired or revoked API token!")),
+    };
+
+    let scopes = parse_scopes(&api_token.scopes);
+
+    if api_token.last_used_at.is_none_or(|last_used_at| last_used_at + LAST_USED_RESOLUTION < now) {
+        let mut api_token = api_token.into_active_model();
+        api_token.last_used_at = Set(Some(now.fixed_offset()));
+        if let Err(e) = api_token.update(db).await {
+            log::warn!("Failed to record API token usage: {}", e);
+        }
+    }
+
+    Ok(UserClaims {
+        id: user.id,
+        role: Role::RegisteredUser,
+        session_version: user.session_version,
+        scopes: Some(scopes),
+    })
+}
+
+pub async fn create_token(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    form: web::Json<CreateApiToken>,
+) -> Result<CreatedApiTokenResponse, AppError> {
+    let form = form.into_inner();
+
+    let secret = nanoid::nanoid!(40);
+    let token = format!("{}{}", API_TOKEN_PREFIX, secret);
+    let now = Utc::now();
+
+    let api_token = api_tokens::ActiveModel {
+        user_id: Set(user_claims.id),
+        name: Set(form.name.trim().to_string()),
+        token_hash: Set(hash_token(&token)),
+        prefix: Set(token[..API_TOKEN_PREFIX.len() + 6].to_string()),
+        scopes: Set(format_scopes(&form.scopes)),
+        created_at: Set(now.fixed_offset()),
+        expires_at: Set(form.expires_in_days.map(|days| (now + chrono::Duration::days(days as i64)).fixed_offset())),
+        ..Default::default()
+    };
+
+    let api_token = api_token.insert(db.get_ref()).await?;
+
+    Ok(CreatedApiTokenResponse {
+        details: to_response(api_token),
+        token,
+    })
+}
+
+pub async fn list_tokens(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    query: web::Query<ListQuery>,
+) -> Result<Page<ApiTokenResponse>, AppError> {
+    let select = api_tokens::Entity::find()
+        .filter(api_tokens::Column::UserId.eq(user_claims.id));
+
+    let select = pagination_service::sort(select, &query, &[
+        ("created_at", api_tokens::Column::CreatedAt.into_simple_expr()),
+        ("last_used_at", api_tokens::Column::LastUsedAt.into_simple_expr()),
+        ("name", api_tokens::Column::Name.into_simple_expr()),
+    ], ("created_at", SortOrder::Desc))?;
+
+    let tokens = pagination_service::paginate(select.order_by_desc(api_tokens::Column::Id), db.get_ref(), &query).await?;
+
+    Ok(tokens.map(to_response))
+}
+
+pub async fn revoke_token(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    token_id: web::Path<i64>,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+
+    let api_token = api_tokens::Entity::find_by_id(token_id.into_inner())
+        .filter(api_tokens::Column::UserId.eq(user_claims.id))
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("API token not found!"))?;
+
+    if api_token.revoked_at.is_some() {
+        return Ok(());
+    }
+
+    let mut api_token = api_token.into_active_model();
+    api_token.revoked_at = Set(Some(Utc::now().fixed_offset()));
+    api_token.update(db).await?;
+
+    Ok(())
+}
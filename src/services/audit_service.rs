@@ -0,0 +1,35 @@
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, DatabaseConnection};
+use sea_orm::ActiveValue::Set;
+use crate::entities::audit_events;
+
+pub const ACCOUNT_LOCKED: &str = "account_locked";
+pub const ACCOUNT_UNLOCKED: &str = "account_unlocked";
+pub const IP_LOCKED: &str = "ip_locked";
+
+pub struct AuditEvent<'a> {
+    pub event: &'a str,
+    pub user_id: Option<i64>,
+    pub actor_id: Option<i64>,
+    pub ip_address: Option<&'a str>,
+    pub details: Option<String>,
+}
+
+/// Stores an audit event. Failures are only logged, auditing never fails the request that caused it.
+pub async fn record(db: &DatabaseConnection, event: AuditEvent<'_>) {
+    log::info!("Audit event {} (user: {:?}, actor: {:?}, ip: {:?})", event.event, event.user_id, event.actor_id, event.ip_address);
+
+    let model = audit_events::ActiveModel {
+        event: Set(event.event.to_string()),
+        user_id: Set(event.user_id),
+        actor_id: Set(event.actor_id),
+        ip_address: Set(event.ip_address.map(str::to_string)),
+        details: Set(event.details),
+        created_at: Set(Utc::now().fixed_offset()),
+        ..Default::default()
+    };
+
+    if let Err(e) = model.insert(db).await {
+        log::error!("Failed to store audit event {}: {}", event.event, e);
+    }
+}
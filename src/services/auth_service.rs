@@ -1,18 +1,37 @@
 use std::future::Future;
 use std::pin::Pin;
-use actix_web::{error, Error, FromRequest, HttpRequest, HttpResponse, ResponseError};
-use actix_web::body::{BoxBody, MessageBody};
+use std::time::Duration;
+use actix_web::{web, Error, FromRequest, HttpRequest};
+use actix_web::body::MessageBody;
 use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::http::Method;
 use actix_web::middleware::Next;
-use jsonwebtoken::{decode, DecodingKey, Validation};
-use jsonwebtoken::Algorithm::HS256;
+use sea_orm::{DatabaseConnection, EntityTrait};
 use serde::{Deserialize, Serialize};
-use shuttle_runtime::SecretStore;
+use crate::entities::users;
+use crate::errors::AppError;
+use crate::services::api_token_service;
+use crate::services::api_token_service::API_TOKEN_PREFIX;
+use crate::services::token_service::TokenService;
 
 #[derive(Clone, Serialize, Deserialize, Debug, Hash, PartialEq, Eq)]
 pub struct UserClaims {
     pub id: i64,
     pub role: Role,
+    #[serde(default)]
+    pub session_version: i32,
+    /// Set when the request was authenticated with an API token, limiting it to these scopes.
+    #[serde(skip)]
+    pub scopes: Option<Vec<ApiScope>>,
+}
+
+/// The `typ` claim of a user token, so a refresh token can't stand in for an access token.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenType {
+    Access,
+    Refresh,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq, Hash)]
@@ -21,52 +40,66 @@ pub enum Role {
     RegisteredUser,
 }
 
-#[derive(Debug)]
-pub struct CookieError {
-    message: String,
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ApiScope {
+    #[serde(rename = "profile:read")]
+    ProfileRead,
+    #[serde(rename = "groups:read")]
+    GroupsRead,
+    #[serde(rename = "groups:write")]
+    GroupsWrite,
+    #[serde(rename = "videos:read")]
+    VideosRead,
+    #[serde(rename = "videos:write")]
+    VideosWrite,
 }
 
-impl CookieError {
-    fn new(message: &str) -> CookieError {
-        CookieError {
-            message: message.to_string(),
+impl ApiScope {
+    /// The scope an API token needs for a request, `None` for routes API tokens can't use at all.
+    pub fn required_for(method: &Method, path: &str) -> Option<ApiScope> {
+        let read = method == Method::GET || method == Method::HEAD;
+
+        if path.starts_with("/storage") || (path.starts_with("/groups") && path.contains("/videos")) || path.starts_with("/users/history") || path == "/search" {
+            Some(if read { ApiScope::VideosRead } else { ApiScope::VideosWrite })
+        } else if path.starts_with("/groups") || path.starts_with("/users/join/group") || path.starts_with("/users/groups") || path.starts_with("/users/invites") || path.starts_with("/users/join-requests") {
+            Some(if read { ApiScope::GroupsRead } else { ApiScope::GroupsWrite })
+        } else if path == "/users/current" && read {
+            Some(ApiScope::ProfileRead)
+        } else {
+            None
         }
     }
 }
 
-impl std::fmt::Display for CookieError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.message)
-    }
-}
+pub const ACCESS_TOKEN_COOKIE: &str = "access_token";
+pub const REFRESH_TOKEN_COOKIE: &str = "refresh_token";
 
-impl ResponseError for CookieError {
-    fn error_response(&self) -> HttpResponse<BoxBody> {
-        HttpResponse::Unauthorized().body(self.message.clone())
+pub const ACCESS_TOKEN_LIFETIME: Duration = Duration::from_secs(3600);
+pub const REFRESH_TOKEN_LIFETIME: Duration = Duration::from_secs(3600 * 24);
+
+pub fn extract_access_token(req: &HttpRequest) -> Result<String, AppError> {
+    if let Some(header) = req.headers().get(AUTHORIZATION) {
+        let header = header
+            .to_str()
+            .map_err(|_| AppError::unauthorized("Authorization header contains invalid characters!"))?;
+
+        return match header.split_once(' ') {
+            Some((scheme, token)) if scheme.eq_ignore_ascii_case("Bearer") && !token.trim().is_empty() => {
+                Ok(token.trim().to_owned())
+            },
+            _ => Err(AppError::unauthorized("Authorization header must use the Bearer scheme!")),
+        };
     }
-}
 
-pub fn extract_access_token(req: &HttpRequest) -> Result<String, CookieError> {
-    let cookie = req.headers()
-        .get("Cookie")
-        .ok_or(CookieError::new("Cookie header not found!"))?
-        .to_str()
-        .map_err(|_| CookieError::new("Cookie header contains invalid characters!"))?
-        .to_owned();
-
-    let access_token = cookie.split(';')
-        .nth(0)
-        .ok_or(CookieError::new("No cookie found in header!"))?
-        .split("=")
-        .nth(1)
-        .ok_or(CookieError::new("Access token not found in cookie!"))?
-        .to_owned();
-
-    Ok(access_token)
+    let cookie = req
+        .cookie(ACCESS_TOKEN_COOKIE)
+        .ok_or(AppError::unauthorized("Access token not found in cookie!"))?;
+
+    Ok(cookie.value().to_owned())
 }
 
 impl FromRequest for UserClaims {
-    type Error = actix_web::Error;
+    type Error = AppError;
     type Future = Pin<Box<dyn Future<Output=Result<Self, Self::Error>>>>;
 
     fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
@@ -75,27 +108,45 @@ impl FromRequest for UserClaims {
         let access_token = match extract_access_token(&req) {
             Ok(token) => token,
             Err(error) => { return Box::pin(async {
-                    Err(error.into())
+                    Err(error)
                 });
             }
         };
 
         Box::pin( async move {
-            get_claim(&access_token).await
+            let token_service = req.app_data::<web::Data<TokenService>>()
+                .ok_or(AppError::internal("token service is not configured"))?;
+            let db = req.app_data::<web::Data<DatabaseConnection>>()
+                .ok_or(AppError::internal("database is not configured"))?;
+
+            if access_token.starts_with(API_TOKEN_PREFIX) {
+                let user_claims = api_token_service::authenticate(db, &access_token).await?;
+
+                return match ApiScope::required_for(req.method(), req.path()) {
+                    Some(scope) if user_claims.scopes.as_ref().is_some_and(|scopes| scopes.contains(&scope)) => Ok(user_claims),
+                    _ => Err(AppError::forbidden("API token is missing the required scope!")),
+                };
+            }
+
+            let user_claims = get_claim(token_service, &access_token).await?;
+            ensure_session_is_current(db, &user_claims).await?;
+
+            Ok(user_claims)
         })
     }
 }
 
-pub async fn get_claim(token: &String) -> Result<UserClaims, actix_web::Error> {
-    let decoded_token = decode::<UserClaims>(
-        token,
-        &DecodingKey::from_secret(std::env::var("JWT_PRIVATE_KEY").unwrap_or_default().to_string().as_ref()),
-        &Validation::new(HS256)
-    );
+pub async fn get_claim(token_service: &TokenService, token: &str) -> Result<UserClaims, AppError> {
+    token_service.verify_user_token(token, TokenType::Access)
+}
+
+/// Rejects tokens of deleted accounts and tokens issued before the account's sessions were revoked.
+async fn ensure_session_is_current(db: &DatabaseConnection, user_claims: &UserClaims) -> Result<(), AppError> {
+    let user = users::Entity::find_by_id(user_claims.id).one(db).await?;
 
-    match decoded_token {
-        Ok(token_data) => Ok(token_data.claims),
-        Err(_e) => Err(actix_web::error::ErrorUnauthorized("Invalid or expired access token!")),
+    match user {
+        Some(user) if !user.is_deleted && user.session_version == user_claims.session_version => Ok(()),
+        _ => Err(AppError::unauthorized("Invalid or expired access token!")),
     }
 }
 
@@ -106,7 +157,7 @@ pub async fn is_admin(
 ) -> Result<ServiceResponse<impl MessageBody>, Error> {
 
     if user_claims.role != Role::Admin {
-        return Err(error::ErrorForbidden("Requires Administrator privileges!"))
+        return Err(AppError::forbidden("Requires Administrator privileges!").into())
     };
 
     next.call(req).await
@@ -119,7 +170,7 @@ pub async fn is_registered(
 ) -> Result<ServiceResponse<impl MessageBody>, Error> {
 
     if user_claims.role != Role::RegisteredUser {
-        return Err(error::ErrorForbidden("Requires Registration!"))
+        return Err(AppError::forbidden("Requires Registration!").into())
     };
 
     next.call(req).await
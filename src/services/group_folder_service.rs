@@ -0,0 +1,317 @@
+use actix_web::web;
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, IntoSimpleExpr, QueryFilter, QueryOrder, QuerySelect};
+use sea_orm::ActiveValue::Set;
+use crate::dtos::group_folder_dto::{CreateFolderForm, FolderFilter, FolderResponse, MoveFolderForm, MoveVideoForm, UpdateFolderForm};
+use crate::dtos::pagination_dto::{ListQuery, Page, SortOrder};
+use crate::entities::{group_folders, group_video};
+use crate::errors::AppError;
+use crate::services::auth_service::UserClaims;
+use crate::services::{group_service, pagination_service};
+
+const ROOT_FOLDER: &str = "root";
+const DUPLICATE_NAME: (&str, &str) = ("name", "A folder with this name already exists here!");
+
+fn to_response(folder: group_folders::Model) -> FolderResponse {
+    FolderResponse {
+        id: folder.id,
+        group_id: folder.group_id,
+        parent_id: folder.parent_id,
+        name: folder.name,
+        position: folder.position,
+        created_at: folder.created_at.to_rfc3339(),
+    }
+}
+
+/// Reads a `root` or folder id query parameter, `None` standing for the top level of the group.
+pub fn parse_folder(field: &str, value: &str) -> Result<Option<i64>, AppError> {
+    match value.trim() {
+        ROOT_FOLDER => Ok(None),
+        value => value.parse().map(Some).map_err(|_| AppError::field(field, "Must be `root` or a folder id.")),
+    }
+}
+
+pub async fn find_folder(db: &DatabaseConnection, group_id: i64, folder_id: i64) -> Result<group_folders::Model, AppError> {
+    group_folders::Entity::find_by_id(folder_id)
+        .filter(group_folders::Column::GroupId.eq(group_id))
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("Folder not found!"))
+}
+
+/// New folders go after their existing siblings.
+async fn next_position(db: &DatabaseConnection, group_id: i64, parent_id: Option<i64>) -> Result<i32, AppError> {
+    let mut select = group_folders::Entity::find()
+        .select_only()
+        .column_as(group_folders::Column::Position.max(), "position")
+        .filter(group_folders::Column::GroupId.eq(group_id));
+
+    select = match parent_id {
+        Some(parent_id) => select.filter(group_folders::Column::ParentId.eq(parent_id)),
+        None => select.filter(group_folders::Column::ParentId.is_null()),
+    };
+
+    let last: Option<Option<i32>> = select.into_tuple().one(db).await?;
+
+    Ok(last.flatten().map_or(0, |position| position + 1))
+}
+
+pub async fn list_folders(
+    db: web::Data<DatabaseConnection>,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+    filter: web::Query<FolderFilter>,
+    query: web::Query<ListQuery>,
+) -> Result<Page<FolderResponse>, AppError> {
+    let db = db.get_ref();
+    let group_id = group_id.into_inner();
+
+    group_service::require_viewer(db, group_id, user_claims.id).await?;
+
+    let mut select = group_folders::Entity::find()
+        .filter(group_folders::Column::GroupId.eq(group_id));
+
+    if let Some(parent) = &filter.parent {
+        select = match parse_folder("parent", parent)? {
+            Some(parent_id) => select.filter(group_folders::Column::ParentId.eq(parent_id)),
+            None => select.filter(group_folders::Column::ParentId.is_null()),
+        };
+    }
+
+    let select = pagination_service::sort(select, &query, &[
+        ("position", group_folders::Column::Position.into_simple_expr()),
+        ("name", group_folders::Column::Name.into_simple_expr()),
+        ("created_at", group_folders::Column::CreatedAt.into_simple_expr()),
+    ], ("position", SortOrder::Asc))?;
+
+    let folders = pagination_service::paginate(
+        select.order_by_asc(group_folders::Column::Name).order_by_asc(group_folders::Column::Id),
+        db,
+        &query,
+    ).await?;
+
+    Ok(folders.map(to_response))
+}
+
+pub async fn create_folder(
+    db: web::Data<DatabaseConnection>,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+    form: web::Json<CreateFolderForm>,
+) -> Result<FolderResponse, AppError> {
+    let db = db.get_ref();
+    let group_id = group_id.into_inner();
+
+    group_service::require_manager(db, group_id, user_claims.id).await?;
+
+    if let Some(parent_id) = form.parent_id {
+        find_folder(db, group_id, parent_id).await?;
+    }
+
+    let folder = group_folders::ActiveModel {
+        group_id: Set(group_id),
+        parent_id: Set(form.parent_id),
+        name: Set(form.name.trim().to_string()),
+        position: Set(next_position(db, group_id, form.parent_id).await?),
+        created_at: Set(Utc::now().fixed_offset()),
+        ..Default::default()
+    };
+
+    let folder = folder.insert(db).await.map_err(|e| AppError::unique(e, &[DUPLICATE_NAME]))?;
+
+    Ok(to_response(folder))
+}
+
+pub async fn update_folder(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: web::Json<UpdateFolderForm>,
+) -> Result<FolderResponse, AppError> {
+    let db = db.get_ref();
+    let (group_id, folder_id) = path.into_inner();
+
+    group_service::require_manager(db, group_id, user_claims.id).await?;
+
+    let mut folder = find_folder(db, group_id, folder_id).await?.into_active_model();
+
+    if let Some(name) = &form.name {
+        folder.name = Set(name.trim().to_string());
+    }
+    if let Some(position) = form.position {
+        folder.position = Set(position);
+    }
+
+    let folder = folder.update(db).await.map_err(|e| AppError::unique(e, &[DUPLICATE_NAME]))?;
+
+    Ok(to_response(folder))
+}
+
+/// Moves a folder and everything in it under another folder of the same group.
+pub async fn move_folder(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: web::Json<MoveFolderForm>,
+) -> Result<FolderResponse, AppError> {
+    let db = db.get_ref();
+    let (group_id, folder_id) = path.into_inner();
+
+    group_service::require_manager(db, group_id, user_claims.id).await?;
+
+    let folder = find_folder(db, group_id, folder_id).await?;
+
+    // Walk up from the new parent, meeting the folder on the way means it would end up inside itself.
+    let mut ancestor_id = form.parent_id;
+    while let Some(id) = ancestor_id {
+        if id == folder.id {
+            return Err(AppError::field("parent_id", "A folder can't be moved into itself or one of its subfolders."));
+        }
+        ancestor_id = find_folder(db, group_id, id).await?.parent_id;
+    }
+
+    let position = next_position(db, group_id, form.parent_id).await?;
+
+    let mut folder = folder.into_active_model();
+    folder.parent_id = Set(form.parent_id);
+    folder.position = Set(position);
+
+    let folder = folder.update(db).await.map_err(|e| AppError::unique(e, &[DUPLICATE_NAME]))?;
+
+    Ok(to_response(folder))
+}
+
+/// Deletes the folder with its subfolders, the videos in them move to the top level of the group.
+pub async fn delete_folder(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+    let (group_id, folder_id) = path.into_inner();
+
+    group_service::require_manager(db, group_id, user_claims.id).await?;
+
+    let folder = find_folder(db, group_id, folder_id).await?;
+    group_folders::Entity::delete_by_id(folder.id).exec(db).await?;
+
+    Ok(())
+}
+
+pub async fn move_video(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: web::Json<MoveVideoForm>,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+    let (group_id, video_id) = path.into_inner();
+
+    group_service::require_manager(db, group_id, user_claims.id).await?;
+
+    if let Some(folder_id) = form.folder_id {
+        find_folder(db, group_id, folder_id).await?;
+    }
+
+    let moved = group_video::Entity::update_many()
+        .col_expr(group_video::Column::FolderId, form.folder_id.into())
+        .filter(group_video::Column::GroupId.eq(group_id))
+        .filter(group_video::Column::VideoId.eq(video_id))
+        .exec(db)
+        .await?;
+
+    if moved.rows_affected == 0 {
+        return Err(AppError::not_found("Video not found!"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{DatabaseBackend, MockDatabase};
+    use crate::entities::{group_user, groups};
+    use crate::services::auth_service::Role;
+    use crate::services::group_service::{GroupRole, GroupVisibility};
+    use super::*;
+
+    const GROUP_ID: i64 = 1;
+    const USER_ID: i64 = 7;
+
+    fn claims() -> UserClaims {
+        UserClaims { id: USER_ID, role: Role::RegisteredUser, session_version: 0, scopes: None }
+    }
+
+    /// Answers the lookups `require_manager` makes for a caller with the given role.
+    fn db(role: GroupRole) -> MockDatabase {
+        MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![groups::Model {
+                id: GROUP_ID,
+                name: "film-club".to_string(),
+                password: None,
+                created_at: Utc::now().fixed_offset(),
+                is_deleted: false,
+                description: None,
+                visibility: GroupVisibility::InviteOnly.as_str().to_string(),
+            }]])
+            .append_query_results([vec![group_user::Model {
+                id: 1,
+                group_id: GROUP_ID,
+                user_id: USER_ID,
+                joined_at: Utc::now().fixed_offset(),
+                role: role.as_str().to_string(),
+            }]])
+    }
+
+    fn folder(id: i64, parent_id: Option<i64>) -> group_folders::Model {
+        group_folders::Model {
+            id,
+            group_id: GROUP_ID,
+            parent_id,
+            name: format!("folder-{}", id),
+            position: 0,
+            created_at: Utc::now().fixed_offset(),
+        }
+    }
+
+    #[test]
+    fn folder_parameters_accept_root_or_an_id() {
+        assert_eq!(parse_folder("parent", " root ").unwrap(), None);
+        assert_eq!(parse_folder("parent", "42").unwrap(), Some(42));
+        assert!(matches!(parse_folder("parent", "../1"), Err(AppError::Validation(_))));
+    }
+
+    #[actix_web::test]
+    async fn members_cannot_create_folders() {
+        let form = web::Json(CreateFolderForm { name: "Season 1".to_string(), parent_id: None });
+
+        let result = create_folder(web::Data::new(db(GroupRole::Member).into_connection()), web::Path::from(GROUP_ID), claims(), form).await;
+
+        assert!(matches!(result, Err(AppError::Forbidden(_))), "{:?}", result);
+    }
+
+    #[actix_web::test]
+    async fn folders_cannot_move_into_their_own_subfolders() {
+        let db = db(GroupRole::Admin)
+            .append_query_results([vec![folder(1, None)]])
+            .append_query_results([vec![folder(2, Some(1))]]);
+        let form = web::Json(MoveFolderForm { parent_id: Some(2) });
+
+        let result = move_folder(web::Data::new(db.into_connection()), web::Path::from((GROUP_ID, 1)), claims(), form).await;
+
+        match result {
+            Err(AppError::Validation(errors)) => assert!(errors.contains_key("parent_id")),
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[actix_web::test]
+    async fn folders_cannot_move_into_themselves() {
+        let db = db(GroupRole::Admin).append_query_results([vec![folder(1, None)]]);
+        let form = web::Json(MoveFolderForm { parent_id: Some(1) });
+
+        let result = move_folder(web::Data::new(db.into_connection()), web::Path::from((GROUP_ID, 1)), claims(), form).await;
+
+        assert!(matches!(result, Err(AppError::Validation(_))), "{:?}", result);
+    }
+}
@@ -0,0 +1,469 @@
+use actix_web::web;
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, EntityTrait, IntoActiveModel, IntoSimpleExpr, QueryFilter, QueryOrder, SqlErr, TransactionTrait};
+use sea_orm::ActiveValue::Set;
+use sea_orm::sea_query::Expr;
+use sha2::{Digest, Sha256};
+use crate::dtos::group_dto::GroupMembershipResponse;
+use crate::dtos::group_invite_dto::{AcceptGroupInvite, CreateGroupInvite, CreatedGroupInviteResponse, GroupInviteResponse, PendingGroupInviteResponse};
+use crate::dtos::pagination_dto::{ListQuery, Page, SortOrder};
+use crate::entities::{group_invites, group_user, groups, users};
+use crate::errors::AppError;
+use crate::services::auth_service::UserClaims;
+use crate::services::{group_service, pagination_service};
+use crate::services::group_service::GroupRole;
+use crate::services::mail_service::{Email, Mailer};
+
+const INVITE_CODE_LENGTH: usize = 24;
+const INVITE_PREFIX_LENGTH: usize = 6;
+const DEFAULT_INVITE_LIFETIME_HOURS: u32 = 24 * 7;
+
+fn hash_code(code: &str) -> String {
+    format!("{:x}", Sha256::digest(code.trim().as_bytes()))
+}
+
+/// Builds the shareable link from `GROUP_INVITE_URL`, codes are handed out on their own when it isn't set.
+fn invite_link(code: &str) -> Option<String> {
+    std::env::var("GROUP_INVITE_URL").ok()
+        .filter(|url| !url.is_empty())
+        .map(|url| format!("{}?code={}", url, code))
+}
+
+fn to_response(invite: group_invites::Model) -> GroupInviteResponse {
+    GroupInviteResponse {
+        id: invite.id,
+        group_id: invite.group_id,
+        prefix: invite.prefix,
+        role: GroupRole::from_db(&invite.role),
+        invited_user_id: invite.invited_user_id,
+        invited_email: invite.invited_email,
+        max_uses: invite.max_uses,
+        uses: invite.uses,
+        created_at: invite.created_at.to_rfc3339(),
+        expires_at: invite.expires_at.map(|expires_at| expires_at.to_rfc3339()),
+        revoked: invite.revoked_at.is_some(),
+    }
+}
+
+/// Invites that can still be redeemed: not revoked, not expired and not used up.
+fn redeemable() -> Condition {
+    Condition::all()
+        .add(group_invites::Column::RevokedAt.is_null())
+        .add(
+            Condition::any()
+                .add(group_invites::Column::ExpiresAt.is_null())
+                .add(group_invites::Column::ExpiresAt.gt(Utc::now()))
+        )
+        .add(
+            Condition::any()
+                .add(group_invites::Column::MaxUses.is_null())
+                .add(Expr::col(group_invites::Column::Uses).lt(Expr::col(group_invites::Column::MaxUses)))
+        )
+}
+
+async fn send_invite_email(mailer: &dyn Mailer, group: &groups::Model, email: &str, code: &str) {
+    let link = invite_link(code).unwrap_or_else(|| format!("Invite code: {}", code));
+
+    let email = Email {
+        to: email.to_string(),
+        subject: format!("You've been invited to {} on LockBox", group.name),
+        body: format!(
+            "You've been invited to join the group \"{}\" on LockBox.\n\nSign in with this email address and accept the invite below:\n{}",
+            group.name, link
+        ),
+    };
+
+    if let Err(e) = mailer.send(email).await {
+        log::warn!("Failed to send invite for group {}: {}", group.id, e);
+    }
+}
+
+pub async fn create_invite(
+    db: web::Data<DatabaseConnection>,
+    mailer: web::Data<dyn Mailer>,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+    form: web::Json<CreateGroupInvite>,
+) -> Result<CreatedGroupInviteResponse, AppError> {
+    let db = db.get_ref();
+    let group_id = group_id.into_inner();
+    let form = form.into_inner();
+
+    let caller_role = group_service::require_manager(db, group_id, user_claims.id).await?;
+    let role = form.role.unwrap_or(GroupRole::Member);
+
+    if role == GroupRole::Owner {
+        return Err(AppError::field("role", "A group can only have one owner."));
+    }
+    if role >= caller_role {
+        return Err(AppError::field("role", "You can only invite members with a lower role than yours."));
+    }
+    if form.user_id.is_some() && form.email.is_some() {
+        return Err(AppError::field("user_id", "Invite either a user or an email address, not both."));
+    }
+
+    if let Some(user_id) = form.user_id {
+        users::Entity::find_by_id(user_id)
+            .filter(users::Column::IsDeleted.eq(false))
+            .one(db)
+            .await?
+            .ok_or(AppError::field("user_id", "User not found."))?;
+
+        if group_service::find_membership(db, group_id, user_id).await?.is_some() {
+            return Err(AppError::conflict("The user is already a member of this group!"));
+        }
+    }
+
+    let invited_email = form.email.map(|email| email.trim().to_lowercase());
+    let is_direct = form.user_id.is_some() || invited_email.is_some();
+
+    let code = nanoid::nanoid!(INVITE_CODE_LENGTH);
+    let now = Utc::now();
+    let lifetime_hours = form.expires_in_hours.unwrap_or(DEFAULT_INVITE_LIFETIME_HOURS);
+
+    let invite = group_invites::ActiveModel {
+        group_id: Set(group_id),
+        created_by: Set(Some(user_claims.id)),
+        code_hash: Set(hash_code(&code)),
+        prefix: Set(code[..INVITE_PREFIX_LENGTH].to_string()),
+        role: Set(role.as_str().to_string()),
+        invited_user_id: Set(form.user_id),
+        invited_email: Set(invited_email.clone()),
+        // Direct invites are meant for one person, so they are single-use unless stated otherwise.
+        max_uses: Set(form.max_uses.map(|max_uses| max_uses as i32).or(is_direct.then_some(1))),
+        uses: Set(0),
+        created_at: Set(now.fixed_offset()),
+        expires_at: Set(Some((now + chrono::Duration::hours(lifetime_hours as i64)).fixed_offset())),
+        ..Default::default()
+    };
+
+    let invite = invite.insert(db).await?;
+
+    if let Some(email) = &invited_email {
+        if let Some(group) = groups::Entity::find_by_id(group_id).one(db).await? {
+            send_invite_email(mailer.get_ref(), &group, email, &code).await;
+        }
+    }
+
+    Ok(CreatedGroupInviteResponse {
+        details: to_response(invite),
+        link: invite_link(&code),
+        code,
+    })
+}
+
+pub async fn list_invites(
+    db: web::Data<DatabaseConnection>,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+    query: web::Query<ListQuery>,
+) -> Result<Page<GroupInviteResponse>, AppError> {
+    let db = db.get_ref();
+    let group_id = group_id.into_inner();
+
+    group_service::require_manager(db, group_id, user_claims.id).await?;
+
+    let select = group_invites::Entity::find()
+        .filter(group_invites::Column::GroupId.eq(group_id));
+
+    let select = pagination_service::sort(select, &query, &[
+        ("created_at", group_invites::Column::CreatedAt.into_simple_expr()),
+        ("expires_at", group_invites::Column::ExpiresAt.into_simple_expr()),
+        ("uses", group_invites::Column::Uses.into_simple_expr()),
+    ], ("created_at", SortOrder::Desc))?;
+
+    let invites = pagination_service::paginate(select.order_by_desc(group_invites::Column::Id), db, &query).await?;
+
+    Ok(invites.map(to_response))
+}
+
+pub async fn revoke_invite(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+    let (group_id, invite_id) = path.into_inner();
+
+    group_service::require_manager(db, group_id, user_claims.id).await?;
+
+    let invite = group_invites::Entity::find_by_id(invite_id)
+        .filter(group_invites::Column::GroupId.eq(group_id))
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("Invite not found!"))?;
+
+    if invite.revoked_at.is_some() {
+        return Ok(());
+    }
+
+    let mut invite = invite.into_active_model();
+    invite.revoked_at = Set(Some(Utc::now().fixed_offset()));
+    invite.update(db).await?;
+
+    Ok(())
+}
+
+async fn find_user(db: &DatabaseConnection, user_id: i64) -> Result<users::Model, AppError> {
+    users::Entity::find_by_id(user_id)
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("User not found!"))
+}
+
+/// Direct invites addressed to the user, either by account or by email address.
+fn addressed_to(user: &users::Model) -> Condition {
+    Condition::any()
+        .add(group_invites::Column::InvitedUserId.eq(user.id))
+        .add(group_invites::Column::InvitedEmail.eq(user.email.trim().to_lowercase()))
+}
+
+pub async fn list_pending_invites(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    query: web::Query<ListQuery>,
+) -> Result<Page<PendingGroupInviteResponse>, AppError> {
+    let db = db.get_ref();
+    let user = find_user(db, user_claims.id).await?;
+
+    let select = group_invites::Entity::find()
+        .filter(addressed_to(&user))
+        .filter(redeemable())
+        .find_also_related(groups::Entity)
+        .filter(groups::Column::IsDeleted.eq(false));
+
+    let select = pagination_service::sort(select, &query, &[
+        ("created_at", group_invites::Column::CreatedAt.into_simple_expr()),
+        ("expires_at", group_invites::Column::ExpiresAt.into_simple_expr()),
+    ], ("created_at", SortOrder::Desc))?;
+
+    let invites = pagination_service::paginate(select.order_by_desc(group_invites::Column::Id), db, &query).await?;
+
+    // The filter on the group's columns makes this an inner join, so the group is always there.
+    Ok(invites.map(|(invite, group)| PendingGroupInviteResponse {
+        id: invite.id,
+        group_id: invite.group_id,
+        group_name: group.map(|group| group.name).unwrap_or_default(),
+        role: GroupRole::from_db(&invite.role),
+        expires_at: invite.expires_at.map(|expires_at| expires_at.to_rfc3339()),
+    }))
+}
+
+fn membership_response(group: groups::Model, membership: group_user::Model) -> GroupMembershipResponse {
+    GroupMembershipResponse {
+        group_id: group.id,
+        name: group.name,
+        role: GroupRole::from_db(&membership.role),
+        joined_at: membership.joined_at.to_rfc3339(),
+    }
+}
+
+/// Consumes one use of the invite and adds the user to its group with the invite's role.
+/// Users who already belong to the group keep their membership and don't use up the invite.
+async fn redeem(db: &DatabaseConnection, invite: group_invites::Model, user: users::Model) -> Result<GroupMembershipResponse, AppError> {
+    let now = Utc::now();
+
+    if invite.revoked_at.is_some() || invite.expires_at.is_some_and(|expires_at| expires_at <= now) {
+        return Err(AppError::conflict("This invite has expired or was revoked!"));
+    }
+
+    let is_direct = invite.invited_user_id.is_some() || invite.invited_email.is_some();
+    let is_addressee = invite.invited_user_id == Some(user.id)
+        || invite.invited_email.as_deref().is_some_and(|email| email.eq_ignore_ascii_case(user.email.trim()));
+
+    if is_direct && !is_addressee {
+        return Err(AppError::forbidden("This invite is addressed to someone else!"));
+    }
+
+    let group = groups::Entity::find_by_id(invite.group_id)
+        .filter(groups::Column::IsDeleted.eq(false))
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("Group not found!"))?;
+
+    if let Some(membership) = group_service::find_membership(db, group.id, user.id).await? {
+        return Ok(membership_response(group, membership));
+    }
+
+    let transaction = db.begin().await?;
+
+    let consumed = group_invites::Entity::update_many()
+        .col_expr(group_invites::Column::Uses, Expr::col(group_invites::Column::Uses).add(1))
+        .filter(group_invites::Column::Id.eq(invite.id))
+        .filter(redeemable())
+        .exec(&transaction)
+        .await?;
+
+    if consumed.rows_affected == 0 {
+        return Err(AppError::conflict("This invite has already been used up!"));
+    }
+
+    let membership = group_user::ActiveModel {
+        group_id: Set(group.id),
+        user_id: Set(user.id),
+        role: Set(GroupRole::from_db(&invite.role).as_str().to_string()),
+        ..Default::default()
+    };
+
+    let membership = match membership.insert(&transaction).await {
+        Ok(membership) => membership,
+        // Joined concurrently through another route, the transaction is rolled back so the use isn't counted.
+        Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+            drop(transaction);
+            let membership = group_service::find_membership(db, group.id, user.id)
+                .await?
+                .ok_or(AppError::internal("membership vanished after a unique violation"))?;
+            return Ok(membership_response(group, membership));
+        },
+        Err(e) => return Err(e.into()),
+    };
+
+    transaction.commit().await?;
+
+    Ok(membership_response(group, membership))
+}
+
+pub async fn accept_invite(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    form: web::Json<AcceptGroupInvite>,
+) -> Result<GroupMembershipResponse, AppError> {
+    let db = db.get_ref();
+    let user = find_user(db, user_claims.id).await?;
+
+    let invite = group_invites::Entity::find()
+        .filter(group_invites::Column::CodeHash.eq(hash_code(&form.code)))
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("Invite not found!"))?;
+
+    redeem(db, invite, user).await
+}
+
+pub async fn accept_direct_invite(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    invite_id: web::Path<i64>,
+) -> Result<GroupMembershipResponse, AppError> {
+    let db = db.get_ref();
+    let user = find_user(db, user_claims.id).await?;
+
+    let invite = group_invites::Entity::find_by_id(invite_id.into_inner())
+        .filter(addressed_to(&user))
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("Invite not found!"))?;
+
+    redeem(db, invite, user).await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult, QueryTrait};
+    use crate::services::group_service::GroupVisibility;
+    use super::*;
+
+    const GROUP_ID: i64 = 1;
+    const USER_ID: i64 = 7;
+
+    fn invite() -> group_invites::Model {
+        group_invites::Model {
+            id: 3,
+            group_id: GROUP_ID,
+            created_by: Some(1),
+            code_hash: hash_code("invite-code"),
+            prefix: "invite".to_string(),
+            role: GroupRole::Member.as_str().to_string(),
+            invited_user_id: None,
+            invited_email: None,
+            max_uses: Some(1),
+            uses: 0,
+            created_at: Utc::now().fixed_offset(),
+            expires_at: Some((Utc::now() + Duration::days(1)).fixed_offset()),
+            revoked_at: None,
+        }
+    }
+
+    fn user() -> users::Model {
+        users::Model {
+            id: USER_ID,
+            username: Some("jane".to_string()),
+            email: "jane@example.com".to_string(),
+            password: None,
+            created_at: Utc::now().fixed_offset(),
+            is_deleted: false,
+            email_verified: true,
+            session_version: 0,
+            totp_secret: None,
+            totp_enabled: false,
+            totp_last_step: None,
+            is_admin: false,
+        }
+    }
+
+    fn group() -> groups::Model {
+        groups::Model {
+            id: GROUP_ID,
+            name: "film-club".to_string(),
+            password: None,
+            created_at: Utc::now().fixed_offset(),
+            is_deleted: false,
+            description: None,
+            visibility: GroupVisibility::InviteOnly.as_str().to_string(),
+        }
+    }
+
+    fn empty_db() -> DatabaseConnection {
+        MockDatabase::new(DatabaseBackend::Postgres).into_connection()
+    }
+
+    #[actix_web::test]
+    async fn expired_invites_cannot_be_redeemed() {
+        let invite = group_invites::Model { expires_at: Some((Utc::now() - Duration::minutes(1)).fixed_offset()), ..invite() };
+
+        let result = redeem(&empty_db(), invite, user()).await;
+
+        assert!(matches!(result, Err(AppError::Conflict(_))), "{:?}", result);
+    }
+
+    #[actix_web::test]
+    async fn revoked_invites_cannot_be_redeemed() {
+        let invite = group_invites::Model { revoked_at: Some(Utc::now().fixed_offset()), ..invite() };
+
+        let result = redeem(&empty_db(), invite, user()).await;
+
+        assert!(matches!(result, Err(AppError::Conflict(_))), "{:?}", result);
+    }
+
+    #[actix_web::test]
+    async fn used_up_invites_cannot_be_redeemed() {
+        // The use is only counted while the invite is still redeemable, a concurrent redemption
+        // that took the last use leaves nothing to update.
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![group()]])
+            .append_query_results([Vec::<group_user::Model>::new()])
+            .append_exec_results([MockExecResult { last_insert_id: 0, rows_affected: 0 }])
+            .into_connection();
+
+        let result = redeem(&db, invite(), user()).await;
+
+        assert!(matches!(result, Err(AppError::Conflict(_))), "{:?}", result);
+    }
+
+    #[actix_web::test]
+    async fn direct_invites_only_admit_their_addressee() {
+        let invite = group_invites::Model { invited_email: Some("john@example.com".to_string()), ..invite() };
+
+        let result = redeem(&empty_db(), invite, user()).await;
+
+        assert!(matches!(result, Err(AppError::Forbidden(_))), "{:?}", result);
+    }
+
+    #[test]
+    fn redemption_requires_a_remaining_use() {
+        let sql = group_invites::Entity::find().filter(redeemable()).build(DatabaseBackend::Postgres).to_string();
+
+        assert!(sql.contains(r#""uses" < "max_uses""#), "{}", sql);
+    }
+}
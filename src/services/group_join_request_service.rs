@@ -0,0 +1,358 @@
+use actix_web::web;
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, SqlErr, TransactionTrait, IntoSimpleExpr};
+use sea_orm::ActiveValue::Set;
+use serde::{Deserialize, Serialize};
+use crate::dtos::group_join_request_dto::{CreateJoinRequest, JoinRequestFilter, JoinRequestResponse};
+use crate::dtos::pagination_dto::{ListQuery, Page, SortOrder};
+use crate::entities::{group_join_requests, group_user, groups, users};
+use crate::errors::AppError;
+use crate::services::auth_service::UserClaims;
+use crate::services::{group_service, pagination_service};
+use crate::services::group_service::{GroupRole, GroupVisibility};
+use crate::services::mail_service::{Email, Mailer};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum JoinRequestStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl JoinRequestStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JoinRequestStatus::Pending => "pending",
+            JoinRequestStatus::Approved => "approved",
+            JoinRequestStatus::Rejected => "rejected",
+        }
+    }
+
+    pub fn from_db(status: &str) -> JoinRequestStatus {
+        match status {
+            "approved" => JoinRequestStatus::Approved,
+            "rejected" => JoinRequestStatus::Rejected,
+            _ => JoinRequestStatus::Pending,
+        }
+    }
+}
+
+fn to_response(request: group_join_requests::Model, user: Option<users::Model>) -> JoinRequestResponse {
+    JoinRequestResponse {
+        id: request.id,
+        group_id: request.group_id,
+        user_id: request.user_id,
+        username: user.and_then(|user| user.username),
+        status: JoinRequestStatus::from_db(&request.status),
+        message: request.message,
+        created_at: request.created_at.to_rfc3339(),
+        reviewed_at: request.reviewed_at.map(|reviewed_at| reviewed_at.to_rfc3339()),
+    }
+}
+
+/// Tells the requester how their request was decided, delivery failures don't undo the decision.
+async fn notify_requester(db: &DatabaseConnection, mailer: &dyn Mailer, request: &group_join_requests::Model, status: JoinRequestStatus) {
+    let recipient = users::Entity::find_by_id(request.user_id).one(db).await;
+    let group = groups::Entity::find_by_id(request.group_id).one(db).await;
+
+    let (user, group) = match (recipient, group) {
+        (Ok(Some(user)), Ok(Some(group))) if !user.is_deleted => (user, group),
+        _ => return,
+    };
+
+    let outcome = match status {
+        JoinRequestStatus::Approved => "approved, you are now a member",
+        _ => "declined",
+    };
+
+    let email = Email {
+        to: user.email.clone(),
+        subject: format!("Your request to join {} on LockBox", group.name),
+        body: format!("Your request to join the group \"{}\" was {}.", group.name, outcome),
+    };
+
+    if let Err(e) = mailer.send(email).await {
+        log::warn!("Failed to notify user {} about join request {}: {}", user.id, request.id, e);
+    }
+}
+
+pub async fn create_request(
+    db: web::Data<DatabaseConnection>,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+    form: web::Json<CreateJoinRequest>,
+) -> Result<JoinRequestResponse, AppError> {
+    let db = db.get_ref();
+    let group_id = group_id.into_inner();
+
+    let group = groups::Entity::find_by_id(group_id)
+        .filter(groups::Column::IsDeleted.eq(false))
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("Group not found!"))?;
+
+    if group_service::find_membership(db, group_id, user_claims.id).await?.is_some() {
+        return Err(AppError::conflict("You are already a member of this group!"));
+    }
+
+    // Hidden groups are invite-only and don't reveal that they exist.
+    if GroupVisibility::from_db(&group.visibility) == GroupVisibility::Hidden {
+        return Err(AppError::not_found("Group not found!"));
+    }
+
+    let request = group_join_requests::ActiveModel {
+        group_id: Set(group_id),
+        user_id: Set(user_claims.id),
+        status: Set(JoinRequestStatus::Pending.as_str().to_string()),
+        message: Set(form.message.as_deref().map(str::trim).filter(|message| !message.is_empty()).map(str::to_string)),
+        created_at: Set(Utc::now().fixed_offset()),
+        ..Default::default()
+    };
+
+    let request = request.insert(db).await.map_err(|e| match e.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(_)) => AppError::conflict("You already have a pending request to join this group!"),
+        _ => e.into(),
+    })?;
+
+    Ok(to_response(request, None))
+}
+
+pub async fn list_requests(
+    db: web::Data<DatabaseConnection>,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+    filter: web::Query<JoinRequestFilter>,
+    query: web::Query<ListQuery>,
+) -> Result<Page<JoinRequestResponse>, AppError> {
+    let db = db.get_ref();
+    let group_id = group_id.into_inner();
+
+    group_service::require_manager(db, group_id, user_claims.id).await?;
+
+    let status = filter.status.unwrap_or(JoinRequestStatus::Pending);
+
+    let select = group_join_requests::Entity::find()
+        .filter(group_join_requests::Column::GroupId.eq(group_id))
+        .filter(group_join_requests::Column::Status.eq(status.as_str()))
+        .find_also_linked(group_join_requests::Requester);
+
+    let select = pagination_service::sort(select, &query, &[
+        ("created_at", group_join_requests::Column::CreatedAt.into_simple_expr()),
+        ("reviewed_at", group_join_requests::Column::ReviewedAt.into_simple_expr()),
+    ], ("created_at", SortOrder::Asc))?;
+
+    let requests = pagination_service::paginate(select.order_by_asc(group_join_requests::Column::Id), db, &query).await?;
+
+    Ok(requests.map(|(request, user)| to_response(request, user)))
+}
+
+pub async fn list_own_requests(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    filter: web::Query<JoinRequestFilter>,
+    query: web::Query<ListQuery>,
+) -> Result<Page<JoinRequestResponse>, AppError> {
+    let db = db.get_ref();
+
+    let mut select = group_join_requests::Entity::find()
+        .filter(group_join_requests::Column::UserId.eq(user_claims.id));
+
+    if let Some(status) = filter.status {
+        select = select.filter(group_join_requests::Column::Status.eq(status.as_str()));
+    }
+
+    let select = pagination_service::sort(select, &query, &[
+        ("created_at", group_join_requests::Column::CreatedAt.into_simple_expr()),
+        ("reviewed_at", group_join_requests::Column::ReviewedAt.into_simple_expr()),
+    ], ("created_at", SortOrder::Desc))?;
+
+    let requests = pagination_service::paginate(select.order_by_desc(group_join_requests::Column::Id), db, &query).await?;
+
+    Ok(requests.map(|request| to_response(request, None)))
+}
+
+/// Moves a pending request to `status`, approving it also adds the requester as a member.
+pub async fn review_request(
+    db: web::Data<DatabaseConnection>,
+    mailer: web::Data<dyn Mailer>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    status: JoinRequestStatus,
+) -> Result<JoinRequestResponse, AppError> {
+    let db = db.get_ref();
+    let (group_id, request_id) = path.into_inner();
+
+    group_service::require_manager(db, group_id, user_claims.id).await?;
+
+    let request = group_join_requests::Entity::find_by_id(request_id)
+        .filter(group_join_requests::Column::GroupId.eq(group_id))
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("Join request not found!"))?;
+
+    let transaction = db.begin().await?;
+
+    // Only the first of two concurrent reviews wins.
+    let reviewed = group_join_requests::Entity::update_many()
+        .col_expr(group_join_requests::Column::Status, status.as_str().into())
+        .col_expr(group_join_requests::Column::ReviewedBy, Some(user_claims.id).into())
+        .col_expr(group_join_requests::Column::ReviewedAt, Some(Utc::now().fixed_offset()).into())
+        .filter(group_join_requests::Column::Id.eq(request.id))
+        .filter(group_join_requests::Column::Status.eq(JoinRequestStatus::Pending.as_str()))
+        .exec(&transaction)
+        .await?;
+
+    if reviewed.rows_affected == 0 {
+        return Err(AppError::conflict("This join request has already been reviewed!"));
+    }
+
+    // The requester may have joined through an invite in the meantime, which is fine.
+    let is_member = group_user::Entity::find()
+        .filter(group_user::Column::GroupId.eq(group_id))
+        .filter(group_user::Column::UserId.eq(request.user_id))
+        .one(&transaction)
+        .await?
+        .is_some();
+
+    if status == JoinRequestStatus::Approved && !is_member {
+        let membership = group_user::ActiveModel {
+            group_id: Set(group_id),
+            user_id: Set(request.user_id),
+            role: Set(GroupRole::Member.as_str().to_string()),
+            ..Default::default()
+        };
+        membership.insert(&transaction).await?;
+    }
+
+    transaction.commit().await?;
+
+    let request = group_join_requests::Entity::find_by_id(request.id)
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("Join request not found!"))?;
+
+    notify_requester(db, mailer.get_ref(), &request, status).await;
+
+    Ok(to_response(request, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult};
+    use crate::services::auth_service::Role;
+    use crate::services::mail_service::FileMailer;
+    use super::*;
+
+    const GROUP_ID: i64 = 1;
+    const REQUEST_ID: i64 = 5;
+    const REQUESTER_ID: i64 = 40;
+    const REVIEWER_ID: i64 = 20;
+
+    fn claims(id: i64) -> UserClaims {
+        UserClaims { id, role: Role::RegisteredUser, session_version: 0, scopes: None }
+    }
+
+    fn group() -> groups::Model {
+        groups::Model {
+            id: GROUP_ID,
+            name: "film-club".to_string(),
+            password: None,
+            created_at: Utc::now().fixed_offset(),
+            is_deleted: false,
+            description: None,
+            visibility: GroupVisibility::InviteOnly.as_str().to_string(),
+        }
+    }
+
+    fn membership(role: GroupRole) -> group_user::Model {
+        group_user::Model {
+            id: REVIEWER_ID,
+            group_id: GROUP_ID,
+            user_id: REVIEWER_ID,
+            joined_at: Utc::now().fixed_offset(),
+            role: role.as_str().to_string(),
+        }
+    }
+
+    fn request() -> group_join_requests::Model {
+        group_join_requests::Model {
+            id: REQUEST_ID,
+            group_id: GROUP_ID,
+            user_id: REQUESTER_ID,
+            status: JoinRequestStatus::Pending.as_str().to_string(),
+            message: None,
+            reviewed_by: None,
+            created_at: Utc::now().fixed_offset(),
+            reviewed_at: None,
+        }
+    }
+
+    fn mailer(outbox: &tempfile::TempDir) -> web::Data<dyn Mailer> {
+        web::Data::from(Arc::new(FileMailer::new(outbox.path())) as Arc<dyn Mailer>)
+    }
+
+    async fn review(db: MockDatabase, outbox: &tempfile::TempDir) -> Result<JoinRequestResponse, AppError> {
+        review_request(
+            web::Data::new(db.into_connection()),
+            mailer(outbox),
+            web::Path::from((GROUP_ID, REQUEST_ID)),
+            claims(REVIEWER_ID),
+            JoinRequestStatus::Approved,
+        ).await
+    }
+
+    #[actix_web::test]
+    async fn members_cannot_review_join_requests() {
+        let outbox = tempfile::tempdir().unwrap();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![group()]])
+            .append_query_results([vec![membership(GroupRole::Member)]]);
+
+        let result = review(db, &outbox).await;
+
+        assert!(matches!(result, Err(AppError::Forbidden(_))), "{:?}", result);
+        assert_eq!(std::fs::read_dir(outbox.path()).unwrap().count(), 0);
+    }
+
+    #[actix_web::test]
+    async fn non_members_cannot_review_join_requests() {
+        let outbox = tempfile::tempdir().unwrap();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![group()]])
+            .append_query_results([Vec::<group_user::Model>::new()]);
+
+        let result = review(db, &outbox).await;
+
+        assert!(matches!(result, Err(AppError::Forbidden(_))), "{:?}", result);
+    }
+
+    #[actix_web::test]
+    async fn requests_are_only_reviewed_once() {
+        let outbox = tempfile::tempdir().unwrap();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![group()]])
+            .append_query_results([vec![membership(GroupRole::Admin)]])
+            .append_query_results([vec![request()]])
+            .append_exec_results([MockExecResult { last_insert_id: 0, rows_affected: 0 }]);
+
+        let result = review(db, &outbox).await;
+
+        assert!(matches!(result, Err(AppError::Conflict(_))), "{:?}", result);
+    }
+
+    #[actix_web::test]
+    async fn hidden_groups_do_not_take_join_requests() {
+        let hidden = groups::Model { visibility: GroupVisibility::Hidden.as_str().to_string(), ..group() };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![hidden]])
+            .append_query_results([Vec::<group_user::Model>::new()])
+            .into_connection();
+        let form = web::Json(CreateJoinRequest { message: None });
+
+        let result = create_request(web::Data::new(db), web::Path::from(GROUP_ID), claims(REQUESTER_ID), form).await;
+
+        assert!(matches!(result, Err(AppError::NotFound(_))), "{:?}", result);
+    }
+}
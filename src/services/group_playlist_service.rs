@@ -0,0 +1,337 @@
+use std::collections::HashSet;
+use actix_web::web;
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, IntoSimpleExpr, JoinType, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, RelationTrait, SqlErr, TransactionTrait};
+use sea_orm::ActiveValue::Set;
+use crate::dtos::group_playlist_dto::{AddPlaylistVideo, CreatePlaylistForm, PlaylistResponse, ReorderPlaylist, UpdatePlaylistForm, MAX_PLAYLIST_VIDEOS};
+use crate::dtos::pagination_dto::{ListQuery, Page, SortOrder};
+use crate::dtos::video_dto::VideoResponse;
+use crate::entities::{group_playlist_videos, group_playlists, group_video, videos};
+use crate::entities::prelude::{GroupVideo, Videos};
+use crate::errors::AppError;
+use crate::services::auth_service::UserClaims;
+use crate::services::{group_service, pagination_service, video_service};
+
+fn to_response(playlist: group_playlists::Model) -> PlaylistResponse {
+    PlaylistResponse {
+        id: playlist.id,
+        group_id: playlist.group_id,
+        name: playlist.name,
+        description: playlist.description,
+        created_by: playlist.created_by,
+        created_at: playlist.created_at.to_rfc3339(),
+    }
+}
+
+async fn find_playlist(db: &DatabaseConnection, group_id: i64, playlist_id: i64) -> Result<group_playlists::Model, AppError> {
+    group_playlists::Entity::find_by_id(playlist_id)
+        .filter(group_playlists::Column::GroupId.eq(group_id))
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("Playlist not found!"))
+}
+
+/// Playlists can only hold videos shared in their group.
+async fn require_group_videos(db: &DatabaseConnection, group_id: i64, video_ids: &[i64]) -> Result<(), AppError> {
+    let found = GroupVideo::find()
+        .filter(group_video::Column::GroupId.eq(group_id))
+        .filter(group_video::Column::VideoId.is_in(video_ids.iter().copied()))
+        .count(db)
+        .await?;
+
+    if found as usize != video_ids.len() {
+        return Err(AppError::not_found("Video not found!"));
+    }
+
+    Ok(())
+}
+
+pub async fn list_playlists(
+    db: web::Data<DatabaseConnection>,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+    query: web::Query<ListQuery>,
+) -> Result<Page<PlaylistResponse>, AppError> {
+    let db = db.get_ref();
+    let group_id = group_id.into_inner();
+
+    group_service::require_viewer(db, group_id, user_claims.id).await?;
+
+    let select = group_playlists::Entity::find()
+        .filter(group_playlists::Column::GroupId.eq(group_id));
+
+    let select = pagination_service::sort(select, &query, &[
+        ("name", group_playlists::Column::Name.into_simple_expr()),
+        ("created_at", group_playlists::Column::CreatedAt.into_simple_expr()),
+    ], ("name", SortOrder::Asc))?;
+
+    let playlists = pagination_service::paginate(select.order_by_asc(group_playlists::Column::Id), db, &query).await?;
+
+    Ok(playlists.map(to_response))
+}
+
+pub async fn create_playlist(
+    db: web::Data<DatabaseConnection>,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+    form: web::Json<CreatePlaylistForm>,
+) -> Result<PlaylistResponse, AppError> {
+    let db = db.get_ref();
+    let group_id = group_id.into_inner();
+
+    group_service::require_manager(db, group_id, user_claims.id).await?;
+
+    let playlist = group_playlists::ActiveModel {
+        group_id: Set(group_id),
+        name: Set(form.name.trim().to_string()),
+        description: Set(form.description.as_deref().map(str::trim).filter(|description| !description.is_empty()).map(str::to_string)),
+        created_by: Set(Some(user_claims.id)),
+        created_at: Set(Utc::now().fixed_offset()),
+        ..Default::default()
+    };
+
+    Ok(to_response(playlist.insert(db).await?))
+}
+
+pub async fn update_playlist(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: web::Json<UpdatePlaylistForm>,
+) -> Result<PlaylistResponse, AppError> {
+    let db = db.get_ref();
+    let (group_id, playlist_id) = path.into_inner();
+
+    group_service::require_manager(db, group_id, user_claims.id).await?;
+
+    let mut playlist = find_playlist(db, group_id, playlist_id).await?.into_active_model();
+
+    if let Some(name) = &form.name {
+        playlist.name = Set(name.trim().to_string());
+    }
+    if let Some(description) = &form.description {
+        playlist.description = Set(Some(description.trim().to_string()).filter(|description| !description.is_empty()));
+    }
+
+    Ok(to_response(playlist.update(db).await?))
+}
+
+pub async fn delete_playlist(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+    let (group_id, playlist_id) = path.into_inner();
+
+    group_service::require_manager(db, group_id, user_claims.id).await?;
+
+    let playlist = find_playlist(db, group_id, playlist_id).await?;
+    group_playlists::Entity::delete_by_id(playlist.id).exec(db).await?;
+
+    Ok(())
+}
+
+/// The playlist's videos in playlist order, `sort` doesn't apply here.
+pub async fn list_playlist_videos(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    query: web::Query<ListQuery>,
+) -> Result<Page<VideoResponse>, AppError> {
+    let db = db.get_ref();
+    let (group_id, playlist_id) = path.into_inner();
+
+    group_service::require_viewer(db, group_id, user_claims.id).await?;
+    let playlist = find_playlist(db, group_id, playlist_id).await?;
+
+    let select = Videos::find()
+        .join(JoinType::InnerJoin, group_playlist_videos::Relation::Videos.def().rev())
+        .find_also_related(GroupVideo)
+        .filter(group_playlist_videos::Column::PlaylistId.eq(playlist.id))
+        .filter(group_video::Column::GroupId.eq(group_id))
+        .order_by_asc(group_playlist_videos::Column::Position)
+        .order_by_asc(videos::Column::Id);
+
+    let videos = pagination_service::paginate(select, db, &query).await?;
+
+    Ok(videos.map(|(video, entry)| video_service::to_response(video, entry)))
+}
+
+/// Appends a video to the end of the playlist.
+pub async fn add_playlist_video(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: web::Json<AddPlaylistVideo>,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+    let (group_id, playlist_id) = path.into_inner();
+
+    group_service::require_manager(db, group_id, user_claims.id).await?;
+    let playlist = find_playlist(db, group_id, playlist_id).await?;
+    require_group_videos(db, group_id, &[form.video_id]).await?;
+
+    let entries = group_playlist_videos::Entity::find()
+        .filter(group_playlist_videos::Column::PlaylistId.eq(playlist.id))
+        .count(db)
+        .await?;
+
+    if entries as usize >= MAX_PLAYLIST_VIDEOS {
+        return Err(AppError::conflict("The playlist is full!"));
+    }
+
+    let last: Option<Option<i32>> = group_playlist_videos::Entity::find()
+        .select_only()
+        .column_as(group_playlist_videos::Column::Position.max(), "position")
+        .filter(group_playlist_videos::Column::PlaylistId.eq(playlist.id))
+        .into_tuple()
+        .one(db)
+        .await?;
+
+    let entry = group_playlist_videos::ActiveModel {
+        playlist_id: Set(playlist.id),
+        video_id: Set(form.video_id),
+        position: Set(last.flatten().map_or(0, |position| position + 1)),
+    };
+
+    entry.insert(db).await.map_err(|e| match e.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(_)) => AppError::conflict("The video is already in this playlist!"),
+        _ => e.into(),
+    })?;
+
+    Ok(())
+}
+
+/// Replaces the playlist's videos with the given ones, which is also how videos are reordered.
+pub async fn reorder_playlist(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: web::Json<ReorderPlaylist>,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+    let (group_id, playlist_id) = path.into_inner();
+
+    group_service::require_manager(db, group_id, user_claims.id).await?;
+    let playlist = find_playlist(db, group_id, playlist_id).await?;
+
+    let unique: HashSet<i64> = form.video_ids.iter().copied().collect();
+    if unique.len() != form.video_ids.len() {
+        return Err(AppError::field("video_ids", "Must not contain a video twice."));
+    }
+    require_group_videos(db, group_id, &form.video_ids).await?;
+
+    let transaction = db.begin().await?;
+
+    group_playlist_videos::Entity::delete_many()
+        .filter(group_playlist_videos::Column::PlaylistId.eq(playlist.id))
+        .exec(&transaction)
+        .await?;
+
+    if !form.video_ids.is_empty() {
+        let entries = form.video_ids.iter().enumerate().map(|(position, video_id)| group_playlist_videos::ActiveModel {
+            playlist_id: Set(playlist.id),
+            video_id: Set(*video_id),
+            position: Set(position as i32),
+        });
+        group_playlist_videos::Entity::insert_many(entries).exec(&transaction).await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+pub async fn remove_playlist_video(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+    let (group_id, playlist_id, video_id) = path.into_inner();
+
+    group_service::require_manager(db, group_id, user_claims.id).await?;
+    let playlist = find_playlist(db, group_id, playlist_id).await?;
+
+    let removed = group_playlist_videos::Entity::delete_many()
+        .filter(group_playlist_videos::Column::PlaylistId.eq(playlist.id))
+        .filter(group_playlist_videos::Column::VideoId.eq(video_id))
+        .exec(db)
+        .await?;
+
+    if removed.rows_affected == 0 {
+        return Err(AppError::not_found("Video not found!"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use sea_orm::{DatabaseBackend, MockDatabase, Value};
+    use crate::entities::{group_user, groups};
+    use crate::services::auth_service::Role;
+    use crate::services::group_service::{GroupRole, GroupVisibility};
+    use super::*;
+
+    const GROUP_ID: i64 = 1;
+    const PLAYLIST_ID: i64 = 3;
+    const USER_ID: i64 = 7;
+
+    /// Answers the lookups of a reorder by a group admin up to the playlist itself.
+    fn db() -> MockDatabase {
+        MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![groups::Model {
+                id: GROUP_ID,
+                name: "film-club".to_string(),
+                password: None,
+                created_at: Utc::now().fixed_offset(),
+                is_deleted: false,
+                description: None,
+                visibility: GroupVisibility::InviteOnly.as_str().to_string(),
+            }]])
+            .append_query_results([vec![group_user::Model {
+                id: 1,
+                group_id: GROUP_ID,
+                user_id: USER_ID,
+                joined_at: Utc::now().fixed_offset(),
+                role: GroupRole::Admin.as_str().to_string(),
+            }]])
+            .append_query_results([vec![group_playlists::Model {
+                id: PLAYLIST_ID,
+                group_id: GROUP_ID,
+                name: "Favourites".to_string(),
+                description: None,
+                created_by: Some(USER_ID),
+                created_at: Utc::now().fixed_offset(),
+            }]])
+    }
+
+    async fn reorder(db: MockDatabase, video_ids: Vec<i64>) -> Result<(), AppError> {
+        reorder_playlist(
+            web::Data::new(db.into_connection()),
+            web::Path::from((GROUP_ID, PLAYLIST_ID)),
+            UserClaims { id: USER_ID, role: Role::RegisteredUser, session_version: 0, scopes: None },
+            web::Json(ReorderPlaylist { video_ids }),
+        ).await
+    }
+
+    #[actix_web::test]
+    async fn playlists_cannot_hold_a_video_twice() {
+        let result = reorder(db(), vec![4, 5, 4]).await;
+
+        assert!(matches!(result, Err(AppError::Validation(_))), "{:?}", result);
+    }
+
+    #[actix_web::test]
+    async fn playlists_only_hold_videos_of_their_group() {
+        // Only one of the two videos is shared in the group.
+        let db = db().append_query_results([[BTreeMap::from([("num_items", Value::BigInt(Some(1)))])]]);
+
+        let result = reorder(db, vec![4, 5]).await;
+
+        assert!(matches!(result, Err(AppError::NotFound(_))), "{:?}", result);
+    }
+}
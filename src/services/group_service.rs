@@ -1,70 +1,819 @@
-use actix_web::{error, web, HttpResponse};
-use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, LoaderTrait, QueryFilter};
+use actix_web::web;
+use aws_sdk_s3 as s3;
+use sea_orm::{ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, DbErr, EntityTrait, IntoActiveModel, IntoSimpleExpr, ModelTrait, QueryFilter, QueryOrder, TransactionTrait};
 use sea_orm::ActiveValue::Set;
-use crate::dtos::group_dto::CreateGroupForm;
-use crate::entities::{group_video, groups};
+use sea_orm::sea_query::{Expr, Func, Query};
+use sea_orm::sea_query::extension::postgres::PgBinOper;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use crate::dtos::group_dto::{CreateGroupForm, GroupFilter, GroupMemberResponse, GroupResponse, MemberFilter, GroupMembershipResponse, UpdateGroupForm, UpdateMemberRole};
+use crate::dtos::pagination_dto::{ListQuery, Page, SortOrder};
+use crate::dtos::video_dto::{VideoFilter, VideoResponse};
+use crate::entities::{group_user, group_video, groups, users, video_subtitles, videos};
 use crate::entities::prelude::{GroupVideo, Videos};
+use crate::errors::AppError;
+use crate::services::auth_service::{Role, UserClaims};
+use crate::services::{group_folder_service, pagination_service, storage_service, video_service};
+
+/// A member's standing within a group, ordered from least to most privileged.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupRole {
+    Member,
+    Admin,
+    Owner,
+}
+
+impl GroupRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GroupRole::Member => "member",
+            GroupRole::Admin => "admin",
+            GroupRole::Owner => "owner",
+        }
+    }
+
+    /// Unknown values fall back to the least privileged role.
+    pub fn from_db(role: &str) -> GroupRole {
+        match role {
+            "owner" => GroupRole::Owner,
+            "admin" => GroupRole::Admin,
+            _ => GroupRole::Member,
+        }
+    }
+}
+
+/// Who can find a group and how they get in.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupVisibility {
+    /// Listed, anyone registered can join.
+    Public,
+    /// Listed, joining requires the group password.
+    Password,
+    /// Listed, joining requires an invite or an approved join request.
+    InviteOnly,
+    /// Only visible to members, joining requires an invite.
+    Hidden,
+}
+
+impl GroupVisibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GroupVisibility::Public => "public",
+            GroupVisibility::Password => "password",
+            GroupVisibility::InviteOnly => "invite_only",
+            GroupVisibility::Hidden => "hidden",
+        }
+    }
+
+    /// Unknown values fall back to the most restrictive mode.
+    pub fn from_db(visibility: &str) -> GroupVisibility {
+        match visibility {
+            "public" => GroupVisibility::Public,
+            "password" => GroupVisibility::Password,
+            "invite_only" => GroupVisibility::InviteOnly,
+            _ => GroupVisibility::Hidden,
+        }
+    }
+}
+
+pub fn to_response(group: groups::Model) -> GroupResponse {
+    GroupResponse {
+        id: group.id,
+        name: group.name,
+        description: group.description,
+        visibility: GroupVisibility::from_db(&group.visibility),
+        created_at: group.created_at.to_rfc3339(),
+        is_deleted: group.is_deleted,
+    }
+}
+
+fn normalize_description(description: Option<&str>) -> Option<String> {
+    description.map(str::trim).filter(|description| !description.is_empty()).map(str::to_string)
+}
 
 pub async fn create_group(
     db: web::Data<DatabaseConnection>,
     form: CreateGroupForm,
-) -> HttpResponse {
-    let db = db.as_ref();
+    user_claims: UserClaims,
+) -> Result<(), AppError> {
+    let transaction = db.begin().await?;
+
+    let visibility = form.visibility.unwrap_or(GroupVisibility::Password);
 
     let group = groups::ActiveModel {
-        name: Set(form.name.clone()),
-        password: Set(form.password.clone()),
+        name: Set(form.name.trim().to_string()),
+        description: Set(normalize_description(form.description.as_deref())),
+        visibility: Set(visibility.as_str().to_string()),
+        password: Set(form.password.clone().filter(|_| visibility == GroupVisibility::Password)),
         ..Default::default()
     };
 
-    match group.insert(db).await {
-        Ok(_) => HttpResponse::Ok().finish(),
-        Err(_) => HttpResponse::InternalServerError().body("Error inserting group"),
+    let group = group.insert(&transaction).await
+        .map_err(|e| AppError::unique(e, &[("name", "A group with this name already exists.")]))?;
+
+    let owner = group_user::ActiveModel {
+        group_id: Set(group.id),
+        user_id: Set(user_claims.id),
+        role: Set(GroupRole::Owner.as_str().to_string()),
+        ..Default::default()
+    };
+    owner.insert(&transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Administrators see every group, everyone else only sees live groups that aren't hidden
+/// unless they are a member.
+pub async fn get_groups(
+    db: web::Data<DatabaseConnection>,
+    viewer: Option<UserClaims>,
+    filter: web::Query<GroupFilter>,
+    query: web::Query<ListQuery>,
+) -> Result<Page<GroupResponse>, AppError> {
+    let mut select = groups::Entity::find();
+
+    if viewer.as_ref().is_none_or(|viewer| viewer.role != Role::Admin) {
+        let mut visible = Condition::any()
+            .add(groups::Column::Visibility.ne(GroupVisibility::Hidden.as_str()));
+
+        if let Some(viewer) = &viewer {
+            visible = visible.add(
+                groups::Column::Id.in_subquery(
+                    Query::select()
+                        .column(group_user::Column::GroupId)
+                        .from(group_user::Entity)
+                        .and_where(group_user::Column::UserId.eq(viewer.id))
+                        .to_owned()
+                )
+            );
+        }
+
+        select = select
+            .filter(groups::Column::IsDeleted.eq(false))
+            .filter(visible);
+    }
+
+    if let Some(q) = &filter.q {
+        select = select.filter(pagination_service::search(groups::Column::Name, q));
     }
+    if let Some(visibility) = filter.visibility {
+        select = select.filter(groups::Column::Visibility.eq(visibility.as_str()));
+    }
+
+    let select = pagination_service::sort(select, &query, &[
+        ("name", groups::Column::Name.into_simple_expr()),
+        ("created_at", groups::Column::CreatedAt.into_simple_expr()),
+    ], ("name", SortOrder::Asc))?;
+
+    let groups = pagination_service::paginate(select.order_by_asc(groups::Column::Id), db.get_ref(), &query).await?;
+
+    Ok(groups.map(to_response))
 }
 
-pub async fn get_groups(db: web::Data<DatabaseConnection>, ) -> HttpResponse {
-    let groups = groups::Entity::find().all(db.as_ref()).await.unwrap_or_default();
-    HttpResponse::Ok().json(groups)
+/// Applies a partial update, the password is expected to be hashed already.
+pub async fn update_group(
+    db: web::Data<DatabaseConnection>,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+    form: UpdateGroupForm,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+    let group_id = group_id.into_inner();
+
+    let role = require_manager(db, group_id, user_claims.id).await?;
+
+    if (form.password.is_some() || form.visibility.is_some()) && role != GroupRole::Owner {
+        return Err(AppError::forbidden("Only the group owner can change how the group is joined!"));
+    }
+
+    let group = groups::Entity::find_by_id(group_id)
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("Group not found!"))?;
+
+    let visibility = form.visibility.unwrap_or(GroupVisibility::from_db(&group.visibility));
+
+    if visibility == GroupVisibility::Password && group.password.is_none() && form.password.is_none() {
+        return Err(AppError::field("password", "Password-protected groups need a password."));
+    }
+
+    let mut group = group.into_active_model();
+    group.visibility = Set(visibility.as_str().to_string());
+    if visibility != GroupVisibility::Password {
+        group.password = Set(None);
+    }
+    if let Some(name) = &form.name {
+        group.name = Set(name.trim().to_string());
+    }
+    if let Some(description) = &form.description {
+        group.description = Set(normalize_description(Some(description)));
+    }
+    if let Some(password) = form.password.filter(|_| visibility == GroupVisibility::Password) {
+        group.password = Set(Some(password));
+    }
+
+    group.update(db).await
+        .map_err(|e| AppError::unique(e, &[("name", "A group with this name already exists.")]))?;
+
+    Ok(())
 }
 
+/// Checks the caller owns the group, deleted groups included so owners can restore them.
+pub async fn require_owner(db: &DatabaseConnection, group_id: i64, user_id: i64) -> Result<(), AppError> {
+    let membership = find_membership(db, group_id, user_id)
+        .await?
+        .ok_or(AppError::not_found("Group not found!"))?;
+
+    if GroupRole::from_db(&membership.role) != GroupRole::Owner {
+        return Err(AppError::forbidden("Requires group owner privileges!"));
+    }
+
+    Ok(())
+}
+
+pub enum GroupOperation {
+    Delete,
+    Restore,
+}
+
+pub async fn modify_group_state(
+    db: web::Data<DatabaseConnection>,
+    group_id: web::Path<i64>,
+    operation: GroupOperation,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+
+    let group = groups::Entity::find_by_id(group_id.into_inner())
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("Group not found!"))?;
+
+    let mut group = group.into_active_model();
+    match operation {
+        GroupOperation::Delete => { group.is_deleted = Set(true); }
+        GroupOperation::Restore => { group.is_deleted = Set(false); }
+    }
+    group.update(db).await?;
+
+    Ok(())
+}
+
+/// Permanently removes the group with its memberships and video links. Videos that no other group
+/// references are deleted as well, their storage objects last so a failed purge never orphans a row.
+pub async fn purge_group(
+    db: web::Data<DatabaseConnection>,
+    client: web::Data<s3::Client>,
+    group_id: web::Path<i64>,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+    let group_id = group_id.into_inner();
+
+    let group = groups::Entity::find_by_id(group_id)
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("Group not found!"))?;
+
+    let transaction = db.begin().await?;
+
+    let video_ids: Vec<i64> = GroupVideo::find()
+        .filter(group_video::Column::GroupId.eq(group.id))
+        .all(&transaction)
+        .await?
+        .into_iter()
+        .map(|entry| entry.video_id)
+        .collect();
+
+    group_video::Entity::delete_many()
+        .filter(group_video::Column::GroupId.eq(group.id))
+        .exec(&transaction)
+        .await?;
+
+    group_user::Entity::delete_many()
+        .filter(group_user::Column::GroupId.eq(group.id))
+        .exec(&transaction)
+        .await?;
+
+    let shared_video_ids: Vec<i64> = GroupVideo::find()
+        .filter(group_video::Column::VideoId.is_in(video_ids.clone()))
+        .all(&transaction)
+        .await?
+        .into_iter()
+        .map(|entry| entry.video_id)
+        .collect();
+
+    let orphaned_videos = Videos::find()
+        .filter(videos::Column::Id.is_in(video_ids))
+        .filter(videos::Column::Id.is_not_in(shared_video_ids))
+        .all(&transaction)
+        .await?;
+
+    // Subtitle rows go with their video, their storage objects have to be collected first.
+    let orphaned_subtitles = video_subtitles::Entity::find()
+        .filter(video_subtitles::Column::VideoId.is_in(orphaned_videos.iter().map(|video| video.id)))
+        .all(&transaction)
+        .await?;
+
+    videos::Entity::delete_many()
+        .filter(videos::Column::Id.is_in(orphaned_videos.iter().map(|video| video.id)))
+        .exec(&transaction)
+        .await?;
+
+    groups::Entity::delete_by_id(group.id).exec(&transaction).await?;
+
+    transaction.commit().await?;
+
+    for video in orphaned_videos {
+        if let Err(e) = storage_service::delete_object(&client, &video.key).await {
+            log::error!("Failed to delete storage object of purged video {}: {}", video.id, e);
+        }
+    }
+
+    for subtitle in orphaned_subtitles {
+        if let Err(e) = storage_service::delete_object(&client, &subtitle.key).await {
+            log::error!("Failed to delete storage object of purged subtitles {}: {}", subtitle.id, e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Public groups share their videos with every registered user, all other groups only with members.
 pub async fn get_group_videos(
     db: web::Data<DatabaseConnection>,
     group_id: web::Path<i64>,
-) -> HttpResponse {
+    user_claims: UserClaims,
+    filter: web::Query<VideoFilter>,
+    query: web::Query<ListQuery>,
+) -> Result<Page<VideoResponse>, AppError> {
     let db = db.as_ref();
     let group_id = group_id.into_inner();
 
-    let entries = GroupVideo::find()
-        .filter(group_video::Column::GroupId.eq(group_id))
-        .all(db)
-        .await
-        .unwrap_or_default();
+    require_viewer(db, group_id, user_claims.id).await?;
 
-    let videos = entries.load_one(Videos, db).await.expect("Error loading videos");
+    let mut select = Videos::find()
+        .find_also_related(GroupVideo)
+        .filter(group_video::Column::GroupId.eq(group_id));
 
-    HttpResponse::Ok().json(videos)
+    if let Some(folder) = &filter.folder {
+        select = match group_folder_service::parse_folder("folder", folder)? {
+            Some(folder_id) => {
+                group_folder_service::find_folder(db, group_id, folder_id).await?;
+                select.filter(group_video::Column::FolderId.eq(folder_id))
+            },
+            None => select.filter(group_video::Column::FolderId.is_null()),
+        };
+    }
+
+    if let Some(q) = &filter.q {
+        select = select.filter(
+            Condition::any()
+                .add(pagination_service::search(videos::Column::Title, q))
+                .add(pagination_service::search(videos::Column::Name, q))
+        );
+    }
+    if let Some(tag) = &filter.tag {
+        select = select.filter(
+            Expr::col((videos::Entity, videos::Column::Tags)).binary(PgBinOper::Contains, Expr::val(json!([tag.trim().to_lowercase()])))
+        );
+    }
+    let metadata = filter.metadata();
+    if !metadata.is_empty() {
+        select = select.filter(
+            Expr::col((group_video::Entity, group_video::Column::Metadata)).binary(PgBinOper::Contains, Expr::val(json!(metadata)))
+        );
+    }
+
+    let select = pagination_service::sort(select, &query, &[
+        ("name", videos::Column::Name.into_simple_expr()),
+        ("title", Func::coalesce([Expr::col((videos::Entity, videos::Column::Title)).into(), Expr::col((videos::Entity, videos::Column::Name)).into()]).into()),
+        ("uploaded_at", videos::Column::UploadedAt.into_simple_expr()),
+    ], ("uploaded_at", SortOrder::Desc))?;
+
+    let videos = pagination_service::paginate(select.order_by_desc(videos::Column::Id), db, &query).await?;
+
+    Ok(videos.map(|(video, entry)| video_service::to_response(video, entry)))
 }
 
 pub async fn add_video_to_group(
     group_id: i64,
     video_id: i64,
+    metadata: serde_json::Value,
     db: web::Data<DatabaseConnection>
-) -> Result<(), actix_web::Error> {
+) -> Result<(), AppError> {
     let db = db.as_ref();
 
     let entity = group_video::ActiveModel {
         group_id: Set(group_id),
         video_id: Set(video_id),
+        metadata: Set(metadata),
         ..Default::default()
     };
 
-    match entity.insert(db).await {
-        Ok(_) => Ok(()),
-        Err(_) => Err(error::ErrorInternalServerError("Failed to add video to group!")),
+    entity.insert(db).await?;
+
+    Ok(())
+}
+
+pub async fn find_membership(db: &DatabaseConnection, group_id: i64, user_id: i64) -> Result<Option<group_user::Model>, DbErr> {
+    group_user::Entity::find()
+        .filter(group_user::Column::GroupId.eq(group_id))
+        .filter(group_user::Column::UserId.eq(user_id))
+        .one(db)
+        .await
+}
+
+/// Returns the caller's membership of an existing group, non-members are turned away.
+pub async fn require_membership(db: &DatabaseConnection, group_id: i64, user_id: i64) -> Result<group_user::Model, AppError> {
+    groups::Entity::find_by_id(group_id)
+        .filter(groups::Column::IsDeleted.eq(false))
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("Group not found!"))?;
+
+    find_membership(db, group_id, user_id)
+        .await?
+        .ok_or(AppError::forbidden("You are not a member of this group!"))
+}
+
+pub async fn get_group_users(
+    db: web::Data<DatabaseConnection>,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+    filter: web::Query<MemberFilter>,
+    query: web::Query<ListQuery>,
+) -> Result<Page<GroupMemberResponse>, AppError> {
+    let db = db.get_ref();
+    let group_id = group_id.into_inner();
+
+    require_membership(db, group_id, user_claims.id).await?;
+
+    let mut select = group_user::Entity::find()
+        .filter(group_user::Column::GroupId.eq(group_id))
+        .find_also_related(users::Entity)
+        .filter(users::Column::IsDeleted.eq(false));
+
+    if let Some(q) = &filter.q {
+        select = select.filter(pagination_service::search(users::Column::Username, q));
+    }
+    if let Some(role) = filter.role {
+        select = select.filter(group_user::Column::Role.eq(role.as_str()));
     }
+
+    let select = pagination_service::sort(select, &query, &[
+        ("joined_at", group_user::Column::JoinedAt.into_simple_expr()),
+        ("username", users::Column::Username.into_simple_expr()),
+        ("role", group_user::Column::Role.into_simple_expr()),
+    ], ("joined_at", SortOrder::Asc))?;
+
+    let members = pagination_service::paginate(select.order_by_asc(group_user::Column::Id), db, &query).await?;
+
+    Ok(members.map(|(membership, user)| GroupMemberResponse {
+        user_id: membership.user_id,
+        username: user.and_then(|user| user.username),
+        role: GroupRole::from_db(&membership.role),
+        joined_at: membership.joined_at.to_rfc3339(),
+    }))
+}
+
+pub async fn get_user_groups(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    filter: web::Query<GroupFilter>,
+    query: web::Query<ListQuery>,
+) -> Result<Page<GroupMembershipResponse>, AppError> {
+    let db = db.get_ref();
+
+    let mut select = group_user::Entity::find()
+        .filter(group_user::Column::UserId.eq(user_claims.id))
+        .find_also_related(groups::Entity)
+        .filter(groups::Column::IsDeleted.eq(false));
+
+    if let Some(q) = &filter.q {
+        select = select.filter(pagination_service::search(groups::Column::Name, q));
+    }
+    if let Some(visibility) = filter.visibility {
+        select = select.filter(groups::Column::Visibility.eq(visibility.as_str()));
+    }
+
+    let select = pagination_service::sort(select, &query, &[
+        ("name", groups::Column::Name.into_simple_expr()),
+        ("joined_at", group_user::Column::JoinedAt.into_simple_expr()),
+    ], ("name", SortOrder::Asc))?;
+
+    let groups = pagination_service::paginate(select.order_by_asc(group_user::Column::Id), db, &query).await?;
+
+    // The filter on the group's columns makes this an inner join, so the group is always there.
+    Ok(groups.map(|(membership, group)| GroupMembershipResponse {
+        group_id: membership.group_id,
+        name: group.map(|group| group.name).unwrap_or_default(),
+        role: GroupRole::from_db(&membership.role),
+        joined_at: membership.joined_at.to_rfc3339(),
+    }))
+}
+
+pub async fn leave_group(
+    db: web::Data<DatabaseConnection>,
+    group_id: web::Path<i64>,
+    user_claims: UserClaims,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+    let membership = require_membership(db, group_id.into_inner(), user_claims.id).await?;
+
+    // A group always keeps its owner.
+    if GroupRole::from_db(&membership.role) == GroupRole::Owner {
+        return Err(AppError::conflict("The group owner can't leave the group!"));
+    }
+
+    membership.delete(db).await?;
+
+    Ok(())
+}
+
+/// Anyone may browse a public group's content, other groups need a membership and hidden ones
+/// don't reveal that they exist.
+pub async fn require_viewer(db: &DatabaseConnection, group_id: i64, user_id: i64) -> Result<groups::Model, AppError> {
+    let group = groups::Entity::find_by_id(group_id)
+        .filter(groups::Column::IsDeleted.eq(false))
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("Group not found!"))?;
+
+    let visibility = GroupVisibility::from_db(&group.visibility);
+    if visibility == GroupVisibility::Public {
+        return Ok(group);
+    }
+
+    match find_membership(db, group_id, user_id).await? {
+        Some(_) => Ok(group),
+        None if visibility == GroupVisibility::Hidden => Err(AppError::not_found("Group not found!")),
+        None => Err(AppError::forbidden("You are not a member of this group!")),
+    }
+}
+
+/// Returns the caller's role in the group as long as they are one of its owners or admins.
+pub async fn require_manager(db: &DatabaseConnection, group_id: i64, user_id: i64) -> Result<GroupRole, AppError> {
+    let membership = require_membership(db, group_id, user_id).await?;
+    let role = GroupRole::from_db(&membership.role);
+
+    if role < GroupRole::Admin {
+        return Err(AppError::forbidden("Requires group owner or admin privileges!"));
+    }
+
+    Ok(role)
 }
 
-pub async fn get_group_users() {
-    todo!()
-}
\ No newline at end of file
+/// Looks up the member an owner or admin wants to manage, only members ranked below the caller qualify.
+async fn find_managed_member(
+    db: &DatabaseConnection,
+    group_id: i64,
+    member_id: i64,
+    user_claims: &UserClaims,
+) -> Result<(GroupRole, group_user::Model), AppError> {
+    let role = require_manager(db, group_id, user_claims.id).await?;
+
+    if member_id == user_claims.id {
+        return Err(AppError::bad_request("You can't manage your own membership!"));
+    }
+
+    let member = find_membership(db, group_id, member_id)
+        .await?
+        .ok_or(AppError::not_found("Member not found!"))?;
+
+    if GroupRole::from_db(&member.role) >= role {
+        return Err(AppError::forbidden("You can only manage members with a lower role than yours!"));
+    }
+
+    Ok((role, member))
+}
+
+pub async fn remove_member(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+    let (group_id, member_id) = path.into_inner();
+
+    let (_, member) = find_managed_member(db, group_id, member_id, &user_claims).await?;
+    member.delete(db).await?;
+
+    Ok(())
+}
+
+pub async fn update_member_role(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: web::Json<UpdateMemberRole>,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+    let (group_id, member_id) = path.into_inner();
+
+    let (role, member) = find_managed_member(db, group_id, member_id, &user_claims).await?;
+
+    if role != GroupRole::Owner {
+        return Err(AppError::forbidden("Only the group owner can change member roles!"));
+    }
+    if form.role == GroupRole::Owner {
+        return Err(AppError::field("role", "A group can only have one owner."));
+    }
+
+    let mut member = member.into_active_model();
+    member.role = Set(form.role.as_str().to_string());
+    member.update(db).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use chrono::Utc;
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult, Value};
+    use crate::services::auth_service::Role;
+    use super::*;
+
+    const GROUP_ID: i64 = 1;
+    const OWNER_ID: i64 = 10;
+    const ADMIN_ID: i64 = 20;
+    const MEMBER_ID: i64 = 30;
+
+    fn claims(id: i64) -> UserClaims {
+        UserClaims { id, role: Role::RegisteredUser, session_version: 0, scopes: None }
+    }
+
+    fn group() -> groups::Model {
+        groups::Model {
+            id: GROUP_ID,
+            name: "film-club".to_string(),
+            password: None,
+            created_at: Utc::now().fixed_offset(),
+            is_deleted: false,
+            description: None,
+            visibility: GroupVisibility::InviteOnly.as_str().to_string(),
+        }
+    }
+
+    fn membership(user_id: i64, role: GroupRole) -> group_user::Model {
+        group_user::Model {
+            id: user_id,
+            group_id: GROUP_ID,
+            user_id,
+            joined_at: Utc::now().fixed_offset(),
+            role: role.as_str().to_string(),
+        }
+    }
+
+    /// Answers the group lookup of `require_membership`, then the given membership lookups in order.
+    fn db(memberships: Vec<Vec<group_user::Model>>) -> MockDatabase {
+        MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![group()]])
+            .append_query_results(memberships)
+    }
+
+    #[actix_web::test]
+    async fn owner_cannot_leave_the_group() {
+        let db = web::Data::new(db(vec![vec![membership(OWNER_ID, GroupRole::Owner)]]).into_connection());
+
+        let result = leave_group(db, web::Path::from(GROUP_ID), claims(OWNER_ID)).await;
+
+        assert!(matches!(result, Err(AppError::Conflict(_))), "{:?}", result);
+    }
+
+    #[actix_web::test]
+    async fn members_can_leave_the_group() {
+        let db = db(vec![vec![membership(MEMBER_ID, GroupRole::Member)]])
+            .append_exec_results([MockExecResult { last_insert_id: 0, rows_affected: 1 }]);
+
+        let result = leave_group(web::Data::new(db.into_connection()), web::Path::from(GROUP_ID), claims(MEMBER_ID)).await;
+
+        assert!(result.is_ok(), "{:?}", result);
+    }
+
+    #[actix_web::test]
+    async fn non_members_cannot_leave_the_group() {
+        let db = web::Data::new(db(vec![vec![]]).into_connection());
+
+        let result = leave_group(db, web::Path::from(GROUP_ID), claims(MEMBER_ID)).await;
+
+        assert!(matches!(result, Err(AppError::Forbidden(_))), "{:?}", result);
+    }
+
+    #[actix_web::test]
+    async fn admins_cannot_remove_the_owner() {
+        let db = web::Data::new(db(vec![
+            vec![membership(ADMIN_ID, GroupRole::Admin)],
+            vec![membership(OWNER_ID, GroupRole::Owner)],
+        ]).into_connection());
+
+        let result = remove_member(db, web::Path::from((GROUP_ID, OWNER_ID)), claims(ADMIN_ID)).await;
+
+        assert!(matches!(result, Err(AppError::Forbidden(_))), "{:?}", result);
+    }
+
+    #[actix_web::test]
+    async fn only_the_owner_changes_member_roles() {
+        let db = web::Data::new(db(vec![
+            vec![membership(ADMIN_ID, GroupRole::Admin)],
+            vec![membership(MEMBER_ID, GroupRole::Member)],
+        ]).into_connection());
+        let form = web::Json(UpdateMemberRole { role: GroupRole::Admin });
+
+        let result = update_member_role(db, web::Path::from((GROUP_ID, MEMBER_ID)), claims(ADMIN_ID), form).await;
+
+        assert!(matches!(result, Err(AppError::Forbidden(_))), "{:?}", result);
+    }
+
+    #[actix_web::test]
+    async fn only_the_owner_deletes_the_group() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![membership(ADMIN_ID, GroupRole::Admin)]])
+            .into_connection();
+
+        let result = require_owner(&db, GROUP_ID, ADMIN_ID).await;
+
+        assert!(matches!(result, Err(AppError::Forbidden(_))), "{:?}", result);
+    }
+
+    #[actix_web::test]
+    async fn non_members_are_not_told_the_group_exists() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<group_user::Model>::new()])
+            .into_connection();
+
+        let result = require_owner(&db, GROUP_ID, MEMBER_ID).await;
+
+        assert!(matches!(result, Err(AppError::NotFound(_))), "{:?}", result);
+    }
+
+    #[actix_web::test]
+    async fn only_the_owner_changes_the_group_password() {
+        let db = web::Data::new(db(vec![vec![membership(ADMIN_ID, GroupRole::Admin)]]).into_connection());
+        let form = UpdateGroupForm { name: None, description: None, password: Some("hunter22".to_string()), visibility: None };
+
+        let result = update_group(db, web::Path::from(GROUP_ID), claims(ADMIN_ID), form).await;
+
+        assert!(matches!(result, Err(AppError::Forbidden(_))), "{:?}", result);
+    }
+
+    /// The SQL `get_groups` runs for the viewer, with the parameters inlined.
+    async fn listing_sql(viewer: Option<UserClaims>) -> String {
+        let db = web::Data::new(MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[BTreeMap::from([("num_items", Value::BigInt(Some(0)))])]])
+            .append_query_results([Vec::<groups::Model>::new()])
+            .into_connection());
+
+        get_groups(db.clone(), viewer, web::Query(GroupFilter::default()), web::Query(ListQuery::default())).await.unwrap();
+
+        // The count comes first, then the page itself.
+        let log = Arc::try_unwrap(db.into_inner()).unwrap().into_transaction_log();
+        log[1].statements()[0].to_string()
+    }
+
+    #[actix_web::test]
+    async fn hidden_groups_are_only_listed_for_their_members() {
+        let sql = listing_sql(Some(claims(MEMBER_ID))).await;
+
+        assert!(sql.contains(r#""Groups"."visibility" <> 'hidden'"#), "{}", sql);
+        assert!(sql.contains(&format!(r#""GroupUser"."user_id" = {}"#, MEMBER_ID)), "{}", sql);
+
+        let sql = listing_sql(None).await;
+
+        assert!(sql.contains(r#""Groups"."visibility" <> 'hidden'"#), "{}", sql);
+        assert!(!sql.contains("GroupUser"), "{}", sql);
+    }
+
+    #[actix_web::test]
+    async fn admins_list_every_group() {
+        let sql = listing_sql(Some(UserClaims { role: Role::Admin, ..claims(ADMIN_ID) })).await;
+
+        assert!(!sql.contains("WHERE"), "{}", sql);
+    }
+
+    #[actix_web::test]
+    async fn hidden_groups_do_not_show_their_videos_to_non_members() {
+        let hidden = groups::Model { visibility: GroupVisibility::Hidden.as_str().to_string(), ..group() };
+        let db = web::Data::new(MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![hidden]])
+            .append_query_results([Vec::<group_user::Model>::new()])
+            .into_connection());
+
+        let result = get_group_videos(db, web::Path::from(GROUP_ID), claims(MEMBER_ID), web::Query(VideoFilter::default()), web::Query(ListQuery::default())).await;
+
+        assert!(matches!(result, Err(AppError::NotFound(_))), "{:?}", result);
+    }
+
+    #[actix_web::test]
+    async fn listed_groups_turn_non_members_away_from_their_videos() {
+        let db = web::Data::new(db(vec![vec![]]).into_connection());
+
+        let result = get_group_videos(db, web::Path::from(GROUP_ID), claims(MEMBER_ID), web::Query(VideoFilter::default()), web::Query(ListQuery::default())).await;
+
+        assert!(matches!(result, Err(AppError::Forbidden(_))), "{:?}", result);
+    }
+}
@@ -0,0 +1,127 @@
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use shuttle_runtime::SecretStore;
+
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub struct MailError {
+    message: String,
+}
+
+impl MailError {
+    fn new(message: impl Into<String>) -> MailError {
+        MailError {
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for MailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+pub type MailFuture<'a> = Pin<Box<dyn Future<Output=Result<(), MailError>> + Send + 'a>>;
+
+pub trait Mailer: Send + Sync {
+    fn send(&self, email: Email) -> MailFuture<'_>;
+}
+
+/// Delivers mail through an SMTP relay.
+pub struct SmtpMailer {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailer {
+    pub fn new(host: &str, port: u16, username: String, password: String, from: &str) -> Result<SmtpMailer, MailError> {
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+            .map_err(|e| MailError::new(e.to_string()))?
+            .port(port)
+            .credentials(Credentials::new(username, password))
+            .build();
+
+        let from = from.parse().map_err(|_| MailError::new("MAIL_FROM is not a valid mailbox"))?;
+
+        Ok(SmtpMailer { from, transport })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, email: Email) -> MailFuture<'_> {
+        Box::pin(async move {
+            let to: Mailbox = email.to.parse()
+                .map_err(|_| MailError::new(format!("Invalid recipient address: {}", email.to)))?;
+
+            let message = Message::builder()
+                .from(self.from.clone())
+                .to(to)
+                .subject(email.subject)
+                .header(ContentType::TEXT_PLAIN)
+                .body(email.body)
+                .map_err(|e| MailError::new(e.to_string()))?;
+
+            self.transport.send(message).await
+                .map(|_| ())
+                .map_err(|e| MailError::new(e.to_string()))
+        })
+    }
+}
+
+/// Writes every message to a directory and the log instead of sending it, for local development and tests.
+pub struct FileMailer {
+    outbox: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(outbox: impl Into<PathBuf>) -> FileMailer {
+        FileMailer { outbox: outbox.into() }
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, email: Email) -> MailFuture<'_> {
+        Box::pin(async move {
+            log::info!("Mail to {}: {}\n{}", email.to, email.subject, email.body);
+
+            tokio::fs::create_dir_all(&self.outbox).await
+                .map_err(|e| MailError::new(e.to_string()))?;
+
+            let file_name = format!("{}-{}.eml", chrono::Utc::now().format("%Y%m%dT%H%M%S%.3f"), nanoid::nanoid!(6));
+            let contents = format!("To: {}\nSubject: {}\n\n{}\n", email.to, email.subject, email.body);
+
+            tokio::fs::write(self.outbox.join(file_name), contents).await
+                .map_err(|e| MailError::new(e.to_string()))
+        })
+    }
+}
+
+/// Picks the mailer from `MAIL_TRANSPORT`: `smtp` uses the `SMTP_*` secrets, anything else writes to `MAIL_OUTBOX_DIR`.
+pub fn create_mailer(secrets: &SecretStore) -> Box<dyn Mailer> {
+    match secrets.get("MAIL_TRANSPORT").as_deref() {
+        Some("smtp") => {
+            let host = secrets.get("SMTP_HOST").expect("SMTP_HOST is not set");
+            let port = secrets.get("SMTP_PORT")
+                .map(|port| port.parse().expect("SMTP_PORT must be a number"))
+                .unwrap_or(587);
+            let username = secrets.get("SMTP_USERNAME").unwrap_or_default();
+            let password = secrets.get("SMTP_PASSWORD").unwrap_or_default();
+            let from = secrets.get("MAIL_FROM").expect("MAIL_FROM is not set");
+
+            Box::new(SmtpMailer::new(&host, port, username, password, &from).expect("Failed to configure SMTP mailer"))
+        },
+        _ => Box::new(FileMailer::new(secrets.get("MAIL_OUTBOX_DIR").unwrap_or("outbox".to_string()))),
+    }
+}
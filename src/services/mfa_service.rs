@@ -0,0 +1,310 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use actix_web::web;
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, IntoActiveModel, QueryFilter, TransactionTrait};
+use sea_orm::ActiveValue::Set;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use totp_rs::{Algorithm, Secret, TOTP};
+use crate::dtos::user_dto::{MfaChallengeResponse, MfaLogin, RecoveryCodesResponse, TotpCode, TotpEnrollmentResponse};
+use crate::entities::{recovery_codes, users};
+use crate::errors::AppError;
+use crate::services::auth_service::{Role, UserClaims};
+use crate::services::throttle_service::Attempt;
+use crate::services::token_service::TokenService;
+use crate::services::user_service::{issue_tokens, IssuedTokens, TokenDelivery};
+
+const TOTP_ISSUER: &str = "LockBox";
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP: u64 = 30;
+const TOTP_SKEW: u64 = 1;
+const MFA_PENDING_PURPOSE: &str = "mfa_pending";
+const MFA_PENDING_LIFETIME: Duration = Duration::from_secs(300);
+const RECOVERY_CODE_COUNT: usize = 10;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct MfaPendingClaims {
+    sub: i64,
+    role: Role,
+    session_version: i32,
+    delivery: TokenDelivery,
+    purpose: String,
+}
+
+fn build_totp(secret: &str, account_name: &str) -> Option<TOTP> {
+    let secret = Secret::Encoded(secret.to_string()).to_bytes().ok()?;
+
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        TOTP_SKEW as u8,
+        TOTP_STEP,
+        secret,
+        Some(TOTP_ISSUER.to_string()),
+        account_name.to_string(),
+    ).ok()
+}
+
+/// Returns the time step the code belongs to, so a code can't be replayed within its validity window.
+fn matching_step(totp: &TOTP, code: &str) -> Option<i64> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let current_step = now / TOTP_STEP;
+
+    (current_step.saturating_sub(TOTP_SKEW)..=current_step + TOTP_SKEW)
+        .find(|step| totp.generate(step * TOTP_STEP) == code.trim())
+        .map(|step| step as i64)
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    format!("{:x}", Sha256::digest(code.trim().to_uppercase().replace('-', "").as_bytes()))
+}
+
+fn generate_recovery_code() -> String {
+    let alphabet: Vec<char> = "ABCDEFGHJKLMNPQRSTUVWXYZ23456789".chars().collect();
+    let code = nanoid::nanoid!(10, &alphabet);
+    format!("{}-{}", &code[..5], &code[5..])
+}
+
+/// Checks a TOTP code against the user's authenticator and falls back to an unused recovery code.
+async fn verify_second_factor(db: &DatabaseConnection, user: users::Model, code: &str) -> Result<bool, DbErr> {
+    let totp = user.totp_secret.as_deref().and_then(|secret| build_totp(secret, &user.email));
+
+    if let Some(step) = totp.as_ref().and_then(|totp| matching_step(totp, code)) {
+        if user.totp_last_step.is_some_and(|last_step| step <= last_step) {
+            return Ok(false);
+        }
+
+        let mut user = user.into_active_model();
+        user.totp_last_step = Set(Some(step));
+        user.update(db).await?;
+
+        return Ok(true);
+    }
+
+    let recovery_code = recovery_codes::Entity::find()
+        .filter(recovery_codes::Column::UserId.eq(user.id))
+        .filter(recovery_codes::Column::CodeHash.eq(hash_recovery_code(code)))
+        .filter(recovery_codes::Column::UsedAt.is_null())
+        .one(db)
+        .await?;
+
+    match recovery_code {
+        Some(recovery_code) => {
+            let mut recovery_code = recovery_code.into_active_model();
+            recovery_code.used_at = Set(Some(Utc::now().fixed_offset()));
+            recovery_code.update(db).await?;
+            Ok(true)
+        },
+        None => Ok(false),
+    }
+}
+
+async fn replace_recovery_codes(db: &DatabaseConnection, user_id: i64) -> Result<Vec<String>, DbErr> {
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT).map(|_| generate_recovery_code()).collect();
+
+    let transaction = db.begin().await?;
+
+    recovery_codes::Entity::delete_many()
+        .filter(recovery_codes::Column::UserId.eq(user_id))
+        .exec(&transaction)
+        .await?;
+
+    let models = codes.iter().map(|code| recovery_codes::ActiveModel {
+        user_id: Set(user_id),
+        code_hash: Set(hash_recovery_code(code)),
+        created_at: Set(Utc::now().fixed_offset()),
+        ..Default::default()
+    });
+    recovery_codes::Entity::insert_many(models).exec(&transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(codes)
+}
+
+pub fn mfa_challenge(
+    token_service: &TokenService,
+    user: &users::Model,
+    role: Role,
+    delivery: TokenDelivery,
+) -> Result<MfaChallengeResponse, AppError> {
+    let claims = MfaPendingClaims {
+        sub: user.id,
+        role,
+        session_version: user.session_version,
+        delivery,
+        purpose: MFA_PENDING_PURPOSE.to_string(),
+    };
+
+    Ok(MfaChallengeResponse {
+        mfa_required: true,
+        mfa_token: token_service.create_signed_token(&claims, MFA_PENDING_LIFETIME)?,
+    })
+}
+
+pub async fn complete_login(
+    db: web::Data<DatabaseConnection>,
+    token_service: web::Data<TokenService>,
+    form: web::Json<MfaLogin>,
+    client_ip: &str,
+) -> Result<IssuedTokens, AppError> {
+    let db = db.get_ref();
+
+    let claims = match token_service.verify::<MfaPendingClaims>(&form.mfa_token) {
+        Ok(claims) if claims.purpose == MFA_PENDING_PURPOSE => claims,
+        _ => return Err(AppError::unauthorized("Invalid or expired MFA token!")),
+    };
+
+    let user = match users::Entity::find_by_id(claims.sub).one(db).await? {
+        Some(user) if !user.is_deleted && user.totp_enabled && user.session_version == claims.session_version => user,
+        _ => return Err(AppError::unauthorized("Invalid or expired MFA token!")),
+    };
+
+    let user_claim = UserClaims {
+        id: user.id,
+        role: claims.role,
+        session_version: user.session_version,
+        scopes: None,
+    };
+
+    // The pending token lives for minutes, long enough to guess codes without the throttle.
+    if !check_second_factor(db, user, &form.code, client_ip).await? {
+        return Err(AppError::unauthorized("Invalid authentication code!"));
+    }
+
+    issue_tokens(&token_service, &user_claim, claims.delivery)
+}
+
+/// Verifies a code against the account's second factor throttle, which login and the MFA settings share so a
+/// stolen session can't be used to guess codes either.
+async fn check_second_factor(db: &DatabaseConnection, user: users::Model, code: &str, client_ip: &str) -> Result<bool, AppError> {
+    let attempt = Attempt::mfa_login(user.id, client_ip).reserve(db).await?;
+
+    let user_id = user.id;
+    if !verify_second_factor(db, user, code).await? {
+        attempt.record_failure(db, Some(user_id)).await;
+        return Ok(false);
+    }
+
+    attempt.record_success(db).await;
+    Ok(true)
+}
+
+async fn find_user(db: &DatabaseConnection, user_id: i64) -> Result<users::Model, AppError> {
+    users::Entity::find_by_id(user_id)
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("User not found!"))
+}
+
+async fn find_user_with_totp(db: &DatabaseConnection, user_id: i64) -> Result<users::Model, AppError> {
+    let user = find_user(db, user_id).await?;
+
+    if !user.totp_enabled {
+        return Err(AppError::bad_request("Two-factor authentication is not enabled!"));
+    }
+
+    Ok(user)
+}
+
+pub async fn enroll_totp(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+) -> Result<TotpEnrollmentResponse, AppError> {
+    let db = db.get_ref();
+    let user = find_user(db, user_claims.id).await?;
+
+    if user.totp_enabled {
+        return Err(AppError::conflict("Two-factor authentication is already enabled!"));
+    }
+
+    let secret = Secret::generate_secret().to_encoded().to_string();
+    let totp = build_totp(&secret, &user.email)
+        .ok_or(AppError::internal("failed to build TOTP from generated secret"))?;
+
+    let response = TotpEnrollmentResponse {
+        secret: secret.clone(),
+        provisioning_uri: totp.get_url(),
+    };
+
+    // The secret only becomes active once a code generated from it is confirmed.
+    let mut user = user.into_active_model();
+    user.totp_secret = Set(Some(secret));
+    user.totp_last_step = Set(None);
+    user.update(db).await?;
+
+    Ok(response)
+}
+
+pub async fn confirm_totp(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    form: web::Json<TotpCode>,
+) -> Result<RecoveryCodesResponse, AppError> {
+    let db = db.get_ref();
+    let user = find_user(db, user_claims.id).await?;
+
+    if user.totp_enabled {
+        return Err(AppError::conflict("Two-factor authentication is already enabled!"));
+    }
+
+    let step = user.totp_secret.as_deref()
+        .and_then(|secret| build_totp(secret, &user.email))
+        .and_then(|totp| matching_step(&totp, &form.code))
+        .ok_or(AppError::field("code", "Invalid authentication code."))?;
+
+    let user_id = user.id;
+    let mut user = user.into_active_model();
+    user.totp_enabled = Set(true);
+    user.totp_last_step = Set(Some(step));
+    user.update(db).await?;
+
+    let recovery_codes = replace_recovery_codes(db, user_id).await?;
+    Ok(RecoveryCodesResponse { recovery_codes })
+}
+
+pub async fn regenerate_recovery_codes(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    form: web::Json<TotpCode>,
+    client_ip: &str,
+) -> Result<RecoveryCodesResponse, AppError> {
+    let db = db.get_ref();
+    let user = find_user_with_totp(db, user_claims.id).await?;
+
+    let user_id = user.id;
+    if !check_second_factor(db, user, &form.code, client_ip).await? {
+        return Err(AppError::field("code", "Invalid authentication code."));
+    }
+
+    let recovery_codes = replace_recovery_codes(db, user_id).await?;
+    Ok(RecoveryCodesResponse { recovery_codes })
+}
+
+pub async fn disable_totp(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    form: web::Json<TotpCode>,
+    client_ip: &str,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+    let user = find_user_with_totp(db, user_claims.id).await?;
+
+    if !check_second_factor(db, user.clone(), &form.code, client_ip).await? {
+        return Err(AppError::field("code", "Invalid authentication code."));
+    }
+
+    let user_id = user.id;
+    let mut user = user.into_active_model();
+    user.totp_enabled = Set(false);
+    user.totp_secret = Set(None);
+    user.totp_last_step = Set(None);
+    user.update(db).await?;
+
+    recovery_codes::Entity::delete_many()
+        .filter(recovery_codes::Column::UserId.eq(user_id))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
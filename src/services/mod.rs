@@ -1,6 +1,28 @@
 pub mod user_service;
 pub mod hash_service;
 pub mod auth_service;
+pub mod token_service;
 pub mod admin_service;
 pub mod storage_service;
-pub mod group_service;
\ No newline at end of file
+pub mod group_service;
+pub mod group_folder_service;
+pub mod group_invite_service;
+pub mod group_join_request_service;
+pub mod group_playlist_service;
+pub mod mail_service;
+pub mod transcription_service;
+pub mod verification_service;
+pub mod password_reset_service;
+pub mod mfa_service;
+pub mod oidc_service;
+pub mod api_token_service;
+pub mod audit_service;
+pub mod throttle_service;
+pub mod password_policy_service;
+pub mod pagination_service;
+pub mod search_service;
+pub mod video_service;
+pub mod video_comment_service;
+pub mod video_progress_service;
+pub mod video_subtitle_service;
+pub mod video_transcript_service;
\ No newline at end of file
@@ -0,0 +1,498 @@
+use std::time::Duration;
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::{web, HttpRequest};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, SqlErr};
+use sea_orm::ActiveValue::Set;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use shuttle_runtime::SecretStore;
+use tokio::sync::RwLock;
+use crate::dtos::user_dto::{OidcCallback, OidcLogin};
+use crate::entities::users;
+use crate::errors::AppError;
+use crate::services::auth_service::{Role, UserClaims};
+use crate::services::{admin_service, mfa_service};
+use crate::services::token_service::TokenService;
+use crate::services::user_service::TokenDelivery;
+
+const OIDC_FLOW_COOKIE: &str = "oidc_flow";
+const OIDC_FLOW_PURPOSE: &str = "oidc_flow";
+const OIDC_FLOW_LIFETIME: Duration = Duration::from_secs(600);
+// RFC 8176 `amr` values that mean the IdP asked for more than a password.
+const MFA_AUTHENTICATION_METHODS: [&str; 5] = ["mfa", "otp", "hwk", "swk", "sc"];
+
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_uri: String,
+    pub scopes: String,
+    pub groups_claim: String,
+    pub admin_groups: Vec<String>,
+    pub post_login_redirect: String,
+    pub trust_idp_mfa: bool,
+}
+
+impl OidcConfig {
+    /// Reads the `OIDC_*` secrets, SSO stays disabled unless `OIDC_ISSUER_URL` is set. `OIDC_TRUST_IDP_MFA=true`
+    /// lets a second factor the IdP reports in `amr` stand in for LockBox' own.
+    pub fn from_secrets(secrets: &SecretStore) -> Option<OidcConfig> {
+        let issuer = secrets.get("OIDC_ISSUER_URL")?;
+
+        Some(OidcConfig {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id: secrets.get("OIDC_CLIENT_ID").expect("OIDC_CLIENT_ID is not set"),
+            client_secret: secrets.get("OIDC_CLIENT_SECRET"),
+            redirect_uri: secrets.get("OIDC_REDIRECT_URI").expect("OIDC_REDIRECT_URI is not set"),
+            scopes: secrets.get("OIDC_SCOPES").unwrap_or("openid email profile".to_string()),
+            groups_claim: secrets.get("OIDC_GROUPS_CLAIM").unwrap_or("groups".to_string()),
+            admin_groups: secrets.get("OIDC_ADMIN_GROUPS")
+                .map(|groups| groups.split(',').map(|group| group.trim().to_string()).filter(|group| !group.is_empty()).collect())
+                .unwrap_or_default(),
+            post_login_redirect: secrets.get("OIDC_POST_LOGIN_REDIRECT").unwrap_or("/".to_string()),
+            trust_idp_mfa: secrets.get("OIDC_TRUST_IDP_MFA").is_some_and(|trust| trust == "true"),
+        })
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenEndpointResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct IdTokenClaims {
+    sub: String,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+    preferred_username: Option<String>,
+    nonce: Option<String>,
+    #[serde(default)]
+    amr: Vec<String>,
+    #[serde(flatten)]
+    additional: serde_json::Map<String, serde_json::Value>,
+}
+
+/// State carried between the authorization redirect and the callback in a signed, short-lived cookie.
+#[derive(Serialize, Deserialize, Debug)]
+struct OidcFlowClaims {
+    state: String,
+    nonce: String,
+    code_verifier: String,
+    role: Role,
+    purpose: String,
+}
+
+/// Authorization code + PKCE client for the company identity provider.
+pub struct OidcClient {
+    config: OidcConfig,
+    http: reqwest::Client,
+    metadata: RwLock<Option<ProviderMetadata>>,
+    jwks: RwLock<Option<JwkSet>>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> OidcClient {
+        OidcClient {
+            config,
+            http: reqwest::Client::new(),
+            metadata: RwLock::new(None),
+            jwks: RwLock::new(None),
+        }
+    }
+
+    /// Discovers the provider configuration on first use so the IdP being down doesn't block startup.
+    async fn metadata(&self) -> Result<ProviderMetadata, String> {
+        if let Some(metadata) = self.metadata.read().await.as_ref() {
+            return Ok(metadata.clone());
+        }
+
+        let url = format!("{}/.well-known/openid-configuration", self.config.issuer);
+        let metadata: ProviderMetadata = self.http.get(&url).send().await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.to_string())?
+            .json().await
+            .map_err(|e| e.to_string())?;
+
+        if metadata.issuer.trim_end_matches('/') != self.config.issuer {
+            return Err(format!("Discovered issuer {} does not match {}", metadata.issuer, self.config.issuer));
+        }
+
+        *self.metadata.write().await = Some(metadata.clone());
+        Ok(metadata)
+    }
+
+    async fn fetch_jwks(&self, metadata: &ProviderMetadata) -> Result<JwkSet, String> {
+        let jwks: JwkSet = self.http.get(&metadata.jwks_uri).send().await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.to_string())?
+            .json().await
+            .map_err(|e| e.to_string())?;
+
+        *self.jwks.write().await = Some(jwks.clone());
+        Ok(jwks)
+    }
+
+    /// Looks the signing key up by `kid`, refetching the key set once in case the IdP rotated its keys.
+    async fn decoding_key(&self, metadata: &ProviderMetadata, kid: Option<&str>) -> Result<DecodingKey, String> {
+        let find = |jwks: &JwkSet| match kid {
+            Some(kid) => jwks.find(kid).cloned(),
+            None if jwks.keys.len() == 1 => jwks.keys.first().cloned(),
+            None => None,
+        };
+
+        let cached = self.jwks.read().await.as_ref().and_then(find);
+        let jwk = match cached {
+            Some(jwk) => jwk,
+            None => find(&self.fetch_jwks(metadata).await?).ok_or("No matching signing key in provider JWKS")?,
+        };
+
+        DecodingKey::from_jwk(&jwk).map_err(|e| e.to_string())
+    }
+
+    async fn exchange_code(&self, metadata: &ProviderMetadata, code: &str, code_verifier: &str) -> Result<String, String> {
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_uri.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(client_secret) = &self.config.client_secret {
+            form.push(("client_secret", client_secret.as_str()));
+        }
+
+        let response: TokenEndpointResponse = self.http.post(&metadata.token_endpoint)
+            .form(&form)
+            .send().await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.to_string())?
+            .json().await
+            .map_err(|e| e.to_string())?;
+
+        Ok(response.id_token)
+    }
+
+    async fn validate_id_token(&self, metadata: &ProviderMetadata, id_token: &str, nonce: &str) -> Result<IdTokenClaims, String> {
+        let header = decode_header(id_token).map_err(|e| e.to_string())?;
+        if !matches!(header.alg, Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512 | Algorithm::ES256 | Algorithm::ES384 | Algorithm::EdDSA) {
+            return Err(format!("Unsupported ID token algorithm {:?}", header.alg));
+        }
+
+        let key = self.decoding_key(metadata, header.kid.as_deref()).await?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iat", "iss", "aud", "sub"]);
+
+        let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map_err(|e| e.to_string())?
+            .claims;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err("ID token nonce does not match".to_string());
+        }
+
+        Ok(claims)
+    }
+
+    fn allowed_roles(&self, claims: &IdTokenClaims) -> Vec<Role> {
+        let groups: Vec<&str> = match claims.additional.get(&self.config.groups_claim) {
+            Some(serde_json::Value::Array(groups)) => groups.iter().filter_map(|group| group.as_str()).collect(),
+            Some(serde_json::Value::String(group)) => vec![group.as_str()],
+            _ => Vec::new(),
+        };
+
+        let mut roles = vec![Role::RegisteredUser];
+        if groups.iter().any(|group| self.config.admin_groups.iter().any(|admin| admin == group)) {
+            roles.push(Role::Admin);
+        }
+
+        roles
+    }
+
+    /// Whether the IdP's second factor counts, which needs both the deployment's trust and the IdP saying it asked for one.
+    fn satisfies_mfa(&self, claims: &IdTokenClaims) -> bool {
+        self.config.trust_idp_mfa && claims.amr.iter().any(|method| MFA_AUTHENTICATION_METHODS.contains(&method.as_str()))
+    }
+}
+
+fn pkce_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+/// Where to send the browser next, with the cookies to set on the way.
+pub struct OidcRedirect {
+    pub location: String,
+    pub cookies: Vec<Cookie<'static>>,
+}
+
+fn provider_unavailable(e: String) -> AppError {
+    AppError::Upstream(format!("OIDC discovery failed: {}", e))
+}
+
+pub async fn begin_login(
+    oidc: web::Data<OidcClient>,
+    token_service: web::Data<TokenService>,
+    query: web::Query<OidcLogin>,
+) -> Result<OidcRedirect, AppError> {
+    let metadata = oidc.metadata().await.map_err(provider_unavailable)?;
+
+    let flow = OidcFlowClaims {
+        state: nanoid::nanoid!(32),
+        nonce: nanoid::nanoid!(32),
+        code_verifier: nanoid::nanoid!(64),
+        role: if query.admin.unwrap_or(false) { Role::Admin } else { Role::RegisteredUser },
+        purpose: OIDC_FLOW_PURPOSE.to_string(),
+    };
+
+    let mut authorization_url = reqwest::Url::parse(&metadata.authorization_endpoint)
+        .map_err(|e| AppError::Upstream(format!("invalid OIDC authorization endpoint: {}", e)))?;
+    authorization_url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &oidc.config.client_id)
+        .append_pair("redirect_uri", &oidc.config.redirect_uri)
+        .append_pair("scope", &oidc.config.scopes)
+        .append_pair("state", &flow.state)
+        .append_pair("nonce", &flow.nonce)
+        .append_pair("code_challenge", &pkce_challenge(&flow.code_verifier))
+        .append_pair("code_challenge_method", "S256");
+
+    let flow_cookie = Cookie::build(OIDC_FLOW_COOKIE, token_service.create_signed_token(&flow, OIDC_FLOW_LIFETIME)?)
+        .path("/")
+        .secure(true)
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(actix_web::cookie::time::Duration::seconds(OIDC_FLOW_LIFETIME.as_secs() as i64))
+        .finish();
+
+    Ok(OidcRedirect {
+        location: authorization_url.to_string(),
+        cookies: vec![flow_cookie],
+    })
+}
+
+pub async fn complete_login(
+    req: HttpRequest,
+    db: web::Data<DatabaseConnection>,
+    oidc: web::Data<OidcClient>,
+    token_service: web::Data<TokenService>,
+    query: web::Query<OidcCallback>,
+) -> Result<OidcRedirect, AppError> {
+    let db = db.get_ref();
+
+    if let Some(idp_error) = &query.error {
+        log::warn!("OIDC provider returned an error: {}", idp_error);
+        return Err(AppError::unauthorized("Single sign-on was not completed!"));
+    }
+
+    let flow = req.cookie(OIDC_FLOW_COOKIE)
+        .and_then(|cookie| token_service.verify::<OidcFlowClaims>(cookie.value()).ok())
+        .filter(|flow| flow.purpose == OIDC_FLOW_PURPOSE);
+
+    let flow = match (flow, &query.state, &query.code) {
+        (Some(flow), Some(state), Some(_)) if &flow.state == state => flow,
+        _ => return Err(AppError::bad_request("Invalid or expired single sign-on request!")),
+    };
+
+    let metadata = oidc.metadata().await.map_err(provider_unavailable)?;
+
+    let id_token = oidc.exchange_code(&metadata, query.code.as_deref().unwrap_or_default(), &flow.code_verifier).await
+        .map_err(|e| AppError::Upstream(format!("OIDC code exchange failed: {}", e)))?;
+
+    let claims = oidc.validate_id_token(&metadata, &id_token, &flow.nonce).await.map_err(|e| {
+        log::warn!("Rejected OIDC ID token: {}", e);
+        AppError::unauthorized("Invalid identity token!")
+    })?;
+
+    let email = match &claims.email {
+        Some(email) if claims.email_verified => email.to_lowercase(),
+        _ => return Err(AppError::forbidden("Identity provider did not supply a verified email address!")),
+    };
+
+    if !oidc.allowed_roles(&claims).contains(&flow.role) {
+        return Err(AppError::forbidden("Your identity provider groups don't grant this role!"));
+    }
+
+    let user = find_or_create_user(db, &email, &claims).await
+        .map_err(|e| AppError::internal(format!("failed to provision SSO user {}: {}", claims.sub, e)))?;
+
+    if user.is_deleted {
+        return Err(AppError::unauthorized("This account has been deleted!"));
+    }
+
+    let mut expired_flow = Cookie::new(OIDC_FLOW_COOKIE, "");
+    expired_flow.set_path("/");
+    expired_flow.make_removal();
+
+    // Same policy as the password login, SSO must not become the way around a second factor.
+    if !oidc.satisfies_mfa(&claims) {
+        if user.totp_enabled {
+            let challenge = mfa_service::mfa_challenge(&token_service, &user, flow.role, TokenDelivery::Cookie)?;
+            return Ok(OidcRedirect {
+                location: format!("{}#mfa_token={}", oidc.config.post_login_redirect, challenge.mfa_token),
+                cookies: vec![expired_flow],
+            });
+        }
+
+        if flow.role == Role::Admin && admin_service::require_admin_mfa(db).await? {
+            return Err(AppError::forbidden("Administrator accounts must enable two-factor authentication!"));
+        }
+    }
+
+    let user_claim = UserClaims {
+        id: user.id,
+        role: flow.role,
+        session_version: user.session_version,
+        scopes: None,
+    };
+
+    Ok(OidcRedirect {
+        location: oidc.config.post_login_redirect.clone(),
+        cookies: vec![
+            token_service.create_access_cookie(&user_claim)?,
+            token_service.create_refresh_cookie(&user_claim)?,
+            expired_flow,
+        ],
+    })
+}
+
+/// Matches the IdP identity to an existing account by verified email, provisioning one on first login.
+/// An unverified account with that email is claimed for the IdP identity, dropping the credentials it was registered with.
+async fn find_or_create_user(db: &DatabaseConnection, email: &str, claims: &IdTokenClaims) -> Result<users::Model, sea_orm::DbErr> {
+    let existing = users::Entity::find()
+        .filter(users::Column::Email.eq(email))
+        .one(db)
+        .await?;
+
+    if let Some(user) = existing {
+        if user.email_verified {
+            return Ok(user);
+        }
+
+        // Whoever registered the unverified account never proved they own the address, the IdP just did.
+        // Their password and second factor would otherwise start working the moment the email counts as verified.
+        let session_version = user.session_version;
+        let mut user: users::ActiveModel = user.into();
+        user.email_verified = Set(true);
+        user.password = Set(None);
+        user.totp_secret = Set(None);
+        user.totp_enabled = Set(false);
+        user.totp_last_step = Set(None);
+        user.session_version = Set(session_version + 1);
+        return user.update(db).await;
+    }
+
+    let username = claims.preferred_username.clone()
+        .or_else(|| email.split('@').next().map(str::to_string));
+
+    let user = users::ActiveModel {
+        username: Set(username),
+        email: Set(email.to_string()),
+        password: Set(None),
+        email_verified: Set(true),
+        ..Default::default()
+    };
+
+    match user.insert(db).await {
+        Ok(user) => Ok(user),
+        Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(constraint)) if constraint.contains("username")) => {
+            // The preferred username may already belong to someone else, the account is still usable without one.
+            let user = users::ActiveModel {
+                username: Set(None),
+                email: Set(email.to_string()),
+                password: Set(None),
+                email_verified: Set(true),
+                ..Default::default()
+            };
+            user.insert(db).await
+        },
+        Err(e) => Err(e),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use chrono::Utc;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+    use super::*;
+
+    fn claims() -> IdTokenClaims {
+        IdTokenClaims {
+            sub: "idp-subject".to_string(),
+            email: Some("jane@example.com".to_string()),
+            email_verified: true,
+            preferred_username: Some("jane".to_string()),
+            nonce: None,
+            amr: Vec::new(),
+            additional: serde_json::Map::new(),
+        }
+    }
+
+    fn user(email_verified: bool) -> users::Model {
+        users::Model {
+            id: 42,
+            username: Some("jane".to_string()),
+            email: "jane@example.com".to_string(),
+            password: Some("$argon2id$squatter".to_string()),
+            created_at: Utc::now().fixed_offset(),
+            is_deleted: false,
+            email_verified,
+            session_version: 3,
+            totp_secret: Some("JBSWY3DPEHPK3PXP".to_string()),
+            totp_enabled: true,
+            totp_last_step: None,
+            is_admin: false,
+        }
+    }
+
+    #[actix_web::test]
+    async fn verified_accounts_are_linked_as_they_are() {
+        let verified = user(true);
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![verified.clone()]])
+            .into_connection();
+
+        let linked = find_or_create_user(&db, "jane@example.com", &claims()).await.unwrap();
+
+        assert_eq!(linked, verified);
+        assert_eq!(db.into_transaction_log().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn unverified_accounts_lose_their_credentials_and_sessions() {
+        let claimed = users::Model { password: None, totp_secret: None, totp_enabled: false, session_version: 4, email_verified: true, ..user(false) };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![user(false)]])
+            .append_query_results([vec![claimed.clone()]])
+            .into_connection();
+        let db = Arc::new(db);
+
+        let linked = find_or_create_user(&db, "jane@example.com", &claims()).await.unwrap();
+        assert_eq!(linked, claimed);
+
+        let log = Arc::try_unwrap(db).unwrap().into_transaction_log();
+        let update = &log[1].statements()[0];
+        assert!(update.sql.starts_with(r#"UPDATE "Users""#), "{}", update.sql);
+        let values = update.values.as_ref().unwrap().0.clone();
+        assert!(values.contains(&sea_orm::Value::String(None)), "{:?}", values);
+        assert!(values.contains(&sea_orm::Value::Bool(Some(false))), "{:?}", values);
+        assert!(values.contains(&sea_orm::Value::Int(Some(4))), "{:?}", values);
+    }
+}
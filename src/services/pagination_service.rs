@@ -0,0 +1,128 @@
+use sea_orm::{ColumnTrait, ConnectionTrait, Order, PaginatorTrait, QueryOrder, SelectorTrait};
+use sea_orm::sea_query::{Expr, Func, SimpleExpr};
+use crate::dtos::pagination_dto::{ListQuery, Page, SortOrder};
+use crate::errors::AppError;
+
+/// Orders by the `sort` field the client asked for, only the listed fields are sortable. Callers add
+/// a unique column afterwards so rows with equal sort values keep a stable order across pages.
+pub fn sort<S: QueryOrder>(
+    select: S,
+    query: &ListQuery,
+    fields: &[(&str, SimpleExpr)],
+    default: (&str, SortOrder),
+) -> Result<S, AppError> {
+    let (name, order) = match &query.sort {
+        Some(name) => (name.as_str(), query.order.unwrap_or(SortOrder::Asc)),
+        None => (default.0, query.order.unwrap_or(default.1)),
+    };
+
+    let expr = fields.iter()
+        .find(|(field, _)| *field == name)
+        .map(|(_, expr)| expr.clone())
+        .ok_or_else(|| {
+            let allowed: Vec<&str> = fields.iter().map(|(field, _)| *field).collect();
+            AppError::field("sort", &format!("Must be one of: {}.", allowed.join(", ")))
+        })?;
+
+    Ok(select.order_by(expr, match order {
+        SortOrder::Asc => Order::Asc,
+        SortOrder::Desc => Order::Desc,
+    }))
+}
+
+/// Fetches the requested page together with the total count.
+pub async fn paginate<'db, C, S>(
+    select: S,
+    db: &'db C,
+    query: &ListQuery,
+) -> Result<Page<<S::Selector as SelectorTrait>::Item>, AppError>
+where
+    C: ConnectionTrait,
+    S: PaginatorTrait<'db, C>,
+{
+    let page = query.page().ok_or(AppError::field("cursor", "Invalid cursor."))?;
+    let per_page = query.per_page();
+
+    let paginator = select.paginate(db, per_page);
+    let total_items = paginator.num_items().await?;
+    let items = paginator.fetch_page(page - 1).await?;
+
+    Ok(Page::new(items, page, per_page, total_items))
+}
+
+/// A case-insensitive substring match for `?q=` style filters.
+pub fn search(column: impl ColumnTrait, term: &str) -> SimpleExpr {
+    let escaped = term.trim().to_lowercase()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    Expr::expr(Func::lower(Expr::col(column.as_column_ref()))).like(format!("%{}%", escaped))
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{DatabaseBackend, EntityTrait, IntoSimpleExpr, QueryFilter, QueryTrait, Value};
+    use crate::entities::groups;
+    use super::*;
+
+    fn sorted_sql(query: ListQuery) -> Result<String, AppError> {
+        let select = sort(groups::Entity::find(), &query, &[
+            ("name", groups::Column::Name.into_simple_expr()),
+            ("created_at", groups::Column::CreatedAt.into_simple_expr()),
+        ], ("name", SortOrder::Asc))?;
+
+        Ok(select.build(DatabaseBackend::Postgres).to_string())
+    }
+
+    #[test]
+    fn sorting_is_limited_to_the_listed_fields() {
+        let query = ListQuery { sort: Some("password".to_string()), ..Default::default() };
+
+        match sorted_sql(query) {
+            Err(AppError::Validation(errors)) => assert_eq!(errors["sort"], ["Must be one of: name, created_at."]),
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sorting_uses_the_requested_field_and_order() {
+        let query = ListQuery { sort: Some("created_at".to_string()), order: Some(SortOrder::Desc), ..Default::default() };
+
+        assert!(sorted_sql(query).unwrap().ends_with(r#"ORDER BY "Groups"."created_at" DESC"#));
+    }
+
+    #[test]
+    fn sorting_falls_back_to_the_default_field() {
+        let query = ListQuery { order: Some(SortOrder::Desc), ..Default::default() };
+
+        assert!(sorted_sql(query).unwrap().ends_with(r#"ORDER BY "Groups"."name" DESC"#));
+    }
+
+    #[test]
+    fn search_terms_match_wildcards_literally() {
+        let statement = groups::Entity::find()
+            .filter(search(groups::Column::Name, " 50%_Off\\ "))
+            .build(DatabaseBackend::Postgres);
+
+        assert!(statement.sql.ends_with(r#"WHERE LOWER("Groups"."name") LIKE $1"#), "{}", statement.sql);
+        assert_eq!(statement.values.unwrap().0, [Value::from(r"%50\%\_off\\%")]);
+    }
+
+    #[test]
+    fn cursors_point_at_the_next_page() {
+        let page = Page::new(vec![0; 20], 1, 20, 45);
+        let query = ListQuery { cursor: page.next_cursor, ..Default::default() };
+
+        assert_eq!(page.total_pages, 3);
+        assert_eq!(query.page(), Some(2));
+        assert_eq!(ListQuery { cursor: Some("not-a-cursor".to_string()), ..Default::default() }.page(), None);
+    }
+
+    #[test]
+    fn page_sizes_are_clamped() {
+        assert_eq!(ListQuery { per_page: Some(0), ..Default::default() }.per_page(), 1);
+        assert_eq!(ListQuery { per_page: Some(10_000), ..Default::default() }.per_page(), 100);
+        assert_eq!(ListQuery::default().per_page(), 20);
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use sha1::{Digest, Sha1};
+use shuttle_runtime::SecretStore;
+use crate::errors::AppError;
+
+const DEFAULT_MIN_LENGTH: usize = 10;
+const MAX_LENGTH: usize = 128;
+const DEFAULT_MIN_SCORE: u8 = 3;
+
+/// Passwords and words common enough that attackers try them first, checked after undoing leet substitutions.
+const COMMON_WORDS: &[&str] = &[
+    "password", "passwort", "qwerty", "letmein", "welcome", "admin", "administrator", "login",
+    "iloveyou", "monkey", "dragon", "football", "baseball", "master", "sunshine", "princess",
+    "shadow", "superman", "batman", "trustno", "secret", "lockbox", "changeme", "default",
+    "starwars", "whatever", "freedom", "hello", "charlie", "summer", "winter", "spring", "autumn",
+];
+
+const KEYBOARD_ROWS: &[&str] = &["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+/// Rules every new account or group password has to satisfy.
+pub struct PasswordPolicy {
+    min_length: usize,
+    min_score: u8,
+    breached_passwords_dir: Option<PathBuf>,
+}
+
+impl PasswordPolicy {
+    /// Reads `PASSWORD_MIN_LENGTH`, `PASSWORD_MIN_SCORE` (0-4) and `BREACHED_PASSWORDS_DIR`, a directory of
+    /// k-anonymity range files named after the first five hex characters of the password's SHA-1 hash.
+    pub fn from_secrets(secrets: &SecretStore) -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: secrets.get("PASSWORD_MIN_LENGTH")
+                .map(|length| length.parse().expect("PASSWORD_MIN_LENGTH must be a number"))
+                .unwrap_or(DEFAULT_MIN_LENGTH),
+            min_score: secrets.get("PASSWORD_MIN_SCORE")
+                .map(|score| score.parse().expect("PASSWORD_MIN_SCORE must be a number between 0 and 4"))
+                .unwrap_or(DEFAULT_MIN_SCORE)
+                .min(4),
+            breached_passwords_dir: secrets.get("BREACHED_PASSWORDS_DIR")
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+        }
+    }
+
+    /// Returns every rule the password breaks. `user_inputs` are personal details such as the username
+    /// or email that shouldn't make up the password.
+    pub async fn violations(&self, password: &str, user_inputs: &[&str]) -> Vec<String> {
+        let length = password.chars().count();
+
+        if length < self.min_length {
+            return vec![format!("Password must be at least {} characters long.", self.min_length)];
+        }
+        if length > MAX_LENGTH {
+            return vec![format!("Password must be at most {} characters long.", MAX_LENGTH)];
+        }
+
+        let mut violations = Vec::new();
+
+        if strength_score(password, user_inputs) < self.min_score {
+            violations.push("Password is too easy to guess, avoid common words, sequences and personal details.".to_string());
+        }
+
+        if self.is_breached(password).await {
+            violations.push("Password has appeared in a data breach, choose a different one.".to_string());
+        }
+
+        violations
+    }
+
+    /// Fails with a field-level validation error when the password doesn't satisfy the policy.
+    pub async fn validate(&self, field: &str, password: &str, user_inputs: &[&str]) -> Result<(), AppError> {
+        let violations = self.violations(password, user_inputs).await;
+
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        Err(AppError::Validation(BTreeMap::from([(field.to_string(), violations)])))
+    }
+
+    /// Looks the password up in the local range file for its hash prefix, so only the prefix picks the file.
+    async fn is_breached(&self, password: &str) -> bool {
+        let dir = match &self.breached_passwords_dir {
+            Some(dir) => dir,
+            None => return false,
+        };
+
+        let hash = format!("{:X}", Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = hash.split_at(5);
+
+        let range = match tokio::fs::read_to_string(dir.join(format!("{}.txt", prefix))).await {
+            Ok(range) => range,
+            Err(e) => {
+                log::warn!("Breached password range {} is unavailable: {}", prefix, e);
+                return false;
+            },
+        };
+
+        range.lines()
+            .filter_map(|line| line.trim().split_once(':'))
+            .any(|(candidate, count)| candidate.eq_ignore_ascii_case(suffix) && count.trim() != "0")
+    }
+}
+
+fn unleet(c: char) -> char {
+    match c {
+        '@' | '4' => 'a',
+        '3' => 'e',
+        '1' | '!' => 'i',
+        '0' => 'o',
+        '$' | '5' => 's',
+        '7' => 't',
+        _ => c,
+    }
+}
+
+fn charset_size(password: &str) -> f64 {
+    let mut size = 0;
+    if password.chars().any(|c| c.is_ascii_lowercase()) { size += 26; }
+    if password.chars().any(|c| c.is_ascii_uppercase()) { size += 26; }
+    if password.chars().any(|c| c.is_ascii_digit()) { size += 10; }
+    if password.chars().any(|c| c.is_ascii_punctuation() || c == ' ') { size += 33; }
+    if !password.is_ascii() { size += 100; }
+    size.max(10) as f64
+}
+
+/// Whether `next` continues a run from `previous`: a repeat, an alphabetical or numeric sequence,
+/// or a neighbouring key on the keyboard.
+fn continues_pattern(previous: char, next: char) -> bool {
+    if previous == next || (previous as i64 - next as i64).abs() == 1 {
+        return true;
+    }
+
+    KEYBOARD_ROWS.iter().any(|row| {
+        match (row.find(previous), row.find(next)) {
+            (Some(a), Some(b)) => a.abs_diff(b) == 1,
+            _ => false,
+        }
+    })
+}
+
+/// A zxcvbn-style score from 0 (trivially guessable) to 4 (very hard to guess), based on the
+/// estimated number of guesses once common words, personal details and keyboard patterns are discounted.
+fn strength_score(password: &str, user_inputs: &[&str]) -> u8 {
+    // Dictionary words are matched on the de-leeted character, keyboard patterns on the typed one.
+    let mut remaining: Vec<Option<(char, char)>> = password.to_lowercase().chars().map(|c| Some((c, unleet(c)))).collect();
+    let mut guesses_log10 = 0.0;
+
+    let user_words = user_inputs.iter()
+        .flat_map(|input| input.split(|c: char| !c.is_alphanumeric()))
+        .map(|word| word.to_lowercase().chars().map(unleet).collect::<String>())
+        .filter(|word| word.chars().count() >= 3);
+
+    let words = COMMON_WORDS.iter().map(|word| (word.to_string(), 4.0))
+        .chain(user_words.map(|word| (word, 1.0)));
+
+    // Each dictionary hit counts as a single guess from a list instead of one guess per character.
+    for (word, word_guesses_log10) in words {
+        let word: Vec<char> = word.chars().collect();
+        let matches = |window: &[Option<(char, char)>]| {
+            window.iter().zip(&word).all(|(c, w)| c.is_some_and(|(_, c)| c == *w))
+        };
+
+        while let Some(start) = remaining.windows(word.len()).position(matches) {
+            remaining.splice(start..start + word.len(), [None]);
+            guesses_log10 += word_guesses_log10;
+        }
+    }
+
+    // Recent years are guessed as one of a couple of hundred values.
+    let is_year = |window: &[Option<(char, char)>]| {
+        let digits: String = window.iter().flatten().map(|(c, _)| *c).collect();
+        digits.len() == 4 && (digits.starts_with("19") || digits.starts_with("20")) && digits.chars().all(|c| c.is_ascii_digit())
+    };
+
+    while let Some(start) = remaining.windows(4).position(is_year) {
+        remaining.splice(start..start + 4, [None]);
+        guesses_log10 += 200f64.log10();
+    }
+
+    let charset_log10 = charset_size(password).log10();
+    let mut previous: Option<char> = None;
+
+    for c in remaining {
+        let c = match c {
+            Some((c, _)) => c,
+            None => {
+                previous = None;
+                continue;
+            },
+        };
+
+        guesses_log10 += match previous {
+            Some(previous) if continues_pattern(previous, c) => 2f64.log10(),
+            _ => charset_log10,
+        };
+        previous = Some(c);
+    }
+
+    match guesses_log10 {
+        g if g < 3.0 => 0,
+        g if g < 6.0 => 1,
+        g if g < 8.0 => 2,
+        g if g < 10.0 => 3,
+        _ => 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSWORD_SUFFIX: &str = "1E4C9B93F3F0682250B6CF8331B7EE68FD8";
+
+    fn policy(breached_passwords_dir: Option<PathBuf>) -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: DEFAULT_MIN_LENGTH,
+            min_score: DEFAULT_MIN_SCORE,
+            breached_passwords_dir,
+        }
+    }
+
+    /// A range directory holding the file for the prefix of "password" (SHA-1 `5BAA61E4...`).
+    fn range_dir(range: &str) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("5BAA6.txt"), range).unwrap();
+        dir
+    }
+
+    #[test]
+    fn weak_passwords_score_below_the_default_minimum() {
+        for password in ["password", "P@ssw0rd2024", "qwertyuiop", "aaaaaaaaaaaa", "1234567890", "changeme2023"] {
+            assert!(strength_score(password, &[]) < DEFAULT_MIN_SCORE, "{} scored {}", password, strength_score(password, &[]));
+        }
+    }
+
+    #[test]
+    fn personal_details_weaken_a_password() {
+        let password = "janedoe1987!";
+
+        assert!(strength_score(password, &[]) >= DEFAULT_MIN_SCORE);
+        assert!(strength_score(password, &["jane.doe@example.com"]) < DEFAULT_MIN_SCORE);
+    }
+
+    #[test]
+    fn strong_passwords_reach_the_default_minimum() {
+        for password in ["correct horse battery staple", "x7#Rq!vM2pLz", "Tr0ub4dor&3-purple-Kiwi"] {
+            assert!(strength_score(password, &[]) >= DEFAULT_MIN_SCORE, "{} scored {}", password, strength_score(password, &[]));
+        }
+    }
+
+    #[test]
+    fn scores_stay_within_range() {
+        assert_eq!(strength_score("", &[]), 0);
+        assert_eq!(strength_score("vK9$wq2#Lm8!Zr4@Tx6%Bn1&", &[]), 4);
+    }
+
+    #[actix_web::test]
+    async fn breached_passwords_are_found_by_prefix_file_and_suffix() {
+        let dir = range_dir(&format!("0018A45C4D1DEF81644B54AB7F969B88D65:3\r\n{}:9545824\r\n", PASSWORD_SUFFIX.to_lowercase()));
+        let policy = policy(Some(dir.path().to_path_buf()));
+
+        assert!(policy.is_breached("password").await);
+        assert!(!policy.is_breached("password1").await);
+    }
+
+    #[actix_web::test]
+    async fn zero_counts_and_malformed_lines_are_not_breaches() {
+        let dir = range_dir(&format!("{}\n{}:0\n", PASSWORD_SUFFIX, PASSWORD_SUFFIX));
+        let policy = policy(Some(dir.path().to_path_buf()));
+
+        assert!(!policy.is_breached("password").await);
+    }
+
+    #[actix_web::test]
+    async fn missing_ranges_are_not_breaches() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert!(!policy(Some(dir.path().to_path_buf())).is_breached("password").await);
+        assert!(!policy(None).is_breached("password").await);
+    }
+}
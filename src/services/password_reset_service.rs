@@ -0,0 +1,199 @@
+use std::time::Duration;
+use actix_web::web;
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QueryFilter, QueryOrder, TransactionTrait};
+use sea_orm::ActiveValue::Set;
+use sea_orm::sea_query::Expr;
+use sha2::{Digest, Sha256};
+use crate::dtos::user_dto::{ForgotPassword, ResetPassword};
+use crate::entities::{password_resets, users};
+use crate::errors::AppError;
+use crate::services::hash_service;
+use crate::services::mail_service::{Email, Mailer};
+use crate::services::password_policy_service::PasswordPolicy;
+
+const RESET_TOKEN_LIFETIME: Duration = Duration::from_secs(3600);
+const RESET_REQUEST_INTERVAL: Duration = Duration::from_secs(60);
+
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+pub async fn forgot_password(
+    db: web::Data<DatabaseConnection>,
+    mailer: web::Data<dyn Mailer>,
+    form: web::Json<ForgotPassword>,
+) {
+    // Every outcome answers the same way so the endpoint can't be used to probe for registered emails. The request is
+    // handled in the background, waiting for the lookups and the mail relay would give registered emails away by timing.
+    let email = form.into_inner().email;
+    actix_web::rt::spawn(async move {
+        if let Err(error) = request_reset(db.get_ref(), mailer.get_ref(), &email).await {
+            log::error!("Failed to process password reset request: {}", error);
+        }
+    });
+}
+
+async fn request_reset(db: &DatabaseConnection, mailer: &dyn Mailer, email: &str) -> Result<(), AppError> {
+    let user = users::Entity::find()
+        .filter(users::Column::Email.eq(email))
+        .one(db)
+        .await?;
+
+    let user = match user {
+        Some(user) if !user.is_deleted => user,
+        _ => return Ok(()),
+    };
+
+    let latest = password_resets::Entity::find()
+        .filter(password_resets::Column::UserId.eq(user.id))
+        .order_by_desc(password_resets::Column::CreatedAt)
+        .one(db)
+        .await?;
+
+    if let Some(latest) = latest {
+        if latest.created_at + RESET_REQUEST_INTERVAL > Utc::now() {
+            return Ok(());
+        }
+    }
+
+    let token = nanoid::nanoid!(43);
+    let now = Utc::now();
+
+    let reset = password_resets::ActiveModel {
+        user_id: Set(user.id),
+        token_hash: Set(hash_token(&token)),
+        created_at: Set(now.fixed_offset()),
+        expires_at: Set((now + RESET_TOKEN_LIFETIME).fixed_offset()),
+        ..Default::default()
+    };
+    reset.insert(db).await?;
+
+    let reset_url = std::env::var("PASSWORD_RESET_URL").unwrap_or_default();
+    let email = Email {
+        to: user.email.clone(),
+        subject: "Reset your LockBox password".to_string(),
+        body: format!(
+            "A password reset was requested for your LockBox account.\n\nChoose a new password by opening the link below:\n{}?token={}\n\nThe link expires in 1 hour. If you didn't request a reset, you can ignore this email.",
+            reset_url, token
+        ),
+    };
+
+    mailer.send(email).await.map_err(AppError::internal)
+}
+
+pub async fn reset_password(
+    db: web::Data<DatabaseConnection>,
+    password_policy: web::Data<PasswordPolicy>,
+    form: web::Json<ResetPassword>,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+    let form = form.into_inner();
+    let invalid_token = || AppError::bad_request("Invalid or expired reset token!");
+
+    let reset = password_resets::Entity::find()
+        .filter(password_resets::Column::TokenHash.eq(hash_token(&form.token)))
+        .one(db)
+        .await?
+        .filter(|reset| reset.used_at.is_none() && reset.expires_at > Utc::now())
+        .ok_or_else(invalid_token)?;
+
+    let user = users::Entity::find_by_id(reset.user_id)
+        .one(db)
+        .await?
+        .filter(|user| !user.is_deleted)
+        .ok_or_else(invalid_token)?;
+
+    let username = user.username.clone().unwrap_or_default();
+    password_policy.validate("password", &form.password, &[&username, &user.email]).await?;
+
+    let hashed_password = hash_service::hash_password(&form.password).await?;
+
+    let transaction = db.begin().await?;
+
+    // Burns this token together with any other outstanding reset links for the account.
+    let used = password_resets::Entity::update_many()
+        .col_expr(password_resets::Column::UsedAt, Expr::value(Utc::now().fixed_offset()))
+        .filter(password_resets::Column::UserId.eq(user.id))
+        .filter(password_resets::Column::UsedAt.is_null())
+        .exec(&transaction)
+        .await?;
+
+    if used.rows_affected == 0 {
+        return Err(invalid_token());
+    }
+
+    let session_version = user.session_version;
+    let mut user = user.into_active_model();
+    user.password = Set(Some(hashed_password));
+    user.session_version = Set(session_version + 1);
+    user.update(&transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+    use crate::services::mail_service::MailFuture;
+    use super::*;
+
+    /// A relay that accepts the mail and then never answers.
+    #[derive(Default)]
+    struct StalledMailer {
+        sent: AtomicUsize,
+    }
+
+    impl Mailer for StalledMailer {
+        fn send(&self, _email: Email) -> MailFuture<'_> {
+            self.sent.fetch_add(1, Ordering::SeqCst);
+            Box::pin(std::future::pending())
+        }
+    }
+
+    fn user() -> users::Model {
+        users::Model {
+            id: 42,
+            username: Some("jane".to_string()),
+            email: "jane@example.com".to_string(),
+            password: None,
+            created_at: Utc::now().fixed_offset(),
+            is_deleted: false,
+            email_verified: true,
+            session_version: 0,
+            totp_secret: None,
+            totp_enabled: false,
+            totp_last_step: None,
+            is_admin: false,
+        }
+    }
+
+    #[actix_web::test]
+    async fn requests_are_answered_without_waiting_for_the_mail() {
+        let now = Utc::now().fixed_offset();
+        let reset = password_resets::Model { id: 1, user_id: 42, token_hash: String::new(), created_at: now, expires_at: now, used_at: None };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![user()]])
+            .append_query_results([Vec::<password_resets::Model>::new()])
+            .append_query_results([vec![reset]])
+            .into_connection();
+        let mailer = Arc::new(StalledMailer::default());
+        let form = web::Json(ForgotPassword { email: "jane@example.com".to_string() });
+
+        let answered = tokio::time::timeout(
+            Duration::from_secs(1),
+            forgot_password(web::Data::new(db), web::Data::from(mailer.clone() as Arc<dyn Mailer>), form),
+        ).await;
+        assert!(answered.is_ok());
+
+        // The mail still goes out in the background.
+        for _ in 0..10 {
+            actix_web::rt::task::yield_now().await;
+        }
+        assert_eq!(mailer.sent.load(Ordering::SeqCst), 1);
+    }
+}
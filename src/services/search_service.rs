@@ -0,0 +1,292 @@
+use actix_web::web;
+use sea_orm::{DatabaseConnection, DbBackend, FromQueryResult, Statement};
+use serde::{Deserialize, Serialize};
+use crate::dtos::pagination_dto::{ListQuery, Page, SortOrder};
+use crate::dtos::search_dto::{SearchQuery, SearchResultResponse};
+use crate::errors::AppError;
+use crate::services::auth_service::{Role, UserClaims};
+use crate::services::group_service::GroupVisibility;
+use crate::services::pagination_service;
+
+const MAX_QUERY_LENGTH: usize = 200;
+
+// ts_headline wraps matches in these, they can't appear in stored text so escaping the excerpt can't touch them.
+const MARK_START: char = '\u{2}';
+const MARK_END: char = '\u{3}';
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchKind {
+    Video,
+    Transcript,
+    Group,
+    User,
+}
+
+impl SearchKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SearchKind::Video => "video",
+            SearchKind::Transcript => "transcript",
+            SearchKind::Group => "group",
+            SearchKind::User => "user",
+        }
+    }
+
+    pub fn from_db(kind: &str) -> SearchKind {
+        match kind {
+            "transcript" => SearchKind::Transcript,
+            "group" => SearchKind::Group,
+            "user" => SearchKind::User,
+            _ => SearchKind::Video,
+        }
+    }
+}
+
+#[derive(Debug, FromQueryResult)]
+struct SearchHit {
+    kind: String,
+    id: i64,
+    title: String,
+    group_id: Option<i64>,
+    rank: f32,
+    headline: String,
+    start_seconds: Option<f64>,
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            MARK_START => escaped.push_str("<mark>"),
+            MARK_END => escaped.push_str("</mark>"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn to_response(hit: SearchHit) -> SearchResultResponse {
+    SearchResultResponse {
+        kind: SearchKind::from_db(&hit.kind),
+        id: hit.id,
+        title: hit.title,
+        group_id: hit.group_id,
+        rank: hit.rank,
+        highlight: escape_html(&hit.headline),
+        start_seconds: hit.start_seconds,
+    }
+}
+
+/// The SQL for one kind of result. `$1` is the search text, `$2` the caller and `$3` the headline options,
+/// the text passed to `ts_headline` is blanked out the same way the migration does so highlights line up.
+fn select_for(kind: SearchKind, is_admin: bool) -> String {
+    // Same rules as the group and video listings: admins see everything, videos of non-public
+    // groups need a membership and hidden groups only show up for their members.
+    let member_of = r#"SELECT group_id FROM "GroupUser" WHERE user_id = $2"#;
+    let video_groups = match is_admin {
+        true => String::from("TRUE"),
+        false => format!("NOT g.is_deleted AND (g.visibility = '{}' OR g.id IN ({}))", GroupVisibility::Public.as_str(), member_of),
+    };
+    let visible_groups = match is_admin {
+        true => String::from("TRUE"),
+        false => format!("NOT g.is_deleted AND (g.visibility <> '{}' OR g.id IN ({}))", GroupVisibility::Hidden.as_str(), member_of),
+    };
+
+    match kind {
+        SearchKind::Video => format!(
+            r#"SELECT '{}' AS kind, v.id, coalesce(v.title, v.name) AS title, access.group_id,
+                      ts_rank(v.search_vector, query) AS rank,
+                      ts_headline('simple', translate(coalesce(v.title, v.name), '._', '  ') || coalesce(' - ' || v.description, ''), query, $3) AS headline,
+                      NULL::double precision AS start_seconds
+               FROM "Videos" v
+               CROSS JOIN websearch_to_tsquery('simple', $1) query
+               CROSS JOIN LATERAL (
+                   SELECT min(gv.group_id) AS group_id FROM "GroupVideo" gv
+                   JOIN "Groups" g ON g.id = gv.group_id
+                   WHERE gv.video_id = v.id AND {}
+               ) access
+               WHERE v.search_vector @@ query AND access.group_id IS NOT NULL"#,
+            kind.as_str(), video_groups
+        ),
+        // One hit per video, the passage of its transcript that matches best.
+        SearchKind::Transcript => format!(
+            r#"SELECT '{}' AS kind, v.id, coalesce(v.title, v.name) AS title, access.group_id,
+                      best.rank,
+                      ts_headline('simple', best.text, query, $3) AS headline,
+                      best.start_seconds
+               FROM (
+                   SELECT DISTINCT ON (s.video_id) s.video_id, s.text, s.start_seconds, ts_rank(s.search_vector, query) AS rank
+                   FROM "TranscriptSegments" s
+                   CROSS JOIN websearch_to_tsquery('simple', $1) query
+                   WHERE s.search_vector @@ query
+                   ORDER BY s.video_id, rank DESC, s.position
+               ) best
+               JOIN "Videos" v ON v.id = best.video_id
+               CROSS JOIN websearch_to_tsquery('simple', $1) query
+               CROSS JOIN LATERAL (
+                   SELECT min(gv.group_id) AS group_id FROM "GroupVideo" gv
+                   JOIN "Groups" g ON g.id = gv.group_id
+                   WHERE gv.video_id = v.id AND {}
+               ) access
+               WHERE access.group_id IS NOT NULL"#,
+            kind.as_str(), video_groups
+        ),
+        SearchKind::Group => format!(
+            r#"SELECT '{}' AS kind, g.id, g.name AS title, g.id AS group_id,
+                      ts_rank(g.search_vector, query) AS rank,
+                      ts_headline('simple', translate(g.name, '._', '  ') || coalesce(' - ' || g.description, ''), query, $3) AS headline,
+                      NULL::double precision AS start_seconds
+               FROM "Groups" g
+               CROSS JOIN websearch_to_tsquery('simple', $1) query
+               WHERE g.search_vector @@ query AND {}"#,
+            kind.as_str(), visible_groups
+        ),
+        SearchKind::User => format!(
+            r#"SELECT '{}' AS kind, u.id, coalesce(u.username, '') AS title, NULL::bigint AS group_id,
+                      ts_rank(u.search_vector, query) AS rank,
+                      ts_headline('simple', translate(coalesce(u.username, ''), '._', '  '), query, $3) AS headline,
+                      NULL::double precision AS start_seconds
+               FROM "Users" u
+               CROSS JOIN websearch_to_tsquery('simple', $1) query
+               WHERE u.search_vector @@ query"#,
+            kind.as_str()
+        ),
+    }
+}
+
+/// Ranked full-text search over videos, their transcripts and groups the caller can access, plus usernames for admins.
+pub async fn search(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    search: web::Query<SearchQuery>,
+    query: web::Query<ListQuery>,
+) -> Result<Page<SearchResultResponse>, AppError> {
+    let term = search.q.trim();
+    if term.is_empty() {
+        return Err(AppError::field("q", "Must not be blank."));
+    }
+    if term.chars().count() > MAX_QUERY_LENGTH {
+        return Err(AppError::field("q", &format!("Must be at most {} characters long.", MAX_QUERY_LENGTH)));
+    }
+
+    let is_admin = user_claims.role == Role::Admin;
+
+    let kinds = match search.kind {
+        Some(SearchKind::User) if !is_admin => return Err(AppError::forbidden("Only admins can search users!")),
+        Some(kind) => vec![kind],
+        None if is_admin => vec![SearchKind::Video, SearchKind::Transcript, SearchKind::Group, SearchKind::User],
+        None => vec![SearchKind::Video, SearchKind::Transcript, SearchKind::Group],
+    };
+
+    let (sort, default_order) = match query.sort.as_deref() {
+        None | Some("rank") => ("rank", SortOrder::Desc),
+        Some("title") => ("lower(title)", SortOrder::Asc),
+        Some(_) => return Err(AppError::field("sort", "Must be one of: rank, title.")),
+    };
+    let order = match query.order.unwrap_or(default_order) {
+        SortOrder::Asc => "ASC",
+        SortOrder::Desc => "DESC",
+    };
+
+    let branches: Vec<String> = kinds.into_iter().map(|kind| select_for(kind, is_admin)).collect();
+    let sql = format!(
+        "SELECT * FROM ({}) AS hits ORDER BY {} {}, kind, id",
+        branches.join(" UNION ALL "), sort, order
+    );
+
+    let headline_options = format!("StartSel=\"{}\", StopSel=\"{}\"", MARK_START, MARK_END);
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        sql,
+        [term.into(), user_claims.id.into(), headline_options.into()],
+    );
+
+    let hits = pagination_service::paginate(SearchHit::find_by_statement(statement), db.get_ref(), &query).await?;
+
+    Ok(hits.map(to_response))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use sea_orm::{MockDatabase, Value};
+    use super::*;
+
+    const MEMBER_OF: &str = r#"g.id IN (SELECT group_id FROM "GroupUser" WHERE user_id = $2)"#;
+
+    fn claims(role: Role) -> UserClaims {
+        UserClaims { id: 7, role, session_version: 0, scopes: None }
+    }
+
+    fn search_query(q: &str, kind: Option<SearchKind>) -> web::Query<SearchQuery> {
+        web::Query(SearchQuery { q: q.to_string(), kind })
+    }
+
+    #[test]
+    fn hidden_groups_only_match_for_their_members() {
+        let sql = select_for(SearchKind::Group, false);
+
+        assert!(sql.contains(&format!("NOT g.is_deleted AND (g.visibility <> 'hidden' OR {})", MEMBER_OF)), "{}", sql);
+    }
+
+    #[test]
+    fn videos_outside_public_groups_need_a_membership() {
+        let sql = select_for(SearchKind::Video, false);
+
+        assert!(sql.contains(&format!("NOT g.is_deleted AND (g.visibility = 'public' OR {})", MEMBER_OF)), "{}", sql);
+    }
+
+    #[test]
+    fn admins_search_every_group() {
+        assert!(!select_for(SearchKind::Group, true).contains("GroupUser"));
+        assert!(!select_for(SearchKind::Video, true).contains("GroupUser"));
+    }
+
+    #[actix_web::test]
+    async fn only_admins_search_users() {
+        let db = web::Data::new(MockDatabase::new(DbBackend::Postgres).into_connection());
+
+        let result = search(db, claims(Role::RegisteredUser), search_query("jane", Some(SearchKind::User)), web::Query(ListQuery::default())).await;
+
+        assert!(matches!(result, Err(AppError::Forbidden(_))), "{:?}", result);
+    }
+
+    #[actix_web::test]
+    async fn users_are_left_out_of_searches_by_non_admins() {
+        let db = web::Data::new(MockDatabase::new(DbBackend::Postgres)
+            .append_query_results([[BTreeMap::from([("num_items", Value::BigInt(Some(0)))])]])
+            .append_query_results([Vec::<BTreeMap<String, Value>>::new()])
+            .into_connection());
+
+        search(db.clone(), claims(Role::RegisteredUser), search_query("jane", None), web::Query(ListQuery::default())).await.unwrap();
+
+        let log = Arc::try_unwrap(db.into_inner()).unwrap().into_transaction_log();
+        let sql = log[1].statements()[0].sql.clone();
+        assert!(sql.contains(r#"FROM "Videos" v"#) && sql.contains(r#"FROM "Groups" g"#), "{}", sql);
+        assert!(!sql.contains(r#"FROM "Users" u"#), "{}", sql);
+    }
+
+    #[actix_web::test]
+    async fn sorting_is_limited_to_rank_and_title() {
+        let db = web::Data::new(MockDatabase::new(DbBackend::Postgres).into_connection());
+        let query = web::Query(ListQuery { sort: Some("id".to_string()), ..Default::default() });
+
+        let result = search(db, claims(Role::Admin), search_query("jane", None), query).await;
+
+        assert!(matches!(result, Err(AppError::Validation(_))), "{:?}", result);
+    }
+
+    #[test]
+    fn highlights_escape_everything_but_the_marks() {
+        let headline = format!("<b>{}Jane{}</b> & \"co\"", MARK_START, MARK_END);
+
+        assert_eq!(escape_html(&headline), "&lt;b&gt;<mark>Jane</mark>&lt;/b&gt; &amp; &quot;co&quot;");
+    }
+}
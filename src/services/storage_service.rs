@@ -1,18 +1,26 @@
+use std::collections::BTreeMap;
 use actix_multipart::form::MultipartForm;
 use actix_multipart::form::tempfile::TempFile;
-use actix_web::{error, web, Error, HttpMessage, HttpRequest, HttpResponse};
+use actix_multipart::form::text::Text;
+use actix_web::web::Bytes;
+use actix_web::web;
 use aws_config::Region;
 use aws_sdk_s3::config::Credentials;
 use aws_sdk_s3 as s3;
 use aws_sdk_s3::operation::create_multipart_upload::CreateMultipartUploadOutput;
 use aws_sdk_s3::types::{ChecksumMode, CompletedMultipartUpload, CompletedPart};
-use futures_util::stream::StreamExt;
 use aws_smithy_types::byte_stream::{ByteStream, Length};
 use sea_orm::ActiveValue::Set;
-use sea_orm::{ActiveModelTrait, DatabaseConnection};
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
 use shuttle_runtime::SecretStore;
-use crate::entities::videos;
-use crate::services::group_service;
+use validator::Validate;
+use crate::dtos::validation;
+use crate::dtos::video_dto::UpdateVideoForm;
+use crate::entities::{group_video, videos};
+use crate::errors::AppError;
+use crate::services::auth_service::UserClaims;
+use crate::services::{group_service, video_service, video_transcript_service};
+use crate::services::transcription_service::TranscriptionEngine;
 
 pub async fn create_client(secrets: SecretStore) -> s3::Client {
     let access_token_id = secrets.get("AWS_ACCESS_KEY_ID").expect("ACCESS_TOKEN_ID");
@@ -46,67 +54,145 @@ pub fn generate_random_key(file_extension: &str) -> String {
     format!("{}.{}", random_str, file_extension)
 }
 
-pub fn extract_file_extension(req_data: &HttpRequest) -> Option<String> {
-    todo!()
+fn bucket_name() -> Result<String, AppError> {
+    std::env::var("VIDEO_STORAGE_BUCKET").map_err(|_| AppError::internal("VIDEO_STORAGE_BUCKET is not set"))
 }
 
+/// Streams the video back to anyone who can view one of the groups it was uploaded to.
 pub async fn serve_video(
     client: web::Data<s3::Client>,
+    db: web::Data<DatabaseConnection>,
     key: web::Path<String>,
-) -> Result<HttpResponse, Error> {
+    user_claims: UserClaims,
+) -> Result<Bytes, AppError> {
+    let db = db.get_ref();
+    let key = key.into_inner();
+
+    let entries = group_video::Entity::find()
+        .inner_join(videos::Entity)
+        .filter(videos::Column::Key.eq(key.as_str()))
+        .all(db)
+        .await?;
 
-    let bucket_name = std::env::var("VIDEO_STORAGE_BUCKET").expect("BUCKET_NAME");
+    for entry in entries {
+        match group_service::require_viewer(db, entry.group_id, user_claims.id).await {
+            Ok(_) => return get_object(&client, &key).await,
+            Err(AppError::Forbidden(_) | AppError::NotFound(_)) => continue,
+            Err(e) => return Err(e),
+        }
+    }
 
+    Err(AppError::not_found("Video not found!"))
+}
+
+/// Reads a whole object from the video bucket.
+pub async fn get_object(client: &s3::Client, key: &str) -> Result<Bytes, AppError> {
     let object = client.get_object()
         .checksum_mode(ChecksumMode::Enabled)
-        .bucket(&bucket_name)
-        .key(&key.into_inner())
+        .bucket(bucket_name()?)
+        .key(key)
         .send()
         .await
-        .map_err(|e| {
-            eprintln!("Failed to fetch video: {:?}", e);
-            actix_web::error::ErrorInternalServerError("Failed to fetch video")
-        })?;
-
-    let body = match object.body.collect().await {
-        Ok(body) => body,
-        Err(_) => return Ok(HttpResponse::InternalServerError().body("INNER ERROR"))
-    };
+        .map_err(|e| AppError::Storage(format!("failed to fetch object {}: {:?}", key, e)))?;
+
+    let body = object.body.collect().await
+        .map_err(|e| AppError::Storage(format!("failed to read object {}: {:?}", key, e)))?;
+
+    Ok(body.into_bytes())
+}
+
+/// Stores a small object in one request, videos go through the multipart upload instead.
+pub async fn put_object(client: &s3::Client, key: &str, body: Vec<u8>, content_type: &str) -> Result<(), AppError> {
+    client.put_object()
+        .bucket(bucket_name()?)
+        .key(key)
+        .content_type(content_type)
+        .body(ByteStream::from(body))
+        .send()
+        .await
+        .map_err(|e| AppError::Storage(format!("failed to store object {}: {:?}", key, e)))?;
 
-    Ok(HttpResponse::Ok().content_type("video/mp4").body(body.into_bytes()))
+    Ok(())
+}
+
+pub async fn delete_object(client: &s3::Client, key: &str) -> Result<(), AppError> {
+    client.delete_object()
+        .bucket(bucket_name()?)
+        .key(key)
+        .send()
+        .await
+        .map_err(|e| AppError::Storage(format!("failed to delete object {}: {:?}", key, e)))?;
+
+    Ok(())
 }
 
 #[derive(Debug, MultipartForm)]
 pub struct UploadForm {
     #[multipart(limit = "512 MiB")]
     file: TempFile,
+    title: Option<Text<String>>,
+    description: Option<Text<String>>,
+    /// Repeat the field once per tag.
+    tags: Vec<Text<String>>,
+    /// A JSON object of string values, stored with the video in the group.
+    metadata: Option<Text<String>>,
+}
+
+impl UploadForm {
+    /// The optional fields next to the file, checked like a `PATCH` of the video.
+    fn details(&self) -> Result<UpdateVideoForm, AppError> {
+        let metadata = match &self.metadata {
+            Some(metadata) => Some(
+                serde_json::from_str::<BTreeMap<String, String>>(metadata)
+                    .map_err(|_| AppError::field("metadata", "Must be a JSON object with string values."))?
+            ),
+            None => None,
+        };
+
+        let details = UpdateVideoForm {
+            title: self.title.as_ref().map(|title| title.to_string()),
+            description: self.description.as_ref().map(|description| description.to_string()),
+            tags: (!self.tags.is_empty()).then(|| self.tags.iter().map(|tag| tag.to_string()).collect()),
+            metadata,
+        };
+
+        details.validate().map_err(|errors| validation::validation_errors(&errors))?;
+
+        Ok(details)
+    }
 }
 
 const CHUNK_SIZE: u64 = 1024 * 1024 * 5;
 const MAX_CHUNKS: u64 = 10000;
 
+/// Stores the video and queues its transcription, which keeps the uploaded file until the engine is done.
 pub async fn upload_video(
     client: web::Data<s3::Client>,
+    engine: web::Data<dyn TranscriptionEngine>,
     MultipartForm(form): MultipartForm<UploadForm>,
     db: web::Data<DatabaseConnection>,
     group_id: web::Path<i64>,
-) -> Result<HttpResponse, Error> {
+    user_claims: UserClaims,
+) -> Result<(), AppError> {
+    let group_id = group_id.into_inner();
+    group_service::require_membership(db.get_ref(), group_id, user_claims.id).await?;
 
-    let bucket_name = std::env::var("VIDEO_STORAGE_BUCKET").expect("BUCKET_NAME");
+    let details = form.details()?;
+
+    let bucket_name = bucket_name()?;
     let key = generate_random_key("mp4");
 
-    let video = videos::ActiveModel {
-        name: Set(form.file.file_name.unwrap_or_default().clone()),
+    let mut video = videos::ActiveModel {
+        name: Set(form.file.file_name.clone().unwrap_or_default()),
         key: Set(key.clone()),
         ..Default::default()
     };
+    video_service::apply_details(&mut video, &details);
 
-    let inserted_video = match video.insert(db.as_ref()).await {
-        Ok(video) => video,
-        Err(_) => return Ok(HttpResponse::InternalServerError().body("Failed to insert video!")),
-    };
+    let inserted_video = video.insert(db.as_ref()).await?;
 
-    group_service::add_video_to_group(group_id.into_inner(), inserted_video.id, db.clone()).await?;
+    let metadata = video_service::metadata_json(&details.metadata.unwrap_or_default());
+    group_service::add_video_to_group(group_id, inserted_video.id, metadata, db.clone()).await?;
 
     let multipart_upload_res: CreateMultipartUploadOutput = client
         .create_multipart_upload()
@@ -114,13 +200,10 @@ pub async fn upload_video(
         .key(&key)
         .send()
         .await
-        .map_err(|e| {
-            eprintln!("{:?}", e);
-            error::ErrorInternalServerError("Failed to create multipart upload")
-        })?;
+        .map_err(|e| AppError::Storage(format!("failed to create multipart upload: {:?}", e)))?;
 
     let upload_id = multipart_upload_res.upload_id()
-        .ok_or(error::ErrorInternalServerError("Missing upload_id after CreateMultipartUpload"))?;
+        .ok_or(AppError::Storage("missing upload_id after CreateMultipartUpload".to_string()))?;
 
     let form_file_size = &form.file.size;
     let file_size = form_file_size.to_owned() as u64;
@@ -133,10 +216,10 @@ pub async fn upload_video(
     }
 
     if file_size == 0 {
-        return Err(error::ErrorInternalServerError("Bad file size."));
+        return Err(AppError::field("file", "The uploaded file is empty."));
     }
     if chunk_count > MAX_CHUNKS {
-        return Err(error::ErrorBadRequest("Too many chunks!" ));
+        return Err(AppError::field("file", "The uploaded file is too large."));
     }
 
     let mut upload_parts: Vec<aws_sdk_s3::types::CompletedPart> = Vec::new();
@@ -153,7 +236,7 @@ pub async fn upload_video(
             .length(Length::Exact(this_chunk))
             .build()
             .await
-            .unwrap();
+            .map_err(|e| AppError::internal(format!("failed to read upload chunk: {:?}", e)))?;
 
         let part_number = (chunk_index as i32) + 1;
         let upload_part_res = client
@@ -165,10 +248,7 @@ pub async fn upload_video(
             .part_number(part_number)
             .send()
             .await
-            .map_err(|e| {
-                eprintln!("{:?}", e);
-                error::ErrorInternalServerError("Failed to upload part")
-            })?;
+            .map_err(|e| AppError::Storage(format!("failed to upload part: {:?}", e)))?;
 
         upload_parts.push(
             CompletedPart::builder()
@@ -190,10 +270,87 @@ pub async fn upload_video(
         .upload_id(upload_id)
         .send()
         .await
-        .map_err(|e| {
-            eprintln!("{:?}", e);
-            error::ErrorInternalServerError("Failed to complete multipart upload")
-        })?;
+        .map_err(|e| AppError::Storage(format!("failed to complete multipart upload: {:?}", e)))?;
+
+    // The video is stored either way, a failed transcription can be retried later.
+    let media = form.file.file.into_temp_path();
+    if let Err(e) = video_transcript_service::queue_transcription(db, engine, inserted_video.id, media).await {
+        log::error!("Failed to queue transcription of video {}: {}", inserted_video.id, e);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use aws_config::BehaviorVersion;
+    use chrono::Utc;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+    use crate::entities::{group_user, groups};
+    use crate::services::auth_service::Role;
+    use crate::services::group_service::GroupVisibility;
+    use super::*;
 
-    Ok(HttpResponse::Ok().body("Upload completed successfully!"))
-}
\ No newline at end of file
+    const KEY: &str = "abcdefghij.mp4";
+
+    fn client() -> web::Data<s3::Client> {
+        let config = s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("auto"))
+            .build();
+        web::Data::new(s3::Client::from_conf(config))
+    }
+
+    fn claims() -> UserClaims {
+        UserClaims { id: 30, role: Role::RegisteredUser, session_version: 0, scopes: None }
+    }
+
+    fn entry(group_id: i64) -> group_video::Model {
+        group_video::Model { group_id, video_id: 5, metadata: serde_json::json!({}), folder_id: None }
+    }
+
+    fn group(id: i64, visibility: GroupVisibility) -> groups::Model {
+        groups::Model {
+            id,
+            name: "film-club".to_string(),
+            password: None,
+            created_at: Utc::now().fixed_offset(),
+            is_deleted: false,
+            description: None,
+            visibility: visibility.as_str().to_string(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn unknown_keys_are_not_found() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<group_video::Model>::new()])
+            .into_connection();
+        let db = web::Data::new(db);
+
+        let result = serve_video(client(), db.clone(), web::Path::from(KEY.to_string()), claims()).await;
+
+        assert!(matches!(result, Err(AppError::NotFound(_))), "{:?}", result);
+
+        let log = Arc::try_unwrap(db.into_inner()).unwrap().into_transaction_log();
+        let lookup = log[0].statements()[0].to_string();
+        assert!(lookup.contains(r#"INNER JOIN "Videos""#), "{}", lookup);
+        assert!(lookup.contains(&format!(r#""Videos"."key" = '{}'"#, KEY)), "{}", lookup);
+    }
+
+    #[actix_web::test]
+    async fn videos_of_groups_the_caller_cant_view_are_not_found() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![entry(1), entry(2)]])
+            .append_query_results([vec![group(1, GroupVisibility::Hidden)]])
+            .append_query_results([Vec::<group_user::Model>::new()])
+            .append_query_results([vec![group(2, GroupVisibility::InviteOnly)]])
+            .append_query_results([Vec::<group_user::Model>::new()])
+            .into_connection();
+
+        let result = serve_video(client(), web::Data::new(db), web::Path::from(KEY.to_string()), claims()).await;
+
+        assert!(matches!(result, Err(AppError::NotFound(_))), "{:?}", result);
+    }
+}
@@ -0,0 +1,383 @@
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use actix_web::HttpRequest;
+use chrono::Utc;
+use sea_orm::{ColumnTrait, Condition, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
+use sea_orm::ActiveValue::Set;
+use sea_orm::sea_query::{Expr, OnConflict};
+use crate::entities::auth_throttles;
+use crate::errors::AppError;
+use crate::services::audit_service;
+use crate::services::audit_service::AuditEvent;
+
+/// Failures allowed before attempts start being delayed.
+const FREE_ATTEMPTS: i32 = 3;
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+const LOCKOUT_THRESHOLD: i32 = 10;
+const LOCKOUT_DURATION: Duration = Duration::from_secs(900);
+/// Failures older than this no longer count towards throttling.
+const FAILURE_WINDOW: Duration = Duration::from_secs(3600);
+
+#[derive(Clone, Copy, Debug)]
+pub enum AttemptKind {
+    Login,
+    MfaLogin,
+    GroupJoin,
+}
+
+impl AttemptKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AttemptKind::Login => "login",
+            AttemptKind::MfaLogin => "mfa_login",
+            AttemptKind::GroupJoin => "group_join",
+        }
+    }
+}
+
+/// A password attempt, throttled both per targeted account and per client IP.
+pub struct Attempt {
+    kind: AttemptKind,
+    account: String,
+    ip: String,
+}
+
+impl Attempt {
+    pub fn login(email: &str, ip: &str) -> Attempt {
+        Attempt {
+            kind: AttemptKind::Login,
+            account: email.trim().to_lowercase(),
+            ip: ip.to_string(),
+        }
+    }
+
+    /// A second factor code, the password check or the session already identified the account by id.
+    pub fn mfa_login(user_id: i64, ip: &str) -> Attempt {
+        Attempt {
+            kind: AttemptKind::MfaLogin,
+            account: user_id.to_string(),
+            ip: ip.to_string(),
+        }
+    }
+
+    pub fn group_join(user_id: i64, ip: &str) -> Attempt {
+        Attempt {
+            kind: AttemptKind::GroupJoin,
+            account: user_id.to_string(),
+            ip: ip.to_string(),
+        }
+    }
+
+    fn account_key(&self) -> String {
+        format!("{}:account:{}", self.kind.as_str(), self.account)
+    }
+
+    fn ip_key(&self) -> String {
+        format!("{}:ip:{}", self.kind.as_str(), self.ip)
+    }
+
+    /// Counts the attempt as a failure before it's checked, so concurrent guesses can't all slip through before
+    /// the first of them is recorded. Fails with a 429 while either the account or the client IP is locked.
+    pub async fn reserve(self, db: &DatabaseConnection) -> Result<ReservedAttempt, AppError> {
+        // The client IP first, so a locked client can't add to the account's count.
+        let ip_locks = self.reserve_key(db, self.ip_key()).await?;
+        let account_locks = self.reserve_key(db, self.account_key()).await?;
+
+        Ok(ReservedAttempt {
+            attempt: self,
+            account_locks,
+            ip_locks,
+        })
+    }
+
+    /// Counts the attempt against the key unless it's locked, and returns whether a failure now causes a lockout.
+    async fn reserve_key(&self, db: &DatabaseConnection, key: String) -> Result<bool, AppError> {
+        let now = Utc::now();
+
+        // Counted in the upsert itself, so concurrent attempts can't overwrite each other's count.
+        let counted_failures = Expr::case(
+            Expr::col((auth_throttles::Entity, auth_throttles::Column::LastFailureAt)).gt((now - FAILURE_WINDOW).fixed_offset()),
+            Expr::col((auth_throttles::Entity, auth_throttles::Column::Failures)).add(1),
+        ).finally(1);
+
+        let throttle = auth_throttles::ActiveModel {
+            key: Set(key.clone()),
+            user_id: Set(None),
+            failures: Set(1),
+            last_failure_at: Set(now.fixed_offset()),
+            locked_until: Set(None),
+        };
+
+        let reserved = auth_throttles::Entity::insert(throttle)
+            .on_conflict(
+                OnConflict::column(auth_throttles::Column::Key)
+                    .value(auth_throttles::Column::Failures, counted_failures)
+                    .update_column(auth_throttles::Column::LastFailureAt)
+                    .action_and_where(
+                        Expr::col((auth_throttles::Entity, auth_throttles::Column::LockedUntil)).is_null()
+                            .or(Expr::col((auth_throttles::Entity, auth_throttles::Column::LockedUntil)).lte(now.fixed_offset()))
+                    )
+                    .to_owned()
+            )
+            .exec_with_returning(db)
+            .await;
+
+        // The row exists but is locked, so the upsert left it alone.
+        let failures = match reserved {
+            Ok(throttle) => throttle.failures,
+            Err(DbErr::RecordNotFound(_)) => {
+                let retry_after = self.retry_after(db).await?.unwrap_or_default();
+                return Err(too_many_attempts(retry_after));
+            },
+            Err(e) => return Err(e.into()),
+        };
+
+        let locked_until = if failures >= LOCKOUT_THRESHOLD {
+            Some(now + LOCKOUT_DURATION)
+        } else if failures > FREE_ATTEMPTS {
+            let backoff = Duration::from_secs(1 << (failures - FREE_ATTEMPTS - 1).min(16));
+            Some(now + backoff.min(MAX_BACKOFF))
+        } else {
+            None
+        };
+
+        // An attempt counted in the meantime sets its own, longer lock.
+        auth_throttles::Entity::update_many()
+            .col_expr(auth_throttles::Column::LockedUntil, Expr::value(locked_until.map(|locked_until| locked_until.fixed_offset())))
+            .filter(auth_throttles::Column::Key.eq(key))
+            .filter(auth_throttles::Column::Failures.eq(failures))
+            .exec(db)
+            .await?;
+
+        Ok(failures >= LOCKOUT_THRESHOLD)
+    }
+
+    /// How long the caller has to wait before the next attempt, `None` when it may proceed.
+    async fn retry_after(&self, db: &DatabaseConnection) -> Result<Option<chrono::Duration>, DbErr> {
+        let throttles = auth_throttles::Entity::find()
+            .filter(auth_throttles::Column::Key.is_in([self.account_key(), self.ip_key()]))
+            .all(db)
+            .await?;
+
+        let now = Utc::now();
+        Ok(throttles.iter()
+            .filter_map(|throttle| throttle.locked_until)
+            .filter(|locked_until| *locked_until > now)
+            .max()
+            .map(|locked_until| locked_until.signed_duration_since(now)))
+    }
+}
+
+/// An attempt already counted as a failure, until its outcome says otherwise.
+pub struct ReservedAttempt {
+    attempt: Attempt,
+    account_locks: bool,
+    ip_locks: bool,
+}
+
+impl ReservedAttempt {
+    /// Keeps the failure counted, ties the account's throttle to the user so unlocking finds it, and audits lockouts.
+    pub async fn record_failure(self, db: &DatabaseConnection, user_id: Option<i64>) {
+        let attempt = &self.attempt;
+
+        if user_id.is_some() {
+            let tagged = auth_throttles::Entity::update_many()
+                .col_expr(auth_throttles::Column::UserId, Expr::value(user_id))
+                .filter(auth_throttles::Column::Key.eq(attempt.account_key()))
+                .exec(db)
+                .await;
+            if let Err(e) = tagged {
+                log::error!("Failed to record {} failure for account: {}", attempt.kind.as_str(), e);
+            }
+        }
+
+        if self.account_locks {
+            audit_service::record(db, AuditEvent {
+                event: audit_service::ACCOUNT_LOCKED,
+                user_id,
+                actor_id: None,
+                ip_address: Some(&attempt.ip),
+                details: Some(format!("{} locked for account {}", attempt.kind.as_str(), attempt.account)),
+            }).await;
+        }
+
+        if self.ip_locks {
+            audit_service::record(db, AuditEvent {
+                event: audit_service::IP_LOCKED,
+                user_id: None,
+                actor_id: None,
+                ip_address: Some(&attempt.ip),
+                details: Some(format!("{} locked for client IP", attempt.kind.as_str())),
+            }).await;
+        }
+    }
+
+    /// Clears the account's failures. The client IP only gets this attempt back, so one valid login can't reset it.
+    pub async fn record_success(self, db: &DatabaseConnection) {
+        let attempt = &self.attempt;
+
+        if let Err(e) = auth_throttles::Entity::delete_by_id(attempt.account_key()).exec(db).await {
+            log::error!("Failed to reset {} throttle: {}", attempt.kind.as_str(), e);
+        }
+
+        let released = auth_throttles::Entity::update_many()
+            .col_expr(auth_throttles::Column::Failures, Expr::col(auth_throttles::Column::Failures).sub(1))
+            .filter(auth_throttles::Column::Key.eq(attempt.ip_key()))
+            .filter(auth_throttles::Column::Failures.gt(0))
+            .exec(db)
+            .await;
+        if let Err(e) = released {
+            log::error!("Failed to release {} attempt for client IP: {}", attempt.kind.as_str(), e);
+        }
+    }
+}
+
+/// Removes every throttle held against the account, including the login throttle keyed by its email.
+pub async fn unlock_account(db: &DatabaseConnection, user_id: i64, email: &str) -> Result<u64, DbErr> {
+    let result = auth_throttles::Entity::delete_many()
+        .filter(
+            Condition::any()
+                .add(auth_throttles::Column::UserId.eq(user_id))
+                .add(auth_throttles::Column::Key.eq(Attempt::login(email, "").account_key()))
+                .add(auth_throttles::Column::Key.eq(Attempt::mfa_login(user_id, "").account_key()))
+                .add(auth_throttles::Column::Key.eq(Attempt::group_join(user_id, "").account_key()))
+        )
+        .exec(db)
+        .await?;
+
+    Ok(result.rows_affected)
+}
+
+/// Proxies allowed to report the client address in `X-Forwarded-For`, from the comma separated `TRUSTED_PROXIES`.
+fn trusted_proxies() -> Vec<IpAddr> {
+    std::env::var("TRUSTED_PROXIES").unwrap_or_default()
+        .split(',')
+        .filter_map(|proxy| proxy.trim().parse().ok())
+        .collect()
+}
+
+fn parse_hop(hop: &str) -> Option<IpAddr> {
+    let hop = hop.trim();
+    hop.parse().ok().or_else(|| hop.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+/// Walks the forwarded chain back from the peer while each hop is a trusted proxy, the first untrusted
+/// address is the client. Anything further left was written by the client and could be anything.
+fn resolve_client_ip(peer: IpAddr, forwarded_for: &[IpAddr], trusted_proxies: &[IpAddr]) -> IpAddr {
+    let mut client = peer;
+
+    for hop in forwarded_for.iter().rev() {
+        if !trusted_proxies.contains(&client) {
+            break;
+        }
+        client = *hop;
+    }
+
+    client
+}
+
+/// The address throttles are keyed by. Forwarding headers only count when the peer is a trusted proxy.
+pub fn client_ip(req: &HttpRequest) -> String {
+    let Some(peer) = req.peer_addr().map(|addr| addr.ip()) else {
+        return "unknown".to_string();
+    };
+
+    let hops: Vec<&str> = req.headers().get_all("x-forwarded-for")
+        .flat_map(|header| header.to_str().unwrap_or_default().split(','))
+        .collect();
+    // Only the hops after the last unreadable one can have come from a proxy.
+    let mut forwarded_for: Vec<IpAddr> = hops.into_iter().rev().map_while(parse_hop).collect();
+    forwarded_for.reverse();
+
+    resolve_client_ip(peer, &forwarded_for, &trusted_proxies()).to_string()
+}
+
+/// The same answer for locked accounts and locked client IPs, so lockouts don't reveal which accounts exist.
+fn too_many_attempts(retry_after: chrono::Duration) -> AppError {
+    AppError::TooManyRequests {
+        message: "Too many failed attempts, try again later!".to_string(),
+        retry_after: retry_after.num_seconds().max(1) as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult, Value};
+    use super::*;
+
+    fn throttle(key: &str, failures: i32, locked_until: Option<chrono::DateTime<Utc>>) -> auth_throttles::Model {
+        auth_throttles::Model {
+            key: key.to_string(),
+            user_id: None,
+            failures,
+            last_failure_at: Utc::now().fixed_offset(),
+            locked_until: locked_until.map(|locked_until| locked_until.fixed_offset()),
+        }
+    }
+
+    #[actix_web::test]
+    async fn attempts_are_counted_before_they_are_checked() {
+        let attempt = Attempt::login("Jane@Example.com", "203.0.113.7");
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![throttle(&attempt.ip_key(), 1, None)]])
+            .append_exec_results([MockExecResult { last_insert_id: 0, rows_affected: 1 }])
+            .append_query_results([vec![throttle(&attempt.account_key(), 1, None)]])
+            .append_exec_results([MockExecResult { last_insert_id: 0, rows_affected: 1 }])
+            .into_connection();
+        let db = Arc::new(db);
+
+        attempt.reserve(&db).await.unwrap();
+
+        let log = Arc::try_unwrap(db).unwrap().into_transaction_log();
+        let ip = log[0].statements()[0].to_string();
+        assert!(ip.contains("'login:ip:203.0.113.7'"), "{}", ip);
+        assert!(ip.contains(r#"ON CONFLICT ("key") DO UPDATE SET"#), "{}", ip);
+        assert!(ip.contains(r#""AuthThrottles"."locked_until" IS NULL OR "AuthThrottles"."locked_until" <="#), "{}", ip);
+        assert!(ip.ends_with(r#"RETURNING "key", "user_id", "failures", "last_failure_at", "locked_until""#), "{}", ip);
+        assert!(log[2].statements()[0].to_string().contains("'login:account:jane@example.com'"));
+    }
+
+    #[actix_web::test]
+    async fn locked_clients_are_turned_away_without_counting_against_the_account() {
+        let attempt = Attempt::login("jane@example.com", "203.0.113.7");
+        let locked = throttle(&attempt.ip_key(), LOCKOUT_THRESHOLD, Some(Utc::now() + LOCKOUT_DURATION));
+        // The upsert skips the locked row and returns nothing, the follow-up lookup finds the lock.
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<BTreeMap<&str, Value>>::new()])
+            .append_query_results([vec![locked]])
+            .into_connection();
+        let db = Arc::new(db);
+
+        let result = attempt.reserve(&db).await;
+
+        assert!(matches!(result, Err(AppError::TooManyRequests { retry_after, .. }) if retry_after > 800), "{:?}", result.err());
+        assert_eq!(Arc::try_unwrap(db).unwrap().into_transaction_log().len(), 2);
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn forwarded_for_is_ignored_from_untrusted_peers() {
+        assert_eq!(resolve_client_ip(ip("203.0.113.7"), &[ip("10.0.0.1")], &[]), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn forwarded_for_is_followed_through_trusted_proxies_only() {
+        let trusted = [ip("10.0.0.2"), ip("10.0.0.3")];
+        let chain = [ip("198.51.100.9"), ip("203.0.113.7"), ip("10.0.0.2")];
+
+        assert_eq!(resolve_client_ip(ip("10.0.0.3"), &chain, &trusted), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn forwarded_hops_parse_with_or_without_a_port() {
+        assert_eq!(parse_hop(" 203.0.113.7 "), Some(ip("203.0.113.7")));
+        assert_eq!(parse_hop("203.0.113.7:4711"), Some(ip("203.0.113.7")));
+        assert_eq!(parse_hop("[2001:db8::1]:4711"), Some(ip("2001:db8::1")));
+        assert_eq!(parse_hop("unknown"), None);
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use actix_web::cookie::{Cookie, SameSite};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ed25519_dalek::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+    OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::traits::PublicKeyParts;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use shuttle_runtime::SecretStore;
+use crate::errors::AppError;
+use crate::services::auth_service::{
+    TokenType, UserClaims, ACCESS_TOKEN_COOKIE, ACCESS_TOKEN_LIFETIME, REFRESH_TOKEN_COOKIE, REFRESH_TOKEN_LIFETIME,
+};
+
+/// Registered claims wrapped around the application claims, flattened into one JWT payload.
+#[derive(Serialize, Deserialize, Debug)]
+struct TokenClaims<T> {
+    exp: u64,
+    iat: u64,
+    #[serde(flatten)]
+    custom: T,
+}
+
+/// User claims tagged with what the token may be used for.
+#[derive(Serialize, Deserialize, Debug)]
+struct UserToken<T> {
+    typ: TokenType,
+    #[serde(flatten)]
+    claims: T,
+}
+
+/// A public key accepted during verification, together with its JWKS representation.
+struct VerificationKey {
+    algorithm: Algorithm,
+    decoding_key: DecodingKey,
+    jwk: Jwk,
+}
+
+/// An additional verification key as configured in `JWT_VERIFICATION_KEYS`.
+#[derive(Deserialize, Debug)]
+struct ConfiguredKey {
+    kid: String,
+    alg: String,
+    public_key: String,
+}
+
+/// Signs LockBox tokens with the active private key and verifies them against every key
+/// still in rotation, selected by the `kid` header.
+pub struct TokenService {
+    kid: String,
+    algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    verification_keys: HashMap<String, VerificationKey>,
+}
+
+impl TokenService {
+    /// Builds the service from `JWT_ALGORITHM` (`EdDSA` or `RS256`), `JWT_PRIVATE_KEY` (PEM),
+    /// `JWT_KEY_ID` and the optional JSON array `JWT_VERIFICATION_KEYS` of retired public keys.
+    pub fn from_secrets(secrets: &SecretStore) -> TokenService {
+        let algorithm = parse_algorithm(&secrets.get("JWT_ALGORITHM").unwrap_or("EdDSA".to_string()))
+            .expect("JWT_ALGORITHM must be either EdDSA or RS256");
+        let kid = secrets.get("JWT_KEY_ID").expect("JWT_KEY_ID is not set");
+        let private_key = secrets.get("JWT_PRIVATE_KEY").expect("JWT_PRIVATE_KEY is not set");
+
+        let configured_keys: Vec<ConfiguredKey> = secrets.get("JWT_VERIFICATION_KEYS")
+            .map(|keys| serde_json::from_str(&keys).expect("JWT_VERIFICATION_KEYS must be a JSON array"))
+            .unwrap_or_default();
+
+        let mut service = TokenService::new(&kid, algorithm, &private_key)
+            .expect("Failed to load JWT_PRIVATE_KEY");
+
+        for key in configured_keys {
+            let algorithm = parse_algorithm(&key.alg)
+                .unwrap_or_else(|| panic!("Unsupported algorithm for verification key {}", key.kid));
+            service.add_verification_key(&key.kid, algorithm, &key.public_key)
+                .unwrap_or_else(|_| panic!("Failed to load verification key {}", key.kid));
+        }
+
+        service
+    }
+
+    pub fn new(kid: &str, algorithm: Algorithm, private_key_pem: &str) -> Result<TokenService, String> {
+        let (encoding_key, jwk) = match algorithm {
+            Algorithm::EdDSA => {
+                let signing_key = ed25519_dalek::SigningKey::from_pkcs8_pem(private_key_pem)
+                    .map_err(|e| e.to_string())?;
+                let encoding_key = EncodingKey::from_ed_pem(private_key_pem.as_bytes())
+                    .map_err(|e| e.to_string())?;
+                (encoding_key, ed25519_jwk(kid, &signing_key.verifying_key()))
+            },
+            Algorithm::RS256 => {
+                let private_key = rsa::RsaPrivateKey::from_pkcs8_pem(private_key_pem)
+                    .or_else(|_| rsa::RsaPrivateKey::from_pkcs1_pem(private_key_pem))
+                    .map_err(|e| e.to_string())?;
+                let encoding_key = EncodingKey::from_rsa_pem(private_key_pem.as_bytes())
+                    .map_err(|e| e.to_string())?;
+                (encoding_key, rsa_jwk(kid, &private_key.to_public_key()))
+            },
+            _ => return Err(format!("Unsupported signing algorithm {:?}", algorithm)),
+        };
+
+        let mut service = TokenService {
+            kid: kid.to_string(),
+            algorithm,
+            encoding_key,
+            verification_keys: HashMap::new(),
+        };
+        service.insert_jwk(kid, algorithm, jwk)?;
+
+        Ok(service)
+    }
+
+    pub fn add_verification_key(&mut self, kid: &str, algorithm: Algorithm, public_key_pem: &str) -> Result<(), String> {
+        let jwk = match algorithm {
+            Algorithm::EdDSA => {
+                let public_key = ed25519_dalek::VerifyingKey::from_public_key_pem(public_key_pem)
+                    .map_err(|e| e.to_string())?;
+                ed25519_jwk(kid, &public_key)
+            },
+            Algorithm::RS256 => {
+                let public_key = rsa::RsaPublicKey::from_public_key_pem(public_key_pem)
+                    .map_err(|e| e.to_string())?;
+                rsa_jwk(kid, &public_key)
+            },
+            _ => return Err(format!("Unsupported verification algorithm {:?}", algorithm)),
+        };
+
+        self.insert_jwk(kid, algorithm, jwk)
+    }
+
+    fn insert_jwk(&mut self, kid: &str, algorithm: Algorithm, jwk: Jwk) -> Result<(), String> {
+        let decoding_key = DecodingKey::from_jwk(&jwk).map_err(|e| e.to_string())?;
+        self.verification_keys.insert(kid.to_string(), VerificationKey { algorithm, decoding_key, jwk });
+        Ok(())
+    }
+
+    pub fn create_signed_token<T: Serialize>(&self, claims: &T, lifetime: Duration) -> Result<String, AppError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| AppError::internal("system clock is before the UNIX epoch"))?;
+
+        let token_claims = TokenClaims {
+            exp: (now + lifetime).as_secs(),
+            iat: now.as_secs(),
+            custom: claims,
+        };
+
+        let mut header = Header::new(self.algorithm);
+        header.kid = Some(self.kid.clone());
+
+        encode(&header, &token_claims, &self.encoding_key)
+            .map_err(|e| AppError::internal(format!("failed to sign token: {}", e)))
+    }
+
+    pub fn verify<T: DeserializeOwned>(&self, token: &str) -> Result<T, AppError> {
+        let header = decode_header(token)
+            .map_err(|_| AppError::unauthorized("Invalid or expired access token!"))?;
+
+        let key = header.kid.as_ref()
+            .and_then(|kid| self.verification_keys.get(kid))
+            .ok_or(AppError::unauthorized("Invalid or expired access token!"))?;
+
+        decode::<TokenClaims<T>>(token, &key.decoding_key, &Validation::new(key.algorithm))
+            .map(|token_data| token_data.claims.custom)
+            .map_err(|_| AppError::unauthorized("Invalid or expired access token!"))
+    }
+
+    pub fn create_user_token(&self, claims: &UserClaims, typ: TokenType) -> Result<String, AppError> {
+        let lifetime = match typ {
+            TokenType::Access => ACCESS_TOKEN_LIFETIME,
+            TokenType::Refresh => REFRESH_TOKEN_LIFETIME,
+        };
+
+        self.create_signed_token(&UserToken { typ, claims }, lifetime)
+    }
+
+    /// Verifies a user token and that it was issued as `typ`.
+    pub fn verify_user_token(&self, token: &str, typ: TokenType) -> Result<UserClaims, AppError> {
+        let token = self.verify::<UserToken<UserClaims>>(token)?;
+
+        match token.typ == typ {
+            true => Ok(token.claims),
+            false => Err(AppError::unauthorized("Invalid or expired access token!")),
+        }
+    }
+
+    pub fn create_access_cookie(&self, claims: &UserClaims) -> Result<Cookie<'static>, AppError> {
+        self.create_cookie(claims, ACCESS_TOKEN_COOKIE, TokenType::Access, SameSite::Lax)
+    }
+
+    pub fn create_refresh_cookie(&self, claims: &UserClaims) -> Result<Cookie<'static>, AppError> {
+        // Only ever sent to `/refresh` by the app itself, never on a navigation from another site.
+        self.create_cookie(claims, REFRESH_TOKEN_COOKIE, TokenType::Refresh, SameSite::Strict)
+    }
+
+    fn create_cookie(&self, claims: &UserClaims, name: &str, typ: TokenType, same_site: SameSite) -> Result<Cookie<'static>, AppError> {
+        let token = self.create_user_token(claims, typ)?;
+
+        Ok(Cookie::build(name.to_string(), token)
+            .secure(true)
+            .http_only(true)
+            .same_site(same_site)
+            .path("/")
+            .finish())
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        let mut keys: Vec<Jwk> = self.verification_keys.values()
+            .map(|key| key.jwk.clone())
+            .collect();
+        keys.sort_by(|a, b| a.common.key_id.cmp(&b.common.key_id));
+
+        JwkSet { keys }
+    }
+}
+
+fn parse_algorithm(algorithm: &str) -> Option<Algorithm> {
+    match algorithm {
+        "EdDSA" | "Ed25519" => Some(Algorithm::EdDSA),
+        "RS256" => Some(Algorithm::RS256),
+        _ => None,
+    }
+}
+
+fn common_parameters(kid: &str, algorithm: KeyAlgorithm) -> CommonParameters {
+    CommonParameters {
+        public_key_use: Some(PublicKeyUse::Signature),
+        key_algorithm: Some(algorithm),
+        key_id: Some(kid.to_string()),
+        ..Default::default()
+    }
+}
+
+fn ed25519_jwk(kid: &str, public_key: &ed25519_dalek::VerifyingKey) -> Jwk {
+    Jwk {
+        common: common_parameters(kid, KeyAlgorithm::EdDSA),
+        algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+            key_type: OctetKeyPairType::OctetKeyPair,
+            curve: EllipticCurve::Ed25519,
+            x: URL_SAFE_NO_PAD.encode(public_key.as_bytes()),
+        }),
+    }
+}
+
+fn rsa_jwk(kid: &str, public_key: &rsa::RsaPublicKey) -> Jwk {
+    Jwk {
+        common: common_parameters(kid, KeyAlgorithm::RS256),
+        algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+            key_type: RSAKeyType::RSA,
+            n: URL_SAFE_NO_PAD.encode(public_key.n().to_bytes_be()),
+            e: URL_SAFE_NO_PAD.encode(public_key.e().to_bytes_be()),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::pkcs8::EncodePrivateKey;
+    use ed25519_dalek::pkcs8::spki::der::pem::LineEnding;
+    use crate::services::auth_service::Role;
+    use super::*;
+
+    fn token_service() -> TokenService {
+        let pem = ed25519_dalek::SigningKey::from_bytes(&[1; 32]).to_pkcs8_pem(LineEnding::LF).unwrap();
+        TokenService::new("test-key", Algorithm::EdDSA, &pem).unwrap()
+    }
+
+    fn claims() -> UserClaims {
+        UserClaims {
+            id: 1,
+            role: Role::RegisteredUser,
+            session_version: 0,
+            scopes: None,
+        }
+    }
+
+    #[test]
+    fn user_tokens_only_verify_as_their_type() {
+        let service = token_service();
+        let access_token = service.create_user_token(&claims(), TokenType::Access).unwrap();
+        let refresh_token = service.create_user_token(&claims(), TokenType::Refresh).unwrap();
+
+        assert_eq!(service.verify_user_token(&access_token, TokenType::Access).unwrap(), claims());
+        assert_eq!(service.verify_user_token(&refresh_token, TokenType::Refresh).unwrap(), claims());
+        assert!(service.verify_user_token(&refresh_token, TokenType::Access).is_err());
+        assert!(service.verify_user_token(&access_token, TokenType::Refresh).is_err());
+    }
+
+    #[test]
+    fn untyped_user_claims_are_rejected() {
+        let service = token_service();
+        let token = service.create_signed_token(&claims(), ACCESS_TOKEN_LIFETIME).unwrap();
+
+        assert!(service.verify_user_token(&token, TokenType::Access).is_err());
+    }
+
+    #[test]
+    fn session_cookies_are_hidden_from_scripts_and_other_sites() {
+        let service = token_service();
+        let access = service.create_access_cookie(&claims()).unwrap();
+        let refresh = service.create_refresh_cookie(&claims()).unwrap();
+
+        for cookie in [&access, &refresh] {
+            assert_eq!(cookie.http_only(), Some(true));
+            assert_eq!(cookie.secure(), Some(true));
+        }
+        assert_eq!(access.same_site(), Some(SameSite::Lax));
+        assert_eq!(refresh.same_site(), Some(SameSite::Strict));
+    }
+}
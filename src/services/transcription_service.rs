@@ -0,0 +1,181 @@
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use serde::Deserialize;
+use shuttle_runtime::SecretStore;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+
+/// A stretch of speech and when it's said, in seconds from the start of the video.
+#[derive(Debug, Clone)]
+pub struct TranscriptSegment {
+    pub start_seconds: f64,
+    pub end_seconds: f64,
+    pub text: String,
+}
+
+#[derive(Debug)]
+pub struct TranscriptionError {
+    message: String,
+}
+
+impl TranscriptionError {
+    fn new(message: impl Into<String>) -> TranscriptionError {
+        TranscriptionError {
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for TranscriptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+pub type TranscriptionFuture<'a> = Pin<Box<dyn Future<Output=Result<Vec<TranscriptSegment>, TranscriptionError>> + Send + 'a>>;
+
+pub trait TranscriptionEngine: Send + Sync {
+    /// Stored with each job, so a transcript can be traced back to what produced it.
+    fn name(&self) -> &'static str;
+
+    /// Turns the speech in a local media file into segments ordered by time.
+    fn transcribe<'a>(&'a self, media: &'a Path) -> TranscriptionFuture<'a>;
+}
+
+#[derive(Deserialize)]
+struct WhisperOutput {
+    transcription: Vec<WhisperSegment>,
+}
+
+#[derive(Deserialize)]
+struct WhisperSegment {
+    offsets: WhisperOffsets,
+    text: String,
+}
+
+/// Milliseconds from the start.
+#[derive(Deserialize)]
+struct WhisperOffsets {
+    from: u64,
+    to: u64,
+}
+
+/// Runs a local whisper.cpp `whisper-cli` style binary, with ffmpeg extracting the 16 kHz mono audio it expects.
+pub struct WhisperCppEngine {
+    binary: PathBuf,
+    model: PathBuf,
+    ffmpeg: PathBuf,
+    language: String,
+    // whisper already keeps every core busy, running two at once only makes both slower.
+    permits: Semaphore,
+}
+
+impl WhisperCppEngine {
+    pub fn new(binary: impl Into<PathBuf>, model: impl Into<PathBuf>, ffmpeg: impl Into<PathBuf>, language: String) -> WhisperCppEngine {
+        WhisperCppEngine {
+            binary: binary.into(),
+            model: model.into(),
+            ffmpeg: ffmpeg.into(),
+            language,
+            permits: Semaphore::new(1),
+        }
+    }
+}
+
+/// Runs a command to completion, failing with the end of its stderr.
+async fn run(command: &mut Command, program: &str) -> Result<(), TranscriptionError> {
+    let output = command.kill_on_drop(true).output().await
+        .map_err(|e| TranscriptionError::new(format!("failed to start {}: {}", program, e)))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let tail: Vec<&str> = stderr.lines().rev().take(5).collect();
+        let tail: Vec<&str> = tail.into_iter().rev().collect();
+        return Err(TranscriptionError::new(format!("{} exited with {}: {}", program, output.status, tail.join(" | "))));
+    }
+
+    Ok(())
+}
+
+impl TranscriptionEngine for WhisperCppEngine {
+    fn name(&self) -> &'static str {
+        "whisper.cpp"
+    }
+
+    fn transcribe<'a>(&'a self, media: &'a Path) -> TranscriptionFuture<'a> {
+        Box::pin(async move {
+            let _permit = self.permits.acquire().await
+                .map_err(|e| TranscriptionError::new(e.to_string()))?;
+
+            let workdir = tempfile::tempdir()
+                .map_err(|e| TranscriptionError::new(format!("failed to create a working directory: {}", e)))?;
+            let audio = workdir.path().join("audio.wav");
+            let output = workdir.path().join("transcript");
+
+            run(
+                Command::new(&self.ffmpeg)
+                    .args(["-nostdin", "-loglevel", "error", "-y", "-i"])
+                    .arg(media)
+                    .args(["-vn", "-ar", "16000", "-ac", "1", "-c:a", "pcm_s16le"])
+                    .arg(&audio),
+                "ffmpeg",
+            ).await?;
+
+            run(
+                Command::new(&self.binary)
+                    .arg("-m").arg(&self.model)
+                    .arg("-f").arg(&audio)
+                    .arg("-l").arg(&self.language)
+                    .arg("-of").arg(&output)
+                    .args(["-oj", "-np"]),
+                "whisper",
+            ).await?;
+
+            let json = tokio::fs::read(output.with_extension("json")).await
+                .map_err(|e| TranscriptionError::new(format!("whisper wrote no transcript: {}", e)))?;
+            let transcript: WhisperOutput = serde_json::from_slice(&json)
+                .map_err(|e| TranscriptionError::new(format!("unreadable whisper transcript: {}", e)))?;
+
+            Ok(transcript.transcription.into_iter()
+                .map(|segment| TranscriptSegment {
+                    start_seconds: segment.offsets.from as f64 / 1000.0,
+                    end_seconds: segment.offsets.to as f64 / 1000.0,
+                    text: segment.text.trim().to_string(),
+                })
+                .filter(|segment| !segment.text.is_empty())
+                .collect())
+        })
+    }
+}
+
+/// Transcribes nothing, for deployments without an engine and for tests. Jobs still run and finish empty.
+pub struct NoopEngine;
+
+impl TranscriptionEngine for NoopEngine {
+    fn name(&self) -> &'static str {
+        "noop"
+    }
+
+    fn transcribe<'a>(&'a self, media: &'a Path) -> TranscriptionFuture<'a> {
+        Box::pin(async move {
+            log::info!("Skipping transcription of {}, no engine is configured", media.display());
+            Ok(Vec::new())
+        })
+    }
+}
+
+/// Picks the engine from `TRANSCRIPTION_ENGINE`: `whisper` uses the `WHISPER_*` secrets, anything else transcribes nothing.
+pub fn create_engine(secrets: &SecretStore) -> Box<dyn TranscriptionEngine> {
+    match secrets.get("TRANSCRIPTION_ENGINE").as_deref() {
+        Some("whisper") => {
+            let binary = secrets.get("WHISPER_BINARY").unwrap_or("whisper-cli".to_string());
+            let model = secrets.get("WHISPER_MODEL").expect("WHISPER_MODEL is not set");
+            let ffmpeg = secrets.get("FFMPEG_BINARY").unwrap_or("ffmpeg".to_string());
+            let language = secrets.get("WHISPER_LANGUAGE").unwrap_or("auto".to_string());
+
+            Box::new(WhisperCppEngine::new(binary, model, ffmpeg, language))
+        },
+        _ => Box::new(NoopEngine),
+    }
+}
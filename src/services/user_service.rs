@@ -1,101 +1,217 @@
-use actix_jwt_auth_middleware::{ AuthResult, TokenSigner};
-use actix_web::{web, HttpResponse};
-use jwt_compact::alg::Hs256;
-use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, QueryFilter, ColumnTrait, IntoActiveModel};
+use actix_web::cookie::Cookie;
+use actix_web::web;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, QueryFilter, ColumnTrait, Condition, IntoActiveModel, IntoSimpleExpr, QueryOrder, SqlErr};
 use sea_orm::ActiveValue::Set;
+use serde::{Deserialize, Serialize};
 use crate::dtos::group_dto::JoinGroup;
-use crate::dtos::user_dto::{UserLogin, UserRegister, UserResponse};
+use crate::dtos::pagination_dto::{ListQuery, Page, SortOrder};
+use crate::dtos::user_dto::{AdminUserResponse, MfaChallengeResponse, TokenResponse, UserFilter, UserLogin, UserRegister, UserResponse};
 use crate::entities::{groups, users};
-use crate::services::auth_service::{Role, UserClaims};
-use crate::services::hash_service;
+use crate::errors::AppError;
+use crate::services::auth_service::{Role, TokenType, UserClaims, ACCESS_TOKEN_LIFETIME};
+use crate::services::{admin_service, group_service, hash_service, mfa_service, pagination_service};
+use crate::services::group_service::{GroupRole, GroupVisibility};
+use crate::services::mail_service::Mailer;
+use crate::services::throttle_service::Attempt;
+use crate::services::token_service::TokenService;
+use crate::services::verification_service;
 use crate::entities::group_user;
 use crate::services::hash_service::verify_password;
 
 pub async fn create_user(
     db: web::Data<DatabaseConnection>,
+    token_service: web::Data<TokenService>,
+    mailer: web::Data<dyn Mailer>,
     new_user: web::Json<UserRegister>
-) -> HttpResponse {
+) -> Result<(), AppError> {
     let db = db.get_ref();
     let user = users::ActiveModel {
         username: Set(Some(new_user.username.clone())),
         email: Set(new_user.email.clone()),
         password: Set(Some(new_user.password.clone())),
+        email_verified: Set(false),
         ..Default::default()
     };
-    
-    match user.insert(db).await {
-        Ok(_) => HttpResponse::Ok().finish(),
-        Err(_) => HttpResponse::InternalServerError().finish(),
-    }
+
+    let user = user.insert(db).await.map_err(|e| AppError::unique(e, &[
+        ("username", "This username is already taken."),
+        ("email", "An account with this email address already exists."),
+    ]))?;
+
+    // The account exists at this point, a failed delivery can be retried through the resend endpoint.
+    let _ = verification_service::send_verification_email(db, &token_service, mailer.get_ref(), &user).await;
+
+    Ok(())
 }
 
-pub async fn get_users(db: web::Data<DatabaseConnection>) -> HttpResponse {
-    let db = db.get_ref();
-    match users::Entity::find().all(db).await {
-        Ok(users) => HttpResponse::Ok().json(users),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+pub async fn get_users(
+    db: web::Data<DatabaseConnection>,
+    filter: web::Query<UserFilter>,
+    query: web::Query<ListQuery>,
+) -> Result<Page<AdminUserResponse>, AppError> {
+    let mut select = users::Entity::find();
+
+    if let Some(q) = &filter.q {
+        select = select.filter(
+            Condition::any()
+                .add(pagination_service::search(users::Column::Username, q))
+                .add(pagination_service::search(users::Column::Email, q))
+        );
+    }
+    if let Some(deleted) = filter.deleted {
+        select = select.filter(users::Column::IsDeleted.eq(deleted));
     }
+
+    let select = pagination_service::sort(select, &query, &[
+        ("id", users::Column::Id.into_simple_expr()),
+        ("username", users::Column::Username.into_simple_expr()),
+        ("email", users::Column::Email.into_simple_expr()),
+        ("created_at", users::Column::CreatedAt.into_simple_expr()),
+    ], ("id", SortOrder::Asc))?;
+
+    let users = pagination_service::paginate(select.order_by_asc(users::Column::Id), db.get_ref(), &query).await?;
+
+    Ok(users.map(|user| AdminUserResponse {
+        id: user.id,
+        username: user.username,
+        email: user.email,
+        email_verified: user.email_verified,
+        totp_enabled: user.totp_enabled,
+        is_deleted: user.is_deleted,
+        created_at: user.created_at.to_rfc3339(),
+    }))
 }
 
-pub async fn get_user(db: web::Data<DatabaseConnection>, user_id: i64) -> HttpResponse {
-    let db = db.get_ref();
+pub async fn get_user(db: web::Data<DatabaseConnection>, user_id: i64) -> Result<UserResponse, AppError> {
+    let user = users::Entity::find()
+        .filter(users::Column::Id.eq(user_id))
+        .one(db.get_ref())
+        .await?
+        .ok_or(AppError::not_found("User not found!"))?;
 
-    match users::Entity::find().filter(users::Column::Id.eq(user_id)).one(db).await {
-        Ok(Some(user)) => {
-            let response = UserResponse {
-                username: user.username.unwrap_or_default(),
-                email: user.email,
-            };
-            HttpResponse::Ok().json(response)
-        },
-        Ok(None) => HttpResponse::Ok().body(user_id.to_string()),
-        Err(_) => HttpResponse::InternalServerError().finish(),
-    }
+    Ok(UserResponse {
+        username: user.username.unwrap_or_default(),
+        email: user.email,
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum TokenDelivery {
+    Cookie,
+    Body,
+}
+
+/// Tokens of a finished login, in the form the client asked for.
+pub enum IssuedTokens {
+    Cookies(Vec<Cookie<'static>>),
+    Body(TokenResponse),
+}
+
+pub enum LoginOutcome {
+    MfaRequired(MfaChallengeResponse),
+    Authenticated(IssuedTokens),
 }
 
 pub async fn login(
     db: web::Data<DatabaseConnection>,
     user_login: web::Json<UserLogin>,
-    token_signer: web::Data<TokenSigner<UserClaims, Hs256>>,
-    user_role: Role
-) -> AuthResult<HttpResponse> {
+    token_service: web::Data<TokenService>,
+    user_role: Role,
+    delivery: TokenDelivery,
+    client_ip: &str,
+) -> Result<LoginOutcome, AppError> {
     let db = db.get_ref();
+    let attempt = Attempt::login(&user_login.email, client_ip).reserve(db).await?;
 
-    let user_result = users::Entity::find()
+    let user = users::Entity::find()
         .filter(users::Column::Email.eq(user_login.email.clone()))
         .one(db)
-        .await;
-
-    match user_result {
-        Ok(Some(user)) => {
-            if user.is_deleted { return Ok(HttpResponse::Unauthorized().finish()); }
-            
-            let user_claim: UserClaims = UserClaims {
-                id: user.id,
-                role: user_role,
-            };
-            
-            match hash_service::verify_password(&user_login.password, &user.password.unwrap_or_default()).await {
-                Ok(true) => {
-                    let mut access_token = token_signer.create_access_cookie(&user_claim)?;
-                    let mut refresh_token = token_signer.create_refresh_cookie(&user_claim)?;
-
-                    access_token.set_path("/");
-                    refresh_token.set_path("/");
-
-                    Ok(HttpResponse::Ok()
-                        .cookie(access_token)
-                        .cookie(refresh_token)
-                        .finish()
-                    )
-                },
-                Err(_) => Ok(HttpResponse::Unauthorized().finish()),
-                _ => Ok(HttpResponse::Unauthorized().finish()),
-            }
+        .await?;
+
+    let user = match user {
+        Some(user) if !user.is_deleted => user,
+        _ => {
+            attempt.record_failure(db, None).await;
+            return Err(AppError::unauthorized("Invalid email or password!"));
         },
-        Ok(None) => Ok(HttpResponse::Unauthorized().finish()),
-        Err(_) => Ok(HttpResponse::Unauthorized().finish()),
+    };
+
+    if !hash_service::verify_password(&user_login.password, &user.password.clone().unwrap_or_default()).await.unwrap_or(false) {
+        attempt.record_failure(db, Some(user.id)).await;
+        return Err(AppError::unauthorized("Invalid email or password!"));
+    }
+
+    attempt.record_success(db).await;
+
+    if !user.email_verified {
+        return Err(AppError::forbidden("Email address has not been verified!"));
+    }
+
+    if user_role == Role::Admin && !user.is_admin {
+        return Err(AppError::forbidden("Requires Administrator privileges!"));
+    }
+
+    if user.totp_enabled {
+        return Ok(LoginOutcome::MfaRequired(mfa_service::mfa_challenge(&token_service, &user, user_role, delivery)?));
     }
+
+    if user_role == Role::Admin && admin_service::require_admin_mfa(db).await? {
+        return Err(AppError::forbidden("Administrator accounts must enable two-factor authentication!"));
+    }
+
+    let user_claim: UserClaims = UserClaims {
+        id: user.id,
+        role: user_role,
+        session_version: user.session_version,
+        scopes: None,
+    };
+
+    Ok(LoginOutcome::Authenticated(issue_tokens(&token_service, &user_claim, delivery)?))
+}
+
+pub fn issue_tokens(
+    token_service: &TokenService,
+    user_claim: &UserClaims,
+    delivery: TokenDelivery,
+) -> Result<IssuedTokens, AppError> {
+    match delivery {
+        TokenDelivery::Cookie => Ok(IssuedTokens::Cookies(vec![
+            token_service.create_access_cookie(user_claim)?,
+            token_service.create_refresh_cookie(user_claim)?,
+        ])),
+        TokenDelivery::Body => Ok(IssuedTokens::Body(TokenResponse {
+            access_token: token_service.create_user_token(user_claim, TokenType::Access)?,
+            refresh_token: token_service.create_user_token(user_claim, TokenType::Refresh)?,
+            token_type: "Bearer".to_string(),
+            expires_in: ACCESS_TOKEN_LIFETIME.as_secs(),
+        })),
+    }
+}
+
+/// Swaps a refresh token for a new pair, unless the account was deleted or its sessions revoked since.
+pub async fn refresh_tokens(
+    db: web::Data<DatabaseConnection>,
+    token_service: web::Data<TokenService>,
+    refresh_token: &str,
+    delivery: TokenDelivery,
+) -> Result<IssuedTokens, AppError> {
+    let claims = token_service.verify_user_token(refresh_token, TokenType::Refresh)
+        .map_err(|_| AppError::unauthorized("Invalid or expired refresh token!"))?;
+
+    let user = users::Entity::find_by_id(claims.id).one(db.get_ref()).await?;
+    let user = match user {
+        Some(user) if !user.is_deleted && user.session_version == claims.session_version => user,
+        _ => return Err(AppError::unauthorized("Invalid or expired refresh token!")),
+    };
+
+    let user_claim = UserClaims {
+        id: user.id,
+        role: claims.role,
+        session_version: user.session_version,
+        scopes: None,
+    };
+
+    issue_tokens(&token_service, &user_claim, delivery)
 }
 
 pub enum UserOperation {
@@ -107,29 +223,23 @@ pub async fn modify_user_state(
     db: web::Data<DatabaseConnection>,
     user_id: web::Path<i64>,
     operation: UserOperation
-) -> HttpResponse {
+) -> Result<(), AppError> {
     let db = db.get_ref();
 
-    let result = users::Entity::find()
+    let user = users::Entity::find()
         .filter(users::Column::Id.eq(user_id.into_inner()))
         .one(db)
-        .await;
-
-    match result {
-        Ok(Some(user)) => {
-            let mut user = user.into_active_model();
-            match operation {
-                UserOperation::Delete => { user.is_deleted = Set(true); }
-                UserOperation::Restore => { user.is_deleted = Set(false); }
-            }
-            match user.update(db).await {
-                Ok(_) => HttpResponse::Ok().finish(),
-                Err(_) => HttpResponse::InternalServerError().finish(),
-            }
-        },
-        Ok(None) => HttpResponse::NotFound().finish(),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+        .await?
+        .ok_or(AppError::not_found("User not found!"))?;
+
+    let mut user = user.into_active_model();
+    match operation {
+        UserOperation::Delete => { user.is_deleted = Set(true); }
+        UserOperation::Restore => { user.is_deleted = Set(false); }
     }
+    user.update(db).await?;
+
+    Ok(())
 }
 
 pub async fn join_group(
@@ -137,34 +247,134 @@ pub async fn join_group(
     group_id: web::Path<i64>,
     join_group: web::Json<JoinGroup>,
     user_claims: UserClaims,
-) -> HttpResponse {
+    client_ip: &str,
+) -> Result<(), AppError> {
     let db = db.get_ref();
     let group_id = group_id.into_inner();
 
     let group = groups::Entity::find()
         .filter(groups::Column::Id.eq(group_id))
+        .filter(groups::Column::IsDeleted.eq(false))
         .one(db)
-        .await;
+        .await?
+        .ok_or(AppError::not_found("Group not found!"))?;
 
-    match group {
-        Ok(Some(group)) => {
-            match verify_password(&join_group.password, &group.password.unwrap_or_default()).await {
-                Ok(true) => (),
-                _ => return HttpResponse::Forbidden().finish(),
+    // Joining a group you're already in is a no-op rather than an error.
+    if group_service::find_membership(db, group_id, user_claims.id).await?.is_some() {
+        return Ok(());
+    }
+
+    match GroupVisibility::from_db(&group.visibility) {
+        GroupVisibility::Public => {},
+        GroupVisibility::Password => {
+            let password = join_group.password.as_deref().unwrap_or_default();
+            let attempt = Attempt::group_join(user_claims.id, client_ip).reserve(db).await?;
+
+            if !verify_password(password, &group.password.unwrap_or_default()).await.unwrap_or(false) {
+                attempt.record_failure(db, Some(user_claims.id)).await;
+                return Err(AppError::forbidden("Invalid group password!"));
             }
-        }
-        Ok(None) => return HttpResponse::NotFound().finish(),
-        Err(_) => return HttpResponse::InternalServerError().finish(),
+
+            attempt.record_success(db).await;
+        },
+        GroupVisibility::InviteOnly => {
+            return Err(AppError::forbidden("This group is invite-only, ask for an invite or request to join!"));
+        },
+        // Hidden groups don't exist for anyone outside of them.
+        GroupVisibility::Hidden => {
+            return Err(AppError::not_found("Group not found!"));
+        },
     }
 
     let entity = group_user::ActiveModel {
         group_id: Set(group_id),
         user_id: Set(user_claims.id),
+        role: Set(GroupRole::Member.as_str().to_string()),
         ..Default::default()
     };
 
     match entity.insert(db).await {
-        Ok(_) => HttpResponse::Ok().finish(),
-        Err(_) => HttpResponse::InternalServerError().body("Failed to join group"),
+        Ok(_) => Ok(()),
+        Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => Ok(()),
+        Err(e) => Err(e.into()),
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use ed25519_dalek::pkcs8::EncodePrivateKey;
+    use ed25519_dalek::pkcs8::spki::der::pem::LineEnding;
+    use jsonwebtoken::Algorithm;
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult};
+    use crate::entities::{auth_throttles, settings};
+    use super::*;
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    fn token_service() -> TokenService {
+        let pem = ed25519_dalek::SigningKey::from_bytes(&[3; 32])
+            .to_pkcs8_pem(LineEnding::LF)
+            .unwrap();
+        TokenService::new("login-test", Algorithm::EdDSA, &pem).unwrap()
+    }
+
+    fn throttle() -> auth_throttles::Model {
+        auth_throttles::Model { key: String::new(), user_id: None, failures: 1, last_failure_at: Utc::now().fixed_offset(), locked_until: None }
+    }
+
+    async fn user(is_admin: bool) -> users::Model {
+        hash_service::init().await;
+        users::Model {
+            id: 42,
+            username: Some("jane".to_string()),
+            email: "jane@example.com".to_string(),
+            password: Some(hash_service::hash_password(PASSWORD).await.unwrap()),
+            created_at: Utc::now().fixed_offset(),
+            is_deleted: false,
+            email_verified: true,
+            session_version: 0,
+            totp_secret: None,
+            totp_enabled: false,
+            totp_last_step: None,
+            is_admin,
+        }
+    }
+
+    /// Answers the throttle reservation, the account lookup and the throttle reset of a correct password.
+    async fn db(is_admin: bool) -> MockDatabase {
+        let exec = || MockExecResult { last_insert_id: 0, rows_affected: 1 };
+        MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![throttle()]])
+            .append_exec_results([exec()])
+            .append_query_results([vec![throttle()]])
+            .append_exec_results([exec()])
+            .append_query_results([vec![user(is_admin).await]])
+            .append_exec_results([exec(), exec()])
+    }
+
+    async fn admin_login(db: MockDatabase) -> Result<LoginOutcome, AppError> {
+        let form = web::Json(UserLogin { email: "jane@example.com".to_string(), password: PASSWORD.to_string() });
+        login(web::Data::new(db.into_connection()), form, web::Data::new(token_service()), Role::Admin, TokenDelivery::Body, "203.0.113.7").await
+    }
+
+    #[actix_web::test]
+    async fn admin_login_needs_an_administrator_account() {
+        let result = admin_login(db(false).await).await;
+
+        assert!(matches!(result, Err(AppError::Forbidden(_))), "{:?}", result.err());
+    }
+
+    #[actix_web::test]
+    async fn administrators_can_sign_in_as_admin() {
+        let db = db(true).await.append_query_results([Vec::<settings::Model>::new()]);
+
+        let result = admin_login(db).await;
+
+        let Ok(LoginOutcome::Authenticated(IssuedTokens::Body(tokens))) = result else {
+            panic!("expected tokens, got {:?}", result.err());
+        };
+        let claims = token_service().verify_user_token(&tokens.access_token, TokenType::Access).unwrap();
+        assert_eq!(claims.role, Role::Admin);
+    }
+}
@@ -0,0 +1,149 @@
+use std::time::Duration;
+use actix_web::web;
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QueryFilter, QueryOrder};
+use sea_orm::ActiveValue::Set;
+use serde::{Deserialize, Serialize};
+use crate::dtos::user_dto::{ResendVerification, VerifyEmail};
+use crate::entities::{email_verifications, users};
+use crate::errors::AppError;
+use crate::services::mail_service::{Email, Mailer};
+use crate::services::token_service::TokenService;
+
+const EMAIL_VERIFICATION_PURPOSE: &str = "email_verification";
+const VERIFICATION_TOKEN_LIFETIME: Duration = Duration::from_secs(3600 * 24);
+const RESEND_INTERVAL: Duration = Duration::from_secs(60);
+const RESEND_WINDOW: Duration = Duration::from_secs(3600);
+const MAX_SENDS_PER_WINDOW: usize = 5;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct EmailVerificationClaims {
+    sub: i64,
+    jti: String,
+    purpose: String,
+}
+
+pub async fn send_verification_email(
+    db: &DatabaseConnection,
+    token_service: &TokenService,
+    mailer: &dyn Mailer,
+    user: &users::Model,
+) -> Result<(), AppError> {
+    let token_id = nanoid::nanoid!(21);
+    let now = Utc::now();
+
+    let verification = email_verifications::ActiveModel {
+        user_id: Set(user.id),
+        token_id: Set(token_id.clone()),
+        created_at: Set(now.fixed_offset()),
+        expires_at: Set((now + VERIFICATION_TOKEN_LIFETIME).fixed_offset()),
+        ..Default::default()
+    };
+
+    verification.insert(db).await?;
+
+    let claims = EmailVerificationClaims {
+        sub: user.id,
+        jti: token_id,
+        purpose: EMAIL_VERIFICATION_PURPOSE.to_string(),
+    };
+    let token = token_service.create_signed_token(&claims, VERIFICATION_TOKEN_LIFETIME)?;
+
+    let verification_url = std::env::var("EMAIL_VERIFICATION_URL").unwrap_or_default();
+    let email = Email {
+        to: user.email.clone(),
+        subject: "Verify your LockBox email address".to_string(),
+        body: format!(
+            "Welcome to LockBox!\n\nConfirm your email address by opening the link below:\n{}?token={}\n\nThe link expires in 24 hours.",
+            verification_url, token
+        ),
+    };
+
+    mailer.send(email).await
+        .map_err(|e| AppError::internal(format!("failed to send verification email to user {}: {}", user.id, e)))
+}
+
+pub async fn verify_email(
+    db: web::Data<DatabaseConnection>,
+    token_service: web::Data<TokenService>,
+    form: web::Json<VerifyEmail>,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+    let invalid_token = || AppError::bad_request("Invalid or expired verification token!");
+
+    let claims = match token_service.verify::<EmailVerificationClaims>(&form.token) {
+        Ok(claims) if claims.purpose == EMAIL_VERIFICATION_PURPOSE => claims,
+        _ => return Err(invalid_token()),
+    };
+
+    let verification = email_verifications::Entity::find()
+        .filter(email_verifications::Column::TokenId.eq(claims.jti))
+        .filter(email_verifications::Column::UserId.eq(claims.sub))
+        .one(db)
+        .await?
+        .filter(|verification| verification.used_at.is_none())
+        .ok_or_else(invalid_token)?;
+
+    let user = users::Entity::find_by_id(claims.sub)
+        .one(db)
+        .await?
+        .ok_or_else(invalid_token)?;
+
+    let mut verification = verification.into_active_model();
+    verification.used_at = Set(Some(Utc::now().fixed_offset()));
+    verification.update(db).await?;
+
+    let mut user = user.into_active_model();
+    user.email_verified = Set(true);
+    user.update(db).await?;
+
+    Ok(())
+}
+
+pub async fn resend_verification(
+    db: web::Data<DatabaseConnection>,
+    token_service: web::Data<TokenService>,
+    mailer: web::Data<dyn Mailer>,
+    form: web::Json<ResendVerification>,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+
+    // Unknown and already verified addresses get the same answer so the endpoint can't be used to probe accounts.
+    let user = match users::Entity::find()
+        .filter(users::Column::Email.eq(form.email.clone()))
+        .one(db)
+        .await?
+    {
+        Some(user) if !user.email_verified && !user.is_deleted => user,
+        _ => return Ok(()),
+    };
+
+    let window_start = Utc::now() - RESEND_WINDOW;
+    let recent = email_verifications::Entity::find()
+        .filter(email_verifications::Column::UserId.eq(user.id))
+        .filter(email_verifications::Column::CreatedAt.gt(window_start.fixed_offset()))
+        .order_by_desc(email_verifications::Column::CreatedAt)
+        .all(db)
+        .await?;
+
+    let retry_after = match recent.first() {
+        Some(latest) if recent.len() >= MAX_SENDS_PER_WINDOW => {
+            let oldest = recent.last().unwrap_or(latest);
+            Some((oldest.created_at + RESEND_WINDOW).signed_duration_since(Utc::now()))
+        },
+        Some(latest) => {
+            let next_allowed = latest.created_at + RESEND_INTERVAL;
+            Some(next_allowed.signed_duration_since(Utc::now())).filter(|wait| wait.num_seconds() > 0)
+        },
+        None => None,
+    };
+
+    if let Some(wait) = retry_after {
+        return Err(AppError::TooManyRequests {
+            message: "Too many verification emails requested, try again later!".to_string(),
+            retry_after: wait.num_seconds().max(1) as u64,
+        });
+    }
+
+    send_verification_email(db, &token_service, mailer.get_ref(), &user).await
+}
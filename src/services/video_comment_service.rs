@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+use actix_web::web;
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, EntityTrait, IntoActiveModel, IntoSimpleExpr, JoinType, QueryFilter, QueryOrder, QuerySelect, RelationTrait};
+use sea_orm::ActiveValue::Set;
+use sea_orm::sea_query::{Alias, Expr, Func, Query};
+use crate::dtos::pagination_dto::{ListQuery, Page, SortOrder};
+use crate::dtos::video_comment_dto::{CommentResponse, CreateCommentForm, UpdateCommentForm};
+use crate::entities::{group_user, group_video, groups, users, video_comments, videos};
+use crate::errors::AppError;
+use crate::services::auth_service::UserClaims;
+use crate::services::{group_service, pagination_service};
+use crate::services::mail_service::{Email, Mailer};
+
+/// Mentions past this many in one comment don't notify anyone, so a comment can't be used to mail the whole group.
+const MAX_MENTIONS: usize = 10;
+
+fn to_response(comment: video_comments::Model, author: Option<users::Model>, reply_count: u64) -> CommentResponse {
+    let deleted = comment.deleted_at.is_some();
+
+    CommentResponse {
+        id: comment.id,
+        group_id: comment.group_id,
+        video_id: comment.video_id,
+        user_id: comment.user_id,
+        username: author.and_then(|author| author.username),
+        parent_id: comment.parent_id,
+        body: (!deleted).then_some(comment.body),
+        start_seconds: comment.start_seconds,
+        end_seconds: comment.end_seconds,
+        reply_count,
+        created_at: comment.created_at.to_rfc3339(),
+        edited_at: comment.edited_at.map(|edited_at| edited_at.to_rfc3339()),
+        deleted,
+    }
+}
+
+/// The lower-cased usernames `@mentioned` in a comment, in order of appearance.
+fn mentions(body: &str) -> Vec<String> {
+    let mut mentions: Vec<String> = Vec::new();
+
+    for word in body.split_whitespace() {
+        let Some(username) = word.strip_prefix('@') else { continue };
+        let username = username.trim_end_matches(|c: char| c.is_ascii_punctuation()).to_lowercase();
+
+        if (3..=32).contains(&username.chars().count()) && !mentions.contains(&username) {
+            mentions.push(username);
+        }
+    }
+
+    mentions.truncate(MAX_MENTIONS);
+    mentions
+}
+
+/// Comments are only seen by members of the group the video is shared in.
+async fn require_group_video(db: &DatabaseConnection, group_id: i64, video_id: i64, user_id: i64) -> Result<(), AppError> {
+    group_service::require_membership(db, group_id, user_id).await?;
+
+    group_video::Entity::find()
+        .filter(group_video::Column::GroupId.eq(group_id))
+        .filter(group_video::Column::VideoId.eq(video_id))
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("Video not found!"))?;
+
+    Ok(())
+}
+
+async fn find_comment(db: &DatabaseConnection, group_id: i64, video_id: i64, comment_id: i64) -> Result<video_comments::Model, AppError> {
+    video_comments::Entity::find_by_id(comment_id)
+        .filter(video_comments::Column::GroupId.eq(group_id))
+        .filter(video_comments::Column::VideoId.eq(video_id))
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("Comment not found!"))
+}
+
+async fn find_author(db: &DatabaseConnection, comment: &video_comments::Model) -> Result<Option<users::Model>, AppError> {
+    match comment.user_id {
+        Some(user_id) => Ok(users::Entity::find_by_id(user_id).one(db).await?),
+        None => Ok(None),
+    }
+}
+
+/// How many visible replies each of the given comments has.
+async fn reply_counts(db: &DatabaseConnection, comment_ids: Vec<i64>) -> Result<HashMap<i64, u64>, AppError> {
+    if comment_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let counts: Vec<(i64, i64)> = video_comments::Entity::find()
+        .select_only()
+        .column(video_comments::Column::ParentId)
+        .column_as(video_comments::Column::Id.count(), "replies")
+        .filter(video_comments::Column::ParentId.is_in(comment_ids))
+        .filter(video_comments::Column::DeletedAt.is_null())
+        .group_by(video_comments::Column::ParentId)
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    Ok(counts.into_iter().map(|(parent_id, replies)| (parent_id, replies as u64)).collect())
+}
+
+/// Emails the members of the group mentioned in a comment, skipping its author and anyone in `already_notified`.
+/// Delivery failures are logged, the comment is saved either way.
+async fn notify_mentioned(
+    db: &DatabaseConnection,
+    mailer: &dyn Mailer,
+    comment: &video_comments::Model,
+    author: Option<&users::Model>,
+    already_notified: &[String],
+) {
+    let usernames: Vec<String> = mentions(&comment.body)
+        .into_iter()
+        .filter(|username| !already_notified.contains(username))
+        .collect();
+
+    if usernames.is_empty() {
+        return;
+    }
+
+    let mentioned = users::Entity::find()
+        .join(JoinType::InnerJoin, users::Relation::GroupUser.def())
+        .filter(group_user::Column::GroupId.eq(comment.group_id))
+        .filter(Expr::expr(Func::lower(Expr::col((users::Entity, users::Column::Username)))).is_in(usernames))
+        .filter(users::Column::IsDeleted.eq(false))
+        .filter(users::Column::Id.ne(comment.user_id.unwrap_or_default()))
+        .all(db)
+        .await;
+    let group = groups::Entity::find_by_id(comment.group_id).one(db).await;
+    let video = videos::Entity::find_by_id(comment.video_id).one(db).await;
+
+    let (mentioned, group, video) = match (mentioned, group, video) {
+        (Ok(mentioned), Ok(Some(group)), Ok(Some(video))) => (mentioned, group, video),
+        _ => return,
+    };
+
+    let author = author.and_then(|author| author.username.as_deref()).unwrap_or("Someone");
+    let title = video.title.unwrap_or(video.name);
+
+    for user in mentioned {
+        let email = Email {
+            to: user.email.clone(),
+            subject: format!("{} mentioned you on LockBox", author),
+            body: format!(
+                "{} mentioned you in a comment on \"{}\" in the group \"{}\":\n\n{}",
+                author, title, group.name, comment.body
+            ),
+        };
+
+        if let Err(e) = mailer.send(email).await {
+            log::warn!("Failed to notify user {} about comment {}: {}", user.id, comment.id, e);
+        }
+    }
+}
+
+/// Top-level comments on a video, deleted ones only show up while they still have replies.
+pub async fn list_comments(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    query: web::Query<ListQuery>,
+) -> Result<Page<CommentResponse>, AppError> {
+    let db = db.get_ref();
+    let (group_id, video_id) = path.into_inner();
+
+    require_group_video(db, group_id, video_id, user_claims.id).await?;
+
+    let replies = Alias::new("replies");
+    let has_replies = Expr::exists(
+        Query::select()
+            .expr(Expr::val(1))
+            .from_as(video_comments::Entity, replies.clone())
+            .and_where(Expr::col((replies.clone(), video_comments::Column::ParentId)).equals((video_comments::Entity, video_comments::Column::Id)))
+            .and_where(Expr::col((replies, video_comments::Column::DeletedAt)).is_null())
+            .to_owned()
+    );
+
+    let select = video_comments::Entity::find()
+        .find_also_related(users::Entity)
+        .filter(video_comments::Column::GroupId.eq(group_id))
+        .filter(video_comments::Column::VideoId.eq(video_id))
+        .filter(video_comments::Column::ParentId.is_null())
+        .filter(Condition::any().add(video_comments::Column::DeletedAt.is_null()).add(has_replies));
+
+    let select = pagination_service::sort(select, &query, &[
+        ("created_at", video_comments::Column::CreatedAt.into_simple_expr()),
+        ("timestamp", video_comments::Column::StartSeconds.into_simple_expr()),
+    ], ("created_at", SortOrder::Desc))?;
+
+    let comments = pagination_service::paginate(select.order_by_asc(video_comments::Column::Id), db, &query).await?;
+
+    let mut counts = reply_counts(db, comments.items.iter().map(|(comment, _)| comment.id).collect()).await?;
+
+    Ok(comments.map(|(comment, author)| {
+        let reply_count = counts.remove(&comment.id).unwrap_or_default();
+        to_response(comment, author, reply_count)
+    }))
+}
+
+/// The replies in a comment's thread, oldest first unless sorted otherwise.
+pub async fn list_replies(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64, i64)>,
+    user_claims: UserClaims,
+    query: web::Query<ListQuery>,
+) -> Result<Page<CommentResponse>, AppError> {
+    let db = db.get_ref();
+    let (group_id, video_id, comment_id) = path.into_inner();
+
+    require_group_video(db, group_id, video_id, user_claims.id).await?;
+    let comment = find_comment(db, group_id, video_id, comment_id).await?;
+
+    let select = video_comments::Entity::find()
+        .find_also_related(users::Entity)
+        .filter(video_comments::Column::ParentId.eq(comment.id))
+        .filter(video_comments::Column::DeletedAt.is_null());
+
+    let select = pagination_service::sort(select, &query, &[
+        ("created_at", video_comments::Column::CreatedAt.into_simple_expr()),
+        ("timestamp", video_comments::Column::StartSeconds.into_simple_expr()),
+    ], ("created_at", SortOrder::Asc))?;
+
+    let replies = pagination_service::paginate(select.order_by_asc(video_comments::Column::Id), db, &query).await?;
+
+    Ok(replies.map(|(reply, author)| to_response(reply, author, 0)))
+}
+
+pub async fn create_comment(
+    db: web::Data<DatabaseConnection>,
+    mailer: web::Data<dyn Mailer>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: web::Json<CreateCommentForm>,
+) -> Result<CommentResponse, AppError> {
+    let db = db.get_ref();
+    let (group_id, video_id) = path.into_inner();
+
+    require_group_video(db, group_id, video_id, user_claims.id).await?;
+
+    match (form.start_seconds, form.end_seconds) {
+        (None, Some(_)) => return Err(AppError::field("end_seconds", "Requires a start_seconds.")),
+        (Some(start), Some(end)) if end < start => return Err(AppError::field("end_seconds", "Must not come before start_seconds.")),
+        _ => {},
+    }
+
+    // Threads are one level deep, a reply to a reply goes to the thread it belongs to.
+    let parent_id = match form.parent_id {
+        Some(parent_id) => {
+            let parent = find_comment(db, group_id, video_id, parent_id).await?;
+            if parent.deleted_at.is_some() {
+                return Err(AppError::conflict("This comment was deleted!"));
+            }
+            Some(parent.parent_id.unwrap_or(parent.id))
+        },
+        None => None,
+    };
+
+    let comment = video_comments::ActiveModel {
+        group_id: Set(group_id),
+        video_id: Set(video_id),
+        user_id: Set(Some(user_claims.id)),
+        parent_id: Set(parent_id),
+        body: Set(form.body.trim().to_string()),
+        start_seconds: Set(form.start_seconds),
+        end_seconds: Set(form.end_seconds),
+        created_at: Set(Utc::now().fixed_offset()),
+        ..Default::default()
+    };
+
+    let comment = comment.insert(db).await?;
+    let author = find_author(db, &comment).await?;
+
+    notify_mentioned(db, mailer.get_ref(), &comment, author.as_ref(), &[]).await;
+
+    Ok(to_response(comment, author, 0))
+}
+
+/// Only the author can edit a comment, members mentioned for the first time get notified.
+pub async fn update_comment(
+    db: web::Data<DatabaseConnection>,
+    mailer: web::Data<dyn Mailer>,
+    path: web::Path<(i64, i64, i64)>,
+    user_claims: UserClaims,
+    form: web::Json<UpdateCommentForm>,
+) -> Result<CommentResponse, AppError> {
+    let db = db.get_ref();
+    let (group_id, video_id, comment_id) = path.into_inner();
+
+    require_group_video(db, group_id, video_id, user_claims.id).await?;
+
+    let comment = find_comment(db, group_id, video_id, comment_id).await?;
+    if comment.deleted_at.is_some() {
+        return Err(AppError::not_found("Comment not found!"));
+    }
+    if comment.user_id != Some(user_claims.id) {
+        return Err(AppError::forbidden("Only the author can edit this comment!"));
+    }
+
+    let previous_mentions = mentions(&comment.body);
+
+    let mut comment = comment.into_active_model();
+    comment.body = Set(form.body.trim().to_string());
+    comment.edited_at = Set(Some(Utc::now().fixed_offset()));
+    let comment = comment.update(db).await?;
+
+    let author = find_author(db, &comment).await?;
+    let reply_count = reply_counts(db, vec![comment.id]).await?.remove(&comment.id).unwrap_or_default();
+
+    notify_mentioned(db, mailer.get_ref(), &comment, author.as_ref(), &previous_mentions).await;
+
+    Ok(to_response(comment, author, reply_count))
+}
+
+/// Authors can delete their own comments, group owners and admins can delete anyone's.
+/// The comment stays without its text so the thread under it isn't lost.
+pub async fn delete_comment(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+    let (group_id, video_id, comment_id) = path.into_inner();
+
+    require_group_video(db, group_id, video_id, user_claims.id).await?;
+
+    let comment = find_comment(db, group_id, video_id, comment_id).await?;
+    if comment.deleted_at.is_some() {
+        return Err(AppError::not_found("Comment not found!"));
+    }
+    if comment.user_id != Some(user_claims.id) {
+        group_service::require_manager(db, group_id, user_claims.id).await?;
+    }
+
+    let mut comment = comment.into_active_model();
+    comment.body = Set(String::new());
+    comment.deleted_at = Set(Some(Utc::now().fixed_offset()));
+    comment.update(db).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{DatabaseBackend, MockDatabase, Value};
+    use crate::services::mail_service::FileMailer;
+    use super::*;
+
+    fn comment(body: String) -> video_comments::Model {
+        video_comments::Model {
+            id: 1,
+            group_id: 1,
+            video_id: 4,
+            user_id: Some(7),
+            parent_id: None,
+            body,
+            start_seconds: None,
+            end_seconds: None,
+            created_at: Utc::now().fixed_offset(),
+            edited_at: None,
+            deleted_at: None,
+        }
+    }
+
+    fn mass_mention(count: usize) -> String {
+        (1..=count).map(|n| format!("@member{:02}", n)).collect::<Vec<String>>().join(" ")
+    }
+
+    #[test]
+    fn mentions_are_normalized_and_deduplicated() {
+        assert_eq!(mentions("Thanks @Jane, and @jane! cc @bob. @al @"), ["jane", "bob"]);
+        assert_eq!(mentions("mail@example.com isn't a mention"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn mentions_are_capped() {
+        let mentioned = mentions(&mass_mention(25));
+
+        assert_eq!(mentioned.len(), MAX_MENTIONS);
+        assert_eq!(mentioned.first().map(String::as_str), Some("member01"));
+        assert_eq!(mentioned.last().map(String::as_str), Some("member10"));
+    }
+
+    #[actix_web::test]
+    async fn mass_mentions_only_look_up_the_first_members() {
+        let outbox = tempfile::tempdir().unwrap();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<users::Model>::new()])
+            .into_connection();
+
+        notify_mentioned(&db, &FileMailer::new(outbox.path()), &comment(mass_mention(25)), None, &[]).await;
+
+        let log = db.into_transaction_log();
+        let usernames = log[0].statements()[0].values.clone().unwrap().0.into_iter()
+            .filter(|value| matches!(value, Value::String(Some(username)) if username.starts_with("member")))
+            .count();
+        assert_eq!(usernames, MAX_MENTIONS);
+    }
+}
@@ -0,0 +1,331 @@
+use actix_web::web;
+use chrono::Utc;
+use sea_orm::{ColumnTrait, Condition, DatabaseConnection, EntityTrait, IntoSimpleExpr, QueryFilter, QueryOrder};
+use sea_orm::ActiveValue::Set;
+use sea_orm::sea_query::{Expr, OnConflict, Query};
+use crate::dtos::pagination_dto::{ListQuery, Page, SortOrder};
+use crate::dtos::video_progress_dto::{ProgressResponse, UpdateProgressForm, WatchHistoryResponse};
+use crate::entities::{group_user, group_video, groups, video_progress, videos};
+use crate::entities::prelude::{GroupVideo, Videos};
+use crate::errors::AppError;
+use crate::services::auth_service::UserClaims;
+use crate::services::{group_service, pagination_service, video_service};
+
+/// A video counts as watched once playback passes this share of its duration, credits rarely get watched.
+const COMPLETION_THRESHOLD: f64 = 0.95;
+
+fn to_response(progress: video_progress::Model) -> ProgressResponse {
+    ProgressResponse {
+        video_id: progress.video_id,
+        group_id: progress.group_id,
+        position_seconds: progress.position_seconds,
+        duration_seconds: progress.duration_seconds,
+        completed: progress.completed,
+        updated_at: progress.updated_at.to_rfc3339(),
+        completed_at: progress.completed_at.map(|completed_at| completed_at.to_rfc3339()),
+    }
+}
+
+fn to_history_response((progress, video): (video_progress::Model, Option<videos::Model>)) -> Option<WatchHistoryResponse> {
+    Some(WatchHistoryResponse {
+        video: video_service::to_response(video?, None),
+        progress: to_response(progress),
+    })
+}
+
+/// Only progress in groups the user is still a member of, for videos still shared in them, shows up.
+fn still_accessible(user_id: i64) -> Condition {
+    Condition::all()
+        .add(
+            video_progress::Column::GroupId.in_subquery(
+                Query::select()
+                    .column((group_user::Entity, group_user::Column::GroupId))
+                    .from(group_user::Entity)
+                    .inner_join(
+                        groups::Entity,
+                        Expr::col((groups::Entity, groups::Column::Id)).equals((group_user::Entity, group_user::Column::GroupId))
+                    )
+                    .and_where(Expr::col((group_user::Entity, group_user::Column::UserId)).eq(user_id))
+                    .and_where(Expr::col((groups::Entity, groups::Column::IsDeleted)).eq(false))
+                    .to_owned()
+            )
+        )
+        .add(
+            Expr::exists(
+                Query::select()
+                    .expr(Expr::val(1))
+                    .from(group_video::Entity)
+                    .and_where(Expr::col((group_video::Entity, group_video::Column::GroupId)).equals((video_progress::Entity, video_progress::Column::GroupId)))
+                    .and_where(Expr::col((group_video::Entity, group_video::Column::VideoId)).equals((video_progress::Entity, video_progress::Column::VideoId)))
+                    .to_owned()
+            )
+        )
+}
+
+async fn require_group_video(db: &DatabaseConnection, group_id: i64, video_id: i64, user_id: i64) -> Result<(), AppError> {
+    group_service::require_membership(db, group_id, user_id).await?;
+
+    GroupVideo::find()
+        .filter(group_video::Column::GroupId.eq(group_id))
+        .filter(group_video::Column::VideoId.eq(video_id))
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("Video not found!"))?;
+
+    Ok(())
+}
+
+async fn find_progress(db: &DatabaseConnection, user_id: i64, video_id: i64) -> Result<Option<video_progress::Model>, AppError> {
+    Ok(video_progress::Entity::find()
+        .filter(video_progress::Column::UserId.eq(user_id))
+        .filter(video_progress::Column::VideoId.eq(video_id))
+        .one(db)
+        .await?)
+}
+
+/// Stores where the user is in a video, called periodically by the player.
+pub async fn update_progress(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: web::Json<UpdateProgressForm>,
+) -> Result<ProgressResponse, AppError> {
+    let db = db.get_ref();
+    let (group_id, video_id) = path.into_inner();
+
+    require_group_video(db, group_id, video_id, user_claims.id).await?;
+
+    let existing = find_progress(db, user_claims.id, video_id).await?;
+    let now = Utc::now().fixed_offset();
+
+    let duration = form.duration_seconds.or(existing.as_ref().and_then(|progress| progress.duration_seconds));
+    let position = match duration {
+        Some(duration) => form.position_seconds.min(duration),
+        None => form.position_seconds,
+    };
+
+    // Rewatching a finished video keeps it finished until it's explicitly marked unwatched.
+    let reached_end = duration.is_some_and(|duration| duration > 0.0 && position >= duration * COMPLETION_THRESHOLD);
+    let was_completed = existing.as_ref().is_some_and(|progress| progress.completed);
+    let completed = form.completed.unwrap_or(was_completed || reached_end);
+
+    let completed_at = match (completed, existing.as_ref().and_then(|progress| progress.completed_at)) {
+        (true, Some(completed_at)) if was_completed => Some(completed_at),
+        (true, _) => Some(now),
+        (false, _) => None,
+    };
+
+    let progress = video_progress::ActiveModel {
+        user_id: Set(user_claims.id),
+        video_id: Set(video_id),
+        group_id: Set(group_id),
+        position_seconds: Set(position),
+        duration_seconds: Set(duration),
+        completed: Set(completed),
+        updated_at: Set(now),
+        completed_at: Set(completed_at),
+        ..Default::default()
+    };
+
+    // Two reports racing for the first row of a video end up as one.
+    video_progress::Entity::insert(progress)
+        .on_conflict(
+            OnConflict::columns([video_progress::Column::UserId, video_progress::Column::VideoId])
+                .update_columns([
+                    video_progress::Column::GroupId,
+                    video_progress::Column::PositionSeconds,
+                    video_progress::Column::DurationSeconds,
+                    video_progress::Column::Completed,
+                    video_progress::Column::UpdatedAt,
+                    video_progress::Column::CompletedAt,
+                ])
+                .to_owned()
+        )
+        .exec(db)
+        .await?;
+
+    let progress = find_progress(db, user_claims.id, video_id)
+        .await?
+        .ok_or(AppError::internal("progress missing right after saving it"))?;
+
+    Ok(to_response(progress))
+}
+
+pub async fn get_progress(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<ProgressResponse, AppError> {
+    let db = db.get_ref();
+    let (group_id, video_id) = path.into_inner();
+
+    require_group_video(db, group_id, video_id, user_claims.id).await?;
+
+    find_progress(db, user_claims.id, video_id)
+        .await?
+        .map(to_response)
+        .ok_or(AppError::not_found("This video hasn't been watched yet!"))
+}
+
+/// Forgets the user's progress on a video, which also takes it off their history.
+pub async fn delete_progress(
+    db: web::Data<DatabaseConnection>,
+    video_id: web::Path<i64>,
+    user_claims: UserClaims,
+) -> Result<(), AppError> {
+    let deleted = video_progress::Entity::delete_many()
+        .filter(video_progress::Column::UserId.eq(user_claims.id))
+        .filter(video_progress::Column::VideoId.eq(video_id.into_inner()))
+        .exec(db.get_ref())
+        .await?;
+
+    if deleted.rows_affected == 0 {
+        return Err(AppError::not_found("This video isn't in your history!"));
+    }
+
+    Ok(())
+}
+
+/// Recently watched videos, `unfinished` limits it to the ones to continue watching.
+pub async fn list_history(
+    db: web::Data<DatabaseConnection>,
+    user_claims: UserClaims,
+    query: web::Query<ListQuery>,
+    unfinished: bool,
+) -> Result<Page<WatchHistoryResponse>, AppError> {
+    let mut select = video_progress::Entity::find()
+        .find_also_related(Videos)
+        .filter(video_progress::Column::UserId.eq(user_claims.id))
+        .filter(still_accessible(user_claims.id));
+
+    if unfinished {
+        select = select
+            .filter(video_progress::Column::Completed.eq(false))
+            .filter(video_progress::Column::PositionSeconds.gt(0.0));
+    }
+
+    let select = pagination_service::sort(select, &query, &[
+        ("updated_at", video_progress::Column::UpdatedAt.into_simple_expr()),
+    ], ("updated_at", SortOrder::Desc))?;
+
+    let history = pagination_service::paginate(select.order_by_desc(video_progress::Column::Id), db.get_ref(), &query).await?;
+
+    // The video can't be missing, its progress is deleted along with it.
+    let history = history.map(to_history_response);
+    Ok(Page::new(history.items.into_iter().flatten().collect(), history.page, history.per_page, history.total_items))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use sea_orm::{DatabaseBackend, MockDatabase, QueryTrait, Value};
+    use crate::services::auth_service::Role;
+    use crate::services::group_service::{GroupRole, GroupVisibility};
+    use super::*;
+
+    const GROUP_ID: i64 = 1;
+    const VIDEO_ID: i64 = 4;
+    const USER_ID: i64 = 7;
+
+    fn claims() -> UserClaims {
+        UserClaims { id: USER_ID, role: Role::RegisteredUser, session_version: 0, scopes: None }
+    }
+
+    /// Answers the group and membership lookups of `require_membership`.
+    fn db(memberships: Vec<group_user::Model>) -> MockDatabase {
+        MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![groups::Model {
+                id: GROUP_ID,
+                name: "film-club".to_string(),
+                password: None,
+                created_at: Utc::now().fixed_offset(),
+                is_deleted: false,
+                description: None,
+                visibility: GroupVisibility::Public.as_str().to_string(),
+            }]])
+            .append_query_results([memberships])
+    }
+
+    fn membership() -> group_user::Model {
+        group_user::Model {
+            id: 1,
+            group_id: GROUP_ID,
+            user_id: USER_ID,
+            joined_at: Utc::now().fixed_offset(),
+            role: GroupRole::Member.as_str().to_string(),
+        }
+    }
+
+    fn group_video() -> group_video::Model {
+        group_video::Model { group_id: GROUP_ID, video_id: VIDEO_ID, metadata: serde_json::json!({}), folder_id: None }
+    }
+
+    fn progress(position_seconds: f64, completed: bool) -> video_progress::Model {
+        video_progress::Model {
+            id: 1,
+            user_id: USER_ID,
+            video_id: VIDEO_ID,
+            group_id: GROUP_ID,
+            position_seconds,
+            duration_seconds: Some(100.0),
+            completed,
+            updated_at: Utc::now().fixed_offset(),
+            completed_at: None,
+        }
+    }
+
+    fn form(position_seconds: f64) -> web::Json<UpdateProgressForm> {
+        web::Json(UpdateProgressForm { position_seconds, duration_seconds: Some(100.0), completed: None })
+    }
+
+    #[actix_web::test]
+    async fn non_members_cannot_report_progress() {
+        let db = web::Data::new(db(vec![]).into_connection());
+
+        let result = update_progress(db, web::Path::from((GROUP_ID, VIDEO_ID)), claims(), form(10.0)).await;
+
+        assert!(matches!(result, Err(AppError::Forbidden(_))), "{:?}", result);
+    }
+
+    #[actix_web::test]
+    async fn progress_needs_the_video_to_be_shared_in_the_group() {
+        let db = web::Data::new(db(vec![membership()])
+            .append_query_results([Vec::<group_video::Model>::new()])
+            .into_connection());
+
+        let result = update_progress(db, web::Path::from((GROUP_ID, VIDEO_ID)), claims(), form(10.0)).await;
+
+        assert!(matches!(result, Err(AppError::NotFound(_))), "{:?}", result);
+    }
+
+    #[actix_web::test]
+    async fn nearing_the_end_marks_the_video_watched() {
+        let db = web::Data::new(db(vec![membership()])
+            .append_query_results([vec![group_video()]])
+            .append_query_results([Vec::<video_progress::Model>::new()])
+            .append_query_results([[BTreeMap::from([("id", Value::BigInt(Some(1)))])]])
+            .append_query_results([vec![progress(100.0, true)]])
+            .into_connection());
+
+        // Positions past the end are clamped to the duration.
+        update_progress(db.clone(), web::Path::from((GROUP_ID, VIDEO_ID)), claims(), form(120.0)).await.unwrap();
+
+        let log = Arc::try_unwrap(db.into_inner()).unwrap().into_transaction_log();
+        let values = log[4].statements()[0].values.clone().unwrap().0;
+        assert!(!values.contains(&Value::Double(Some(120.0))), "{:?}", values);
+        assert!(values.contains(&Value::Bool(Some(true))), "{:?}", values);
+    }
+
+    #[test]
+    fn history_only_covers_live_groups_the_user_belongs_to() {
+        let sql = video_progress::Entity::find()
+            .filter(still_accessible(USER_ID))
+            .build(DatabaseBackend::Postgres)
+            .to_string();
+
+        assert!(sql.contains(&format!(r#""GroupUser"."user_id" = {}"#, USER_ID)), "{}", sql);
+        assert!(sql.contains(r#""Groups"."is_deleted" = FALSE"#), "{}", sql);
+        assert!(sql.contains(r#"EXISTS(SELECT 1 FROM "GroupVideo""#), "{}", sql);
+    }
+}
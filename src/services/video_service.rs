@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+use actix_web::web;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QueryFilter, TransactionTrait};
+use sea_orm::ActiveValue::Set;
+use serde_json::Value;
+use crate::dtos::video_dto::{UpdateVideoForm, VideoResponse};
+use crate::dtos::video_subtitle_dto::VideoDetailResponse;
+use crate::entities::{group_video, videos};
+use crate::errors::AppError;
+use crate::services::auth_service::UserClaims;
+use crate::services::{group_service, video_subtitle_service, video_transcript_service};
+
+pub fn to_response(video: videos::Model, entry: Option<group_video::Model>) -> VideoResponse {
+    VideoResponse {
+        id: video.id,
+        title: video.title.unwrap_or_else(|| video.name.clone()),
+        key: video.key,
+        name: video.name,
+        description: video.description,
+        tags: serde_json::from_value(video.tags).unwrap_or_default(),
+        folder_id: entry.as_ref().and_then(|entry| entry.folder_id),
+        metadata: entry.and_then(|entry| serde_json::from_value(entry.metadata).ok()).unwrap_or_default(),
+        uploaded_at: video.uploaded_at.map(|uploaded_at| uploaded_at.and_utc().to_rfc3339()),
+    }
+}
+
+/// Trims and lower-cases tags, dropping duplicates but keeping the given order.
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
+/// The metadata as stored, keys and values trimmed and entries without a value left out.
+pub fn metadata_json(metadata: &BTreeMap<String, String>) -> Value {
+    let metadata: BTreeMap<&str, &str> = metadata.iter()
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .filter(|(_, value)| !value.is_empty())
+        .collect();
+
+    serde_json::to_value(metadata).unwrap_or_else(|_| Value::Object(Default::default()))
+}
+
+/// Copies the title, description and tags present in `form` onto `video`, empty texts clear them.
+pub fn apply_details(video: &mut videos::ActiveModel, form: &UpdateVideoForm) {
+    if let Some(title) = &form.title {
+        video.title = Set(Some(title.trim().to_string()).filter(|title| !title.is_empty()));
+    }
+    if let Some(description) = &form.description {
+        video.description = Set(Some(description.trim().to_string()).filter(|description| !description.is_empty()));
+    }
+    if let Some(tags) = &form.tags {
+        video.tags = Set(Value::from(normalize_tags(tags)));
+    }
+}
+
+/// A single video of the group, with its caption tracks and transcription status.
+pub async fn get_video(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<VideoDetailResponse, AppError> {
+    let db = db.get_ref();
+    let (group_id, video_id) = path.into_inner();
+
+    group_service::require_viewer(db, group_id, user_claims.id).await?;
+
+    let (entry, video) = group_video::Entity::find()
+        .filter(group_video::Column::GroupId.eq(group_id))
+        .filter(group_video::Column::VideoId.eq(video_id))
+        .find_also_related(videos::Entity)
+        .one(db)
+        .await?
+        .and_then(|(entry, video)| Some((entry, video?)))
+        .ok_or(AppError::not_found("Video not found!"))?;
+
+    Ok(VideoDetailResponse {
+        video: to_response(video, Some(entry)),
+        subtitles: video_subtitle_service::list_subtitles(db, group_id, video_id, user_claims.id).await?,
+        transcription: video_transcript_service::latest_job(db, video_id).await?,
+    })
+}
+
+pub async fn update_video(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    form: web::Json<UpdateVideoForm>,
+) -> Result<VideoResponse, AppError> {
+    let db = db.get_ref();
+    let (group_id, video_id) = path.into_inner();
+
+    group_service::require_manager(db, group_id, user_claims.id).await?;
+
+    let (entry, video) = group_video::Entity::find()
+        .filter(group_video::Column::GroupId.eq(group_id))
+        .filter(group_video::Column::VideoId.eq(video_id))
+        .find_also_related(videos::Entity)
+        .one(db)
+        .await?
+        .and_then(|(entry, video)| Some((entry, video?)))
+        .ok_or(AppError::not_found("Video not found!"))?;
+
+    let transaction = db.begin().await?;
+
+    let mut video = video.into_active_model();
+    apply_details(&mut video, &form);
+    let video = video.update(&transaction).await?;
+
+    let entry = match &form.metadata {
+        Some(metadata) => {
+            let mut entry = entry.into_active_model();
+            entry.metadata = Set(metadata_json(metadata));
+            entry.update(&transaction).await?
+        },
+        None => entry,
+    };
+
+    transaction.commit().await?;
+
+    Ok(to_response(video, Some(entry)))
+}
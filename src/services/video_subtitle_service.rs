@@ -0,0 +1,389 @@
+use actix_multipart::form::MultipartForm;
+use actix_multipart::form::tempfile::TempFile;
+use actix_multipart::form::text::Text;
+use actix_web::web;
+use actix_web::web::Bytes;
+use aws_sdk_s3 as s3;
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QueryFilter, QueryOrder};
+use sea_orm::ActiveValue::Set;
+use crate::dtos::video_subtitle_dto::SubtitleResponse;
+use crate::entities::{group_video, video_subtitles};
+use crate::errors::AppError;
+use crate::services::auth_service::UserClaims;
+use crate::services::{group_service, storage_service};
+
+pub const WEBVTT_CONTENT_TYPE: &str = "text/vtt; charset=utf-8";
+
+const WEBVTT_HEADER: &str = "WEBVTT";
+const MAX_LANGUAGE_LENGTH: usize = 35;
+const MAX_LABEL_LENGTH: usize = 100;
+
+#[derive(Debug, MultipartForm)]
+pub struct SubtitleUploadForm {
+    /// A WebVTT (`.vtt`) or SubRip (`.srt`) file, SubRip is converted to WebVTT.
+    #[multipart(limit = "2 MiB")]
+    file: TempFile,
+    label: Option<Text<String>>,
+}
+
+pub fn to_response(subtitle: video_subtitles::Model, group_id: i64) -> SubtitleResponse {
+    SubtitleResponse {
+        url: format!("/groups/{}/videos/{}/subtitles/{}", group_id, subtitle.video_id, subtitle.language),
+        label: subtitle.label.unwrap_or_else(|| subtitle.language.clone()),
+        language: subtitle.language,
+        updated_at: subtitle.updated_at.to_rfc3339(),
+    }
+}
+
+/// Lower-cases a BCP 47 style tag (`en`, `pt-BR`, `zh-Hant`), which is how tracks are keyed.
+fn parse_language(language: &str) -> Result<String, AppError> {
+    let language = language.trim().to_lowercase();
+    let mut subtags = language.split('-');
+
+    let primary = subtags.next().unwrap_or_default();
+    let valid = language.len() <= MAX_LANGUAGE_LENGTH
+        && (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_lowercase())
+        && subtags.all(|subtag| (2..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric()));
+
+    match valid {
+        true => Ok(language),
+        false => Err(AppError::field("language", "Must be a language tag like `en` or `pt-BR`.")),
+    }
+}
+
+/// Converts an SRT timestamp (`00:01:02,500`) to WebVTT (`00:01:02.500`).
+fn srt_timestamp(timestamp: &str) -> Option<String> {
+    let (clock, millis) = timestamp.trim().split_once([',', '.'])?;
+    let clock: Vec<u32> = clock.split(':').map(|part| part.trim().parse().ok()).collect::<Option<_>>()?;
+    let [hours, minutes, seconds] = clock[..] else { return None };
+
+    if minutes > 59 || seconds > 59 || millis.len() != 3 || !millis.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    Some(format!("{:02}:{:02}:{:02}.{}", hours, minutes, seconds, millis))
+}
+
+/// Rewrites SubRip cues as WebVTT, `None` if a cue is malformed or there are none.
+fn srt_to_webvtt(srt: &str) -> Option<String> {
+    let mut webvtt = String::from(WEBVTT_HEADER);
+    let mut cues = 0;
+    let mut lines = srt.lines().map(str::trim_end).peekable();
+
+    loop {
+        while lines.next_if(|line| line.is_empty()).is_some() {}
+        let Some(mut line) = lines.next() else { break };
+
+        // The cue number is optional in practice, WebVTT keeps it as the cue identifier.
+        let mut identifier = None;
+        if line.chars().all(|c| c.is_ascii_digit()) {
+            identifier = Some(line);
+            line = lines.next()?;
+        }
+
+        let (start, end) = line.split_once("-->")?;
+        // Anything after the end time is SRT positioning, which WebVTT expresses differently.
+        let end = end.split_whitespace().next()?;
+
+        webvtt.push_str("\n\n");
+        if let Some(identifier) = identifier {
+            webvtt.push_str(identifier);
+            webvtt.push('\n');
+        }
+        webvtt.push_str(&format!("{} --> {}", srt_timestamp(start)?, srt_timestamp(end)?));
+
+        while let Some(text) = lines.next_if(|line| !line.is_empty()) {
+            webvtt.push('\n');
+            webvtt.push_str(&text.replace("-->", "->"));
+        }
+
+        cues += 1;
+    }
+
+    webvtt.push('\n');
+    (cues > 0).then_some(webvtt)
+}
+
+/// The uploaded track as WebVTT, SRT files are recognised by their extension or by lacking the WebVTT header.
+fn to_webvtt(file_name: Option<&str>, contents: &str) -> Result<String, AppError> {
+    let contents = contents.trim_start_matches('\u{feff}').replace("\r\n", "\n").replace('\r', "\n");
+    let is_srt = file_name.is_some_and(|name| name.to_lowercase().ends_with(".srt"));
+
+    let header = contents.lines().next().unwrap_or_default();
+    let is_webvtt = header == WEBVTT_HEADER || header.starts_with("WEBVTT ") || header.starts_with("WEBVTT\t");
+
+    if is_webvtt && !is_srt {
+        return Ok(contents);
+    }
+
+    srt_to_webvtt(&contents).ok_or(AppError::field("file", "Must be a valid WebVTT or SRT file."))
+}
+
+async fn require_group_video(db: &DatabaseConnection, group_id: i64, video_id: i64) -> Result<(), AppError> {
+    group_video::Entity::find()
+        .filter(group_video::Column::GroupId.eq(group_id))
+        .filter(group_video::Column::VideoId.eq(video_id))
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("Video not found!"))?;
+
+    Ok(())
+}
+
+async fn find_subtitle(db: &DatabaseConnection, video_id: i64, language: &str) -> Result<Option<video_subtitles::Model>, AppError> {
+    Ok(video_subtitles::Entity::find()
+        .filter(video_subtitles::Column::VideoId.eq(video_id))
+        .filter(video_subtitles::Column::Language.eq(language))
+        .one(db)
+        .await?)
+}
+
+/// The video's tracks ordered by language, for the video detail. Checked on its own like `serve_subtitle`, so
+/// other callers can't hand out tracks of videos the user can't see.
+pub async fn list_subtitles(db: &DatabaseConnection, group_id: i64, video_id: i64, user_id: i64) -> Result<Vec<SubtitleResponse>, AppError> {
+    group_service::require_viewer(db, group_id, user_id).await?;
+    require_group_video(db, group_id, video_id).await?;
+
+    let subtitles = video_subtitles::Entity::find()
+        .filter(video_subtitles::Column::VideoId.eq(video_id))
+        .order_by_asc(video_subtitles::Column::Language)
+        .all(db)
+        .await?;
+
+    Ok(subtitles.into_iter().map(|subtitle| to_response(subtitle, group_id)).collect())
+}
+
+/// The track as WebVTT, readable by whoever can see the group's videos.
+pub async fn serve_subtitle(
+    db: web::Data<DatabaseConnection>,
+    client: web::Data<s3::Client>,
+    path: web::Path<(i64, i64, String)>,
+    user_claims: UserClaims,
+) -> Result<Bytes, AppError> {
+    let db = db.get_ref();
+    let (group_id, video_id, language) = path.into_inner();
+
+    group_service::require_viewer(db, group_id, user_claims.id).await?;
+    require_group_video(db, group_id, video_id).await?;
+
+    let subtitle = find_subtitle(db, video_id, &parse_language(&language)?)
+        .await?
+        .ok_or(AppError::not_found("Subtitles not found!"))?;
+
+    storage_service::get_object(&client, &subtitle.key).await
+}
+
+/// Adds the track for a language or replaces the existing one. Like the title, tracks belong to the
+/// video and show up in every group it's shared in.
+pub async fn upload_subtitle(
+    db: web::Data<DatabaseConnection>,
+    client: web::Data<s3::Client>,
+    path: web::Path<(i64, i64, String)>,
+    user_claims: UserClaims,
+    MultipartForm(form): MultipartForm<SubtitleUploadForm>,
+) -> Result<SubtitleResponse, AppError> {
+    let db = db.get_ref();
+    let (group_id, video_id, language) = path.into_inner();
+
+    group_service::require_manager(db, group_id, user_claims.id).await?;
+    require_group_video(db, group_id, video_id).await?;
+
+    let language = parse_language(&language)?;
+    let label = form.label.as_ref().map(|label| label.trim().to_string()).filter(|label| !label.is_empty());
+    if label.as_ref().is_some_and(|label| label.chars().count() > MAX_LABEL_LENGTH) {
+        return Err(AppError::field("label", &format!("Must be at most {} characters long.", MAX_LABEL_LENGTH)));
+    }
+
+    let contents = std::fs::read(form.file.file.path())
+        .map_err(|e| AppError::internal(format!("failed to read uploaded subtitles: {:?}", e)))?;
+    let contents = String::from_utf8(contents).map_err(|_| AppError::field("file", "Must be UTF-8 encoded text."))?;
+    let webvtt = to_webvtt(form.file.file_name.as_deref(), &contents)?;
+
+    // A new key per upload, so a replaced track is never served half-written.
+    let key = storage_service::generate_random_key("vtt");
+    storage_service::put_object(&client, &key, webvtt.into_bytes(), WEBVTT_CONTENT_TYPE).await?;
+
+    let now = Utc::now().fixed_offset();
+    let existing = find_subtitle(db, video_id, &language).await?;
+    let replaced_key = existing.as_ref().map(|subtitle| subtitle.key.clone());
+
+    let saved = match existing {
+        Some(subtitle) => {
+            let mut subtitle = subtitle.into_active_model();
+            subtitle.label = Set(label);
+            subtitle.key = Set(key.clone());
+            subtitle.updated_at = Set(now);
+            subtitle.update(db).await
+        },
+        None => video_subtitles::ActiveModel {
+            video_id: Set(video_id),
+            language: Set(language),
+            label: Set(label),
+            key: Set(key.clone()),
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..Default::default()
+        }.insert(db).await,
+    };
+
+    let subtitle = match saved {
+        Ok(subtitle) => subtitle,
+        Err(e) => {
+            if let Err(e) = storage_service::delete_object(&client, &key).await {
+                log::error!("Failed to delete storage object of unsaved subtitles {}: {}", key, e);
+            }
+            return Err(AppError::unique(e, &[("language", "Subtitles for this language were just uploaded!")]));
+        },
+    };
+
+    if let Some(replaced_key) = replaced_key {
+        if let Err(e) = storage_service::delete_object(&client, &replaced_key).await {
+            log::error!("Failed to delete storage object of replaced subtitles {}: {}", subtitle.id, e);
+        }
+    }
+
+    Ok(to_response(subtitle, group_id))
+}
+
+pub async fn delete_subtitle(
+    db: web::Data<DatabaseConnection>,
+    client: web::Data<s3::Client>,
+    path: web::Path<(i64, i64, String)>,
+    user_claims: UserClaims,
+) -> Result<(), AppError> {
+    let db = db.get_ref();
+    let (group_id, video_id, language) = path.into_inner();
+
+    group_service::require_manager(db, group_id, user_claims.id).await?;
+    require_group_video(db, group_id, video_id).await?;
+
+    let subtitle = find_subtitle(db, video_id, &parse_language(&language)?)
+        .await?
+        .ok_or(AppError::not_found("Subtitles not found!"))?;
+
+    video_subtitles::Entity::delete_by_id(subtitle.id).exec(db).await?;
+
+    if let Err(e) = storage_service::delete_object(&client, &subtitle.key).await {
+        log::error!("Failed to delete storage object of subtitles {}: {}", subtitle.id, e);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+    use crate::entities::{group_user, groups};
+    use crate::services::group_service::GroupVisibility;
+    use super::*;
+
+    fn group(visibility: GroupVisibility) -> groups::Model {
+        groups::Model {
+            id: 1,
+            name: "film-club".to_string(),
+            password: None,
+            created_at: Utc::now().fixed_offset(),
+            is_deleted: false,
+            description: None,
+            visibility: visibility.as_str().to_string(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn tracks_of_hidden_groups_are_not_listed_for_outsiders() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![group(GroupVisibility::Hidden)]])
+            .append_query_results([Vec::<group_user::Model>::new()])
+            .into_connection();
+        let db = Arc::new(db);
+
+        let result = list_subtitles(&db, 1, 5, 30).await;
+
+        assert!(matches!(result, Err(AppError::NotFound(_))), "{:?}", result);
+        assert_eq!(Arc::try_unwrap(db).unwrap().into_transaction_log().len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn tracks_are_only_listed_for_videos_of_the_group() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![group(GroupVisibility::Public)]])
+            .append_query_results([Vec::<group_video::Model>::new()])
+            .into_connection();
+
+        let result = list_subtitles(&db, 1, 5, 30).await;
+
+        assert!(matches!(result, Err(AppError::NotFound(_))), "{:?}", result);
+    }
+
+    #[test]
+    fn srt_with_crlf_and_bom_is_converted() {
+        let srt = "\u{feff}1\r\n00:00:01,000 --> 00:00:02,500\r\nHello\r\nworld\r\n\r\n2\r\n00:01:02,003 --> 00:01:04,000\r\nBye\r\n";
+
+        assert_eq!(
+            to_webvtt(Some("movie.srt"), srt).unwrap(),
+            "WEBVTT\n\n1\n00:00:01.000 --> 00:00:02.500\nHello\nworld\n\n2\n00:01:02.003 --> 00:01:04.000\nBye\n"
+        );
+    }
+
+    #[test]
+    fn cue_numbers_are_optional() {
+        let srt = "00:00:01,000 --> 00:00:02,000\nFirst\n\n00:00:03,000 --> 00:00:04,000\nSecond\n";
+
+        assert_eq!(
+            srt_to_webvtt(srt).unwrap(),
+            "WEBVTT\n\n00:00:01.000 --> 00:00:02.000\nFirst\n\n00:00:03.000 --> 00:00:04.000\nSecond\n"
+        );
+    }
+
+    #[test]
+    fn positioning_after_the_end_time_is_dropped() {
+        let srt = "1\n00:00:01,000 --> 00:00:02,000 X1:40 X2:600 Y1:20 Y2:50\nPositioned\n";
+
+        assert_eq!(srt_to_webvtt(srt).unwrap(), "WEBVTT\n\n1\n00:00:01.000 --> 00:00:02.000\nPositioned\n");
+    }
+
+    #[test]
+    fn arrows_in_cue_text_are_defused() {
+        let srt = "1\n00:00:01,000 --> 00:00:02,000\nLeft --> right\n";
+
+        assert_eq!(srt_to_webvtt(srt).unwrap(), "WEBVTT\n\n1\n00:00:01.000 --> 00:00:02.000\nLeft -> right\n");
+    }
+
+    #[test]
+    fn malformed_timestamps_are_rejected() {
+        for timestamp in ["00:00:01", "00:00:01,5", "00:60:01,000", "00:00:60,000", "0:01,000", "aa:00:01,000", "00:00:01,0000"] {
+            assert_eq!(srt_timestamp(timestamp), None, "{}", timestamp);
+        }
+        assert_eq!(srt_timestamp(" 100:00:01.250 "), Some("100:00:01.250".to_string()));
+        assert_eq!(srt_timestamp("0:0:2,000"), Some("00:00:02.000".to_string()));
+
+        assert!(srt_to_webvtt("1\n00:00:01,000 -> 00:00:02,000\nNo arrow\n").is_none());
+        assert!(srt_to_webvtt("1\n00:00:01,000 --> 00:00:02,00x\nBad end\n").is_none());
+        assert!(srt_to_webvtt("1\n").is_none());
+        assert!(srt_to_webvtt("").is_none());
+    }
+
+    #[test]
+    fn webvtt_is_kept_and_srt_extensions_win() {
+        let webvtt = "WEBVTT - Title\r\n\r\n00:00:01.000 --> 00:00:02.000\r\nHi\r\n";
+        assert_eq!(to_webvtt(Some("track.vtt"), webvtt).unwrap(), "WEBVTT - Title\n\n00:00:01.000 --> 00:00:02.000\nHi\n");
+
+        assert!(to_webvtt(Some("track.SRT"), "WEBVTT\n\n00:00:01.000 --> 00:00:02.000\nHi\n").is_err());
+        assert!(to_webvtt(None, "not subtitles").is_err());
+    }
+
+    #[test]
+    fn language_tags_are_normalised() {
+        assert_eq!(parse_language("pt-BR").unwrap(), "pt-br");
+        assert_eq!(parse_language(" zh-Hant ").unwrap(), "zh-hant");
+        assert_eq!(parse_language("en").unwrap(), "en");
+        assert_eq!(parse_language("sr-Latn-RS").unwrap(), "sr-latn-rs");
+
+        for language in ["", "e", "english", "pt_BR", "pt-", "pt-B", "en-abcdefghi", "12"] {
+            assert!(parse_language(language).is_err(), "{}", language);
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::io::Write;
+use actix_web::web;
+use aws_sdk_s3 as s3;
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QueryFilter, QueryOrder, SqlErr, TransactionTrait};
+use sea_orm::ActiveValue::Set;
+use serde::{Deserialize, Serialize};
+use tempfile::TempPath;
+use crate::dtos::pagination_dto::{ListQuery, Page};
+use crate::dtos::video_transcript_dto::{TranscriptFilter, TranscriptSegmentResponse, TranscriptionJobResponse};
+use crate::entities::{group_video, transcript_segments, transcription_jobs, videos};
+use crate::errors::AppError;
+use crate::services::auth_service::UserClaims;
+use crate::services::{group_service, pagination_service, storage_service};
+use crate::services::transcription_service::{TranscriptSegment, TranscriptionEngine};
+
+// Keeps each insert well below Postgres' limit on bind parameters.
+const SEGMENT_BATCH_SIZE: usize = 1000;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptionStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+impl TranscriptionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TranscriptionStatus::Queued => "queued",
+            TranscriptionStatus::Running => "running",
+            TranscriptionStatus::Completed => "completed",
+            TranscriptionStatus::Failed => "failed",
+        }
+    }
+
+    pub fn from_db(status: &str) -> TranscriptionStatus {
+        match status {
+            "running" => TranscriptionStatus::Running,
+            "completed" => TranscriptionStatus::Completed,
+            "failed" => TranscriptionStatus::Failed,
+            _ => TranscriptionStatus::Queued,
+        }
+    }
+}
+
+fn to_job_response(job: transcription_jobs::Model) -> TranscriptionJobResponse {
+    TranscriptionJobResponse {
+        id: job.id,
+        video_id: job.video_id,
+        engine: job.engine,
+        status: TranscriptionStatus::from_db(&job.status),
+        error: job.error,
+        created_at: job.created_at.to_rfc3339(),
+        started_at: job.started_at.map(|started_at| started_at.to_rfc3339()),
+        finished_at: job.finished_at.map(|finished_at| finished_at.to_rfc3339()),
+    }
+}
+
+fn to_segment_response(segment: transcript_segments::Model) -> TranscriptSegmentResponse {
+    TranscriptSegmentResponse {
+        position: segment.position,
+        start_seconds: segment.start_seconds,
+        end_seconds: segment.end_seconds,
+        text: segment.text,
+    }
+}
+
+fn webvtt_timestamp(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!("{:02}:{:02}:{:02}.{:03}", millis / 3_600_000, millis / 60_000 % 60, millis / 1000 % 60, millis % 1000)
+}
+
+/// The transcript as a WebVTT caption track, one cue per segment.
+fn to_webvtt(segments: &[transcript_segments::Model]) -> String {
+    let mut webvtt = String::from("WEBVTT\n");
+
+    for segment in segments {
+        // Cue text can't contain blank lines or the cue timing arrow.
+        let text = segment.text.split_whitespace().collect::<Vec<&str>>().join(" ").replace("-->", "->");
+        webvtt.push_str(&format!(
+            "\n{}\n{} --> {}\n{}\n",
+            segment.position + 1, webvtt_timestamp(segment.start_seconds), webvtt_timestamp(segment.end_seconds), text
+        ));
+    }
+
+    webvtt
+}
+
+async fn require_group_video(db: &DatabaseConnection, group_id: i64, video_id: i64) -> Result<(), AppError> {
+    group_video::Entity::find()
+        .filter(group_video::Column::GroupId.eq(group_id))
+        .filter(group_video::Column::VideoId.eq(video_id))
+        .one(db)
+        .await?
+        .ok_or(AppError::not_found("Video not found!"))?;
+
+    Ok(())
+}
+
+/// The video's most recent transcription job, for the video detail.
+pub async fn latest_job(db: &DatabaseConnection, video_id: i64) -> Result<Option<TranscriptionJobResponse>, AppError> {
+    Ok(transcription_jobs::Entity::find()
+        .filter(transcription_jobs::Column::VideoId.eq(video_id))
+        .order_by_desc(transcription_jobs::Column::CreatedAt)
+        .order_by_desc(transcription_jobs::Column::Id)
+        .one(db)
+        .await?
+        .map(to_job_response))
+}
+
+async fn finish_job(db: &DatabaseConnection, job: transcription_jobs::Model, error: Option<String>) -> Result<(), AppError> {
+    let status = match error {
+        Some(_) => TranscriptionStatus::Failed,
+        None => TranscriptionStatus::Completed,
+    };
+
+    let mut job = job.into_active_model();
+    job.status = Set(status.as_str().to_string());
+    job.error = Set(error);
+    job.finished_at = Set(Some(Utc::now().fixed_offset()));
+    job.update(db).await?;
+
+    Ok(())
+}
+
+/// Replaces the video's transcript with the new segments.
+async fn save_segments(db: &DatabaseConnection, video_id: i64, segments: Vec<TranscriptSegment>) -> Result<(), AppError> {
+    let transaction = db.begin().await?;
+
+    transcript_segments::Entity::delete_many()
+        .filter(transcript_segments::Column::VideoId.eq(video_id))
+        .exec(&transaction)
+        .await?;
+
+    let segments: Vec<transcript_segments::ActiveModel> = segments.into_iter().enumerate()
+        .map(|(position, segment)| transcript_segments::ActiveModel {
+            video_id: Set(video_id),
+            position: Set(position as i32),
+            start_seconds: Set(segment.start_seconds),
+            end_seconds: Set(segment.end_seconds.max(segment.start_seconds)),
+            text: Set(segment.text),
+            ..Default::default()
+        })
+        .collect();
+
+    for batch in segments.chunks(SEGMENT_BATCH_SIZE) {
+        transcript_segments::Entity::insert_many(batch.to_vec()).exec(&transaction).await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+async fn run_job(db: &DatabaseConnection, engine: &dyn TranscriptionEngine, job: transcription_jobs::Model, media: TempPath) -> Result<(), AppError> {
+    let mut running = job.into_active_model();
+    running.status = Set(TranscriptionStatus::Running.as_str().to_string());
+    running.started_at = Set(Some(Utc::now().fixed_offset()));
+    let job = running.update(db).await?;
+
+    let transcribed = engine.transcribe(&media).await;
+    drop(media);
+
+    match transcribed {
+        Ok(segments) => match save_segments(db, job.video_id, segments).await {
+            Ok(()) => finish_job(db, job, None).await,
+            Err(e) => finish_job(db, job, Some(format!("failed to save the transcript: {}", e))).await,
+        },
+        Err(e) => finish_job(db, job, Some(e.to_string())).await,
+    }
+}
+
+/// Queues a job for the video and runs it in the background, the media file is deleted once the engine is done with it.
+pub async fn queue_transcription(
+    db: web::Data<DatabaseConnection>,
+    engine: web::Data<dyn TranscriptionEngine>,
+    video_id: i64,
+    media: TempPath,
+) -> Result<transcription_jobs::Model, AppError> {
+    let job = transcription_jobs::ActiveModel {
+        video_id: Set(video_id),
+        engine: Set(engine.name().to_string()),
+        status: Set(TranscriptionStatus::Queued.as_str().to_string()),
+        created_at: Set(Utc::now().fixed_offset()),
+        ..Default::default()
+    };
+
+    let job = job.insert(db.get_ref()).await.map_err(|e| match e.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(_)) => AppError::conflict("This video is already being transcribed!"),
+        _ => e.into(),
+    })?;
+
+    let queued = job.clone();
+    actix_web::rt::spawn(async move {
+        let job_id = queued.id;
+        if let Err(e) = run_job(&db, engine.get_ref(), queued, media).await {
+            log::error!("Transcription job {} failed to run: {}", job_id, e);
+        }
+    });
+
+    Ok(job)
+}
+
+/// Jobs run in memory, the ones a restart cut short would otherwise stay queued or running forever.
+pub async fn fail_interrupted_jobs(db: &DatabaseConnection) -> Result<(), AppError> {
+    transcription_jobs::Entity::update_many()
+        .col_expr(transcription_jobs::Column::Status, TranscriptionStatus::Failed.as_str().into())
+        .col_expr(transcription_jobs::Column::Error, Some("Interrupted by a server restart.").into())
+        .col_expr(transcription_jobs::Column::FinishedAt, Some(Utc::now().fixed_offset()).into())
+        .filter(transcription_jobs::Column::Status.is_in([TranscriptionStatus::Queued.as_str(), TranscriptionStatus::Running.as_str()]))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Transcribes the video again from storage, e.g. after a failed job or a change of engine.
+pub async fn retranscribe(
+    db: web::Data<DatabaseConnection>,
+    client: web::Data<s3::Client>,
+    engine: web::Data<dyn TranscriptionEngine>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<TranscriptionJobResponse, AppError> {
+    let (group_id, video_id) = path.into_inner();
+
+    group_service::require_manager(&db, group_id, user_claims.id).await?;
+    require_group_video(&db, group_id, video_id).await?;
+
+    let video = videos::Entity::find_by_id(video_id)
+        .one(db.get_ref())
+        .await?
+        .ok_or(AppError::not_found("Video not found!"))?;
+
+    let contents = storage_service::get_object(&client, &video.key).await?;
+
+    let mut media = tempfile::NamedTempFile::new()
+        .map_err(|e| AppError::internal(format!("failed to create a temporary file: {:?}", e)))?;
+    media.write_all(&contents)
+        .map_err(|e| AppError::internal(format!("failed to write the video to a temporary file: {:?}", e)))?;
+
+    let job = queue_transcription(db, engine, video.id, media.into_temp_path()).await?;
+
+    Ok(to_job_response(job))
+}
+
+/// The transcript in spoken order, `sort` doesn't apply here.
+pub async fn list_segments(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+    filter: web::Query<TranscriptFilter>,
+    query: web::Query<ListQuery>,
+) -> Result<Page<TranscriptSegmentResponse>, AppError> {
+    let db = db.get_ref();
+    let (group_id, video_id) = path.into_inner();
+
+    group_service::require_viewer(db, group_id, user_claims.id).await?;
+    require_group_video(db, group_id, video_id).await?;
+
+    let mut select = transcript_segments::Entity::find()
+        .filter(transcript_segments::Column::VideoId.eq(video_id));
+
+    if let Some(q) = filter.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        select = select.filter(pagination_service::search(transcript_segments::Column::Text, q));
+    }
+
+    let segments = pagination_service::paginate(select.order_by_asc(transcript_segments::Column::Position), db, &query).await?;
+
+    Ok(segments.map(to_segment_response))
+}
+
+/// The transcript as WebVTT captions, readable by whoever can see the group's videos.
+pub async fn serve_captions(
+    db: web::Data<DatabaseConnection>,
+    path: web::Path<(i64, i64)>,
+    user_claims: UserClaims,
+) -> Result<String, AppError> {
+    let db = db.get_ref();
+    let (group_id, video_id) = path.into_inner();
+
+    group_service::require_viewer(db, group_id, user_claims.id).await?;
+    require_group_video(db, group_id, video_id).await?;
+
+    let segments = transcript_segments::Entity::find()
+        .filter(transcript_segments::Column::VideoId.eq(video_id))
+        .order_by_asc(transcript_segments::Column::Position)
+        .all(db)
+        .await?;
+
+    if segments.is_empty() {
+        return Err(AppError::not_found("Transcript not found!"));
+    }
+
+    Ok(to_webvtt(&segments))
+}